@@ -0,0 +1,151 @@
+//! Async adapters for tokio, enabled with the `tokio` feature.
+//!
+//! A document is encoded into memory on the calling task and then written
+//! asynchronously. Decoding reads the whole input asynchronously, so it holds
+//! the input in memory, and then parses it on tokio's blocking thread pool
+//! so that a large document does not stall other tasks. For streams of
+//! messages, [`CompactCodec`] frames compact documents for `tokio_util`'s
+//! `FramedRead` and `FramedWrite`, in the same framing as
+//! [`DocumentReader`](crate::DocumentReader).
+
+use crate::{decoder, encoder, DecodeOptions, EncodeOptions};
+use anyhow::{Context, Result};
+use bytes::{Buf, BufMut, BytesMut};
+use serde_json::Value;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio_util::codec::{Decoder, Encoder};
+
+/// Encode a JSON value to TOON and write it to an async writer, then flush.
+pub async fn encode_to_async_writer<W: AsyncWrite + Unpin>(
+    mut writer: W,
+    input: &Value,
+    opt: &EncodeOptions,
+) -> Result<()> {
+    let bytes = encoder::encode(input, opt)?;
+    writer
+        .write_all(&bytes)
+        .await
+        .context("Failed to write TOON output")?;
+    writer
+        .flush()
+        .await
+        .context("Failed to write TOON output")?;
+    Ok(())
+}
+
+/// Read an async reader to the end and decode it as TOON, detecting the
+/// format like [`crate::decode_toon_to_json`]. The input is held in memory
+/// while it is decoded, with [`tokio::task::spawn_blocking`], so this must be
+/// called within a tokio runtime.
+pub async fn decode_from_async_reader<R: AsyncRead + Unpin>(
+    mut reader: R,
+    opt: &DecodeOptions,
+) -> Result<Value> {
+    let mut bytes = Vec::new();
+    reader
+        .read_to_end(&mut bytes)
+        .await
+        .context("Failed to read TOON input")?;
+    let opt = opt.clone();
+    tokio::task::spawn_blocking(move || decoder::decode(&bytes, &opt))
+        .await
+        .context("TOON decoding task failed")?
+}
+
+/// Length of the u32 little-endian prefix of each frame.
+const FRAME_HEADER_LEN: usize = 4;
+
+/// `tokio_util` codec for compact TOON messages, each framed by its byte
+/// length as a u32 little-endian.
+#[derive(Clone, Debug)]
+pub struct CompactCodec {
+    max_frame_length: usize,
+}
+
+impl CompactCodec {
+    /// Frame lengths above this are rejected unless raised with
+    /// [`with_max_frame_length`](Self::with_max_frame_length).
+    pub const DEFAULT_MAX_FRAME_LENGTH: usize = 8 * 1024 * 1024;
+
+    pub fn new() -> Self {
+        CompactCodec {
+            max_frame_length: Self::DEFAULT_MAX_FRAME_LENGTH,
+        }
+    }
+
+    /// Reject frames longer than `max` bytes, in both directions, so a
+    /// corrupt or hostile length cannot make the decoder buffer without end.
+    pub fn with_max_frame_length(mut self, max: usize) -> Self {
+        self.max_frame_length = max;
+        self
+    }
+}
+
+impl Default for CompactCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for CompactCodec {
+    type Item = Value;
+    type Error = anyhow::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Value>> {
+        if src.len() < FRAME_HEADER_LEN {
+            return Ok(None);
+        }
+        let len = u32::from_le_bytes(src[..FRAME_HEADER_LEN].try_into().unwrap()) as usize;
+        if len > self.max_frame_length {
+            anyhow::bail!(
+                "Frame of {} bytes exceeds the maximum of {} bytes",
+                len,
+                self.max_frame_length
+            );
+        }
+        if src.len() < FRAME_HEADER_LEN + len {
+            src.reserve(FRAME_HEADER_LEN + len - src.len());
+            return Ok(None);
+        }
+
+        src.advance(FRAME_HEADER_LEN);
+        let frame = src.split_to(len);
+        let opt = DecodeOptions {
+            compact: true,
+            ..Default::default()
+        };
+        decoder::decode(&frame, &opt).map(Some)
+    }
+}
+
+impl Encoder<&Value> for CompactCodec {
+    type Error = anyhow::Error;
+
+    fn encode(&mut self, item: &Value, dst: &mut BytesMut) -> Result<()> {
+        let opt = EncodeOptions {
+            compact: true,
+            ..Default::default()
+        };
+        let bytes = encoder::encode(item, &opt)?;
+        if bytes.len() > self.max_frame_length {
+            anyhow::bail!(
+                "Frame of {} bytes exceeds the maximum of {} bytes",
+                bytes.len(),
+                self.max_frame_length
+            );
+        }
+
+        dst.reserve(FRAME_HEADER_LEN + bytes.len());
+        dst.put_u32_le(bytes.len() as u32);
+        dst.extend_from_slice(&bytes);
+        Ok(())
+    }
+}
+
+impl Encoder<Value> for CompactCodec {
+    type Error = anyhow::Error;
+
+    fn encode(&mut self, item: Value, dst: &mut BytesMut) -> Result<()> {
+        self.encode(&item, dst)
+    }
+}
@@ -3,7 +3,12 @@ use clap::{Parser, Subcommand};
 use std::fs;
 use std::io::{self, Read, Write};
 use std::path::PathBuf;
-use toon::{decode_toon_to_json, encode_json_to_toon, DecodeOptions, EncodeOptions};
+use toon::diff::DiffOptions;
+use toon::schema::{InferOptions, Schema, SchemaInferrer};
+use toon::{
+    decode_from_reader, encode_to_writer, format_toon, DecodeOptions, EncodeOptions, Indent,
+    Query, QuotePolicy,
+};
 
 #[derive(Parser)]
 #[command(name = "toon")]
@@ -29,17 +34,45 @@ pub enum Commands {
         #[arg(long)]
         tabular_arrays: bool,
 
+        /// Annotate tabular columns that hold one type with it (# id:int, name:str)
+        #[arg(long, requires = "tabular_arrays")]
+        typed_columns: bool,
+
         /// Use compact binary format
         #[arg(long)]
         compact: bool,
 
-        /// Indentation in spaces (default: 2)
+        /// Add offset tables to large arrays and objects for random access (with --compact)
+        #[arg(long, requires = "compact")]
+        index: bool,
+
+        /// Indentation: a number of spaces or 'tab' (default: 2)
         #[arg(long)]
-        indent: Option<u8>,
+        indent: Option<Indent>,
+
+        /// Maximum line width; fit small arrays and objects on one line
+        #[arg(long)]
+        max_width: Option<usize>,
+
+        /// When to quote strings: minimal, always, non-ascii or ascii
+        #[arg(long, default_value = "minimal")]
+        quoting: QuotePolicy,
 
         /// Fail on validation errors
         #[arg(long)]
         strict: bool,
+
+        /// Read newline-delimited JSON and encode the records as one array
+        #[arg(long)]
+        ndjson: bool,
+
+        /// Fail if the input does not match this JSON Schema (each record with --ndjson)
+        #[arg(long)]
+        schema: Option<PathBuf>,
+
+        /// Store the schema in the output and leave out the type tags it fixes (with --compact)
+        #[arg(long, requires_all = ["schema", "compact"], conflicts_with = "ndjson")]
+        embed_schema: bool,
     },
 
     /// Decode TOON to JSON format
@@ -54,6 +87,120 @@ pub enum Commands {
         /// Fail on validation errors
         #[arg(long)]
         strict: bool,
+
+        /// Write one JSON value per line, one for each element of a top-level array
+        #[arg(long)]
+        ndjson: bool,
+
+        /// Fail if the document does not match this JSON Schema (each line with --ndjson)
+        #[arg(long)]
+        schema: Option<PathBuf>,
+    },
+
+    /// Print the value at a JSON Pointer or JSONPath in a TOON document
+    Get {
+        /// JSON Pointer (/users/0/name) or JSONPath ($.users[*].name)
+        path: Query,
+
+        /// Input TOON file (stdin if not provided)
+        input: Option<PathBuf>,
+
+        /// Output file (stdout if not provided)
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+
+        /// Print JSON instead of TOON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Apply a JSON Patch or merge patch to a TOON document, keeping its format
+    Patch {
+        /// Patch file, in JSON or TOON
+        patch: PathBuf,
+
+        /// Input TOON file (stdin if not provided)
+        input: Option<PathBuf>,
+
+        /// Output file (stdout if not provided)
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+
+        /// Treat the patch as an RFC 7396 merge patch instead of RFC 6902 operations
+        #[arg(long)]
+        merge: bool,
+
+        /// Rewrite the input file instead of writing to stdout
+        #[arg(short, long, requires = "input", conflicts_with = "out")]
+        in_place: bool,
+    },
+
+    /// Show the structural differences between two TOON documents
+    Diff {
+        /// Original TOON file
+        old: PathBuf,
+
+        /// Changed TOON file
+        new: PathBuf,
+
+        /// Match elements of arrays of objects, such as tabular rows, by this column
+        #[arg(long)]
+        key: Option<String>,
+
+        /// Print the differences as a JSON Patch
+        #[arg(long)]
+        patch: bool,
+
+        /// Output file (stdout if not provided)
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+    },
+
+    /// Check TOON documents against a JSON Schema
+    Validate {
+        /// JSON Schema file, in JSON or TOON
+        #[arg(long)]
+        schema: PathBuf,
+
+        /// Input TOON files (stdin if none)
+        inputs: Vec<PathBuf>,
+    },
+
+    /// Work with JSON Schemas of TOON documents
+    Schema {
+        #[command(subcommand)]
+        command: SchemaCommand,
+    },
+
+    /// Rewrite TOON text files in canonical form
+    Fmt {
+        /// Input TOON files, rewritten in place (stdin to stdout if none)
+        inputs: Vec<PathBuf>,
+
+        /// Exit with an error if any input is not canonical, without writing
+        #[arg(long)]
+        check: bool,
+
+        /// Indentation: a number of spaces or 'tab'
+        #[arg(long, default_value = "2")]
+        indent: Indent,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum SchemaCommand {
+    /// Infer a JSON Schema that all the given documents satisfy
+    Infer {
+        /// Sample TOON files (stdin if none)
+        inputs: Vec<PathBuf>,
+
+        /// Output file (stdout if not provided)
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+
+        /// Most distinct values of a string field to list as an enum
+        #[arg(long, default_value = "10")]
+        enum_limit: usize,
     },
 }
 
@@ -63,48 +210,274 @@ pub fn run(cli: Cli) -> Result<()> {
             input,
             out,
             tabular_arrays,
+            typed_columns,
             compact,
+            index,
             indent,
+            max_width,
+            quoting,
             strict,
+            ndjson,
+            schema,
+            embed_schema,
         } => {
-            let input_data = read_input(input.as_deref())?;
-            let json: serde_json::Value = serde_json::from_slice(&input_data)
-                .context("Failed to parse input JSON")?;
-
+            let reader = io::BufReader::new(open_input(input.as_deref())?);
             let options = EncodeOptions {
                 tabular_arrays,
+                typed_columns,
                 compact,
+                compact_index: index,
                 indent,
+                max_width,
+                quoting,
                 strict,
+                schema: schema.as_deref().map(load_schema).transpose()?,
+                embed_schema,
             };
 
-            let toon_data = encode_json_to_toon(&json, &options)
-                .context("Failed to encode JSON to TOON")?;
+            let mut output = Output::create(out.as_deref())?;
+            if ndjson {
+                toon::ndjson::encode(reader, &mut output, &options)
+                    .context("Failed to encode NDJSON to TOON")?;
+                return output.finish();
+            }
 
-            write_output(out.as_deref(), &toon_data)?;
-            Ok(())
+            let json: serde_json::Value =
+                serde_json::from_reader(reader).context("Failed to parse input JSON")?;
+            encode_to_writer(&mut output, &json, &options)
+                .context("Failed to encode JSON to TOON")?;
+            output.finish()
         }
 
-        Commands::Decode { input, out, strict } => {
-            let input_data = read_input(input.as_deref())?;
-
+        Commands::Decode {
+            input,
+            out,
+            strict,
+            ndjson,
+            schema,
+        } => {
             let options = DecodeOptions {
                 compact: false, // Auto-detect
                 strict,
+                schema: schema.as_deref().map(load_schema).transpose()?,
             };
 
-            let json = decode_toon_to_json(&input_data, &options)
+            if ndjson {
+                let mut output = Output::create(out.as_deref())?;
+                toon::ndjson::decode(open_input(input.as_deref())?, &mut output, &options)
+                    .context("Failed to decode TOON to NDJSON")?;
+                return output.finish();
+            }
+
+            let json = decode_from_reader(open_input(input.as_deref())?, &options)
                 .context("Failed to decode TOON to JSON")?;
 
-            let json_str = serde_json::to_string_pretty(&json)
+            let mut output = Output::create(out.as_deref())?;
+            serde_json::to_writer_pretty(&mut output, &json)
+                .context("Failed to serialize JSON")?;
+            output.finish()
+        }
+
+        Commands::Get {
+            path,
+            input,
+            out,
+            json,
+        } => {
+            let input_data = read_input(input.as_deref())?;
+            let mut selected =
+                toon::query::select_document(&input_data, &path, &DecodeOptions::default())
+                    .context("Failed to query TOON document")?;
+
+            // A singular path prints its value; other paths print the list of matches
+            let result = if path.is_singular() {
+                match selected.pop() {
+                    Some(value) => value,
+                    None => anyhow::bail!("No value at {}", path),
+                }
+            } else {
+                serde_json::Value::Array(selected)
+            };
+
+            let mut output = Output::create(out.as_deref())?;
+            if json {
+                serde_json::to_writer_pretty(&mut output, &result)
+                    .context("Failed to serialize JSON")?;
+            } else {
+                encode_to_writer(&mut output, &result, &EncodeOptions::default())
+                    .context("Failed to encode TOON")?;
+            }
+            output.write_all(b"\n").context("Failed to write output")?;
+            output.finish()
+        }
+
+        Commands::Patch {
+            patch,
+            input,
+            out,
+            merge,
+            in_place,
+        } => {
+            let patch_value = read_json_or_toon(&patch)
+                .with_context(|| format!("Failed to parse patch {:?} as JSON or TOON", patch))?;
+            let patch = if merge {
+                toon::Patch::Merge(patch_value)
+            } else {
+                toon::Patch::Json(patch_value)
+            };
+
+            let input_data = read_input(input.as_deref())?;
+            let patched =
+                toon::patch::patch_document(&input_data, &patch, &DecodeOptions::default())
+                    .context("Failed to patch TOON document")?;
+            let out = if in_place { input } else { out };
+            write_output(out.as_deref(), &patched)
+        }
+
+        Commands::Diff {
+            old,
+            new,
+            key,
+            patch,
+            out,
+        } => {
+            let decode = |path: &std::path::Path| {
+                toon::decode_toon_to_json(&read_input(Some(path))?, &DecodeOptions::default())
+                    .with_context(|| format!("Failed to decode {:?}", path))
+            };
+            let changes = toon::diff::diff(&decode(&old)?, &decode(&new)?, &DiffOptions { key });
+
+            let mut output = Output::create(out.as_deref())?;
+            if patch {
+                serde_json::to_writer_pretty(&mut output, &toon::diff::to_patch(&changes))
+                    .context("Failed to serialize JSON")?;
+                writeln!(output).context("Failed to write output")?;
+            } else {
+                for change in &changes {
+                    writeln!(output, "{}", change).context("Failed to write output")?;
+                }
+            }
+            output.finish()
+        }
+
+        Commands::Validate { schema, inputs } => {
+            let schema = load_schema(&schema)?;
+            let documents: Vec<Option<&std::path::Path>> = if inputs.is_empty() {
+                vec![None]
+            } else {
+                inputs.iter().map(|p| Some(p.as_path())).collect()
+            };
+
+            let mut output = io::BufWriter::new(io::stdout().lock());
+            let mut invalid = 0;
+            for path in &documents {
+                let name = path.map_or("<stdin>".into(), |p| p.display().to_string());
+                let doc = toon::decode_toon_to_json(&read_input(*path)?, &DecodeOptions::default())
+                    .with_context(|| format!("Failed to decode {}", name))?;
+                let violations = schema.validate(&doc);
+                if violations.is_empty() {
+                    writeln!(output, "{}: valid", name).context("Failed to write output")?;
+                    continue;
+                }
+                invalid += 1;
+                for violation in &violations {
+                    writeln!(output, "{}: {}", name, violation).context("Failed to write output")?;
+                }
+            }
+            output.flush().context("Failed to write output")?;
+
+            if invalid > 0 {
+                anyhow::bail!(
+                    "{} of {} documents do not match the schema",
+                    invalid,
+                    documents.len()
+                );
+            }
+            Ok(())
+        }
+
+        Commands::Schema {
+            command:
+                SchemaCommand::Infer {
+                    inputs,
+                    out,
+                    enum_limit,
+                },
+        } => {
+            let mut inferrer = SchemaInferrer::new(InferOptions { enum_limit });
+            let samples: Vec<Option<&std::path::Path>> = if inputs.is_empty() {
+                vec![None]
+            } else {
+                inputs.iter().map(|p| Some(p.as_path())).collect()
+            };
+            for path in samples {
+                let doc = toon::decode_toon_to_json(&read_input(path)?, &DecodeOptions::default())
+                    .with_context(|| {
+                        format!("Failed to decode {:?}", path.unwrap_or("<stdin>".as_ref()))
+                    })?;
+                inferrer.observe(&doc);
+            }
+
+            let mut output = Output::create(out.as_deref())?;
+            serde_json::to_writer_pretty(&mut output, &inferrer.schema())
                 .context("Failed to serialize JSON")?;
+            writeln!(output).context("Failed to write output")?;
+            output.finish()
+        }
 
-            write_output(out.as_deref(), json_str.as_bytes())?;
+        Commands::Fmt {
+            inputs,
+            check,
+            indent,
+        } => {
+            if inputs.is_empty() {
+                let input_data = read_input(None)?;
+                let formatted = format_toon(&input_data, indent).context("Failed to format TOON")?;
+                if check {
+                    if formatted != input_data {
+                        anyhow::bail!("<stdin> is not in canonical form");
+                    }
+                    return Ok(());
+                }
+                return write_output(None, &formatted);
+            }
+
+            let mut unformatted = Vec::new();
+            for path in &inputs {
+                let input_data = read_input(Some(path))?;
+                let formatted = format_toon(&input_data, indent)
+                    .with_context(|| format!("Failed to format {:?}", path))?;
+                if formatted == input_data {
+                    continue;
+                }
+                if check {
+                    unformatted.push(path.display().to_string());
+                } else {
+                    write_output(Some(path), &formatted)?;
+                }
+            }
+
+            if !unformatted.is_empty() {
+                anyhow::bail!("Not in canonical form: {}", unformatted.join(", "));
+            }
             Ok(())
         }
     }
 }
 
+/// Parse a file holding JSON, or TOON in any format.
+fn read_json_or_toon(path: &std::path::Path) -> Result<serde_json::Value> {
+    let data = read_input(Some(path))?;
+    serde_json::from_slice(&data)
+        .or_else(|_| toon::decode_toon_to_json(&data, &DecodeOptions::default()))
+}
+
+fn load_schema(path: &std::path::Path) -> Result<Schema> {
+    read_json_or_toon(path)
+        .and_then(Schema::new)
+        .with_context(|| format!("Failed to load schema {:?}", path))
+}
+
 fn read_input(path: Option<&std::path::Path>) -> Result<Vec<u8>> {
     match path {
         Some(p) => fs::read(p).with_context(|| format!("Failed to read file: {:?}", p)),
@@ -118,6 +491,17 @@ fn read_input(path: Option<&std::path::Path>) -> Result<Vec<u8>> {
     }
 }
 
+/// Open the input file, or stdin if no path is given, for streaming reads.
+fn open_input(path: Option<&std::path::Path>) -> Result<Box<dyn Read>> {
+    match path {
+        Some(p) => {
+            let file = fs::File::open(p).with_context(|| format!("Failed to read file: {:?}", p))?;
+            Ok(Box::new(file))
+        }
+        None => Ok(Box::new(io::stdin().lock())),
+    }
+}
+
 fn write_output(path: Option<&std::path::Path>, data: &[u8]) -> Result<()> {
     match path {
         Some(p) => fs::write(p, data).with_context(|| format!("Failed to write file: {:?}", p)),
@@ -129,3 +513,71 @@ fn write_output(path: Option<&std::path::Path>, data: &[u8]) -> Result<()> {
         }
     }
 }
+
+/// Buffered streaming output to a file, or stdout if no path is given.
+///
+/// A file is written under a temporary name in the same directory and only
+/// renamed over the target by [`Output::finish`], so a command that fails
+/// part way, on bad input or a schema violation, leaves an existing file
+/// as it was.
+struct Output {
+    writer: io::BufWriter<Box<dyn Write>>,
+    /// The temporary file and the target it replaces
+    file: Option<(PathBuf, PathBuf)>,
+}
+
+impl Output {
+    fn create(path: Option<&std::path::Path>) -> Result<Self> {
+        let Some(p) = path else {
+            return Ok(Output {
+                writer: io::BufWriter::new(Box::new(io::stdout().lock())),
+                file: None,
+            });
+        };
+
+        let name = p.file_name().unwrap_or_default().to_string_lossy();
+        let temp = p.with_file_name(format!(".{}.{}.tmp", name, std::process::id()));
+        let file = fs::File::create(&temp)
+            .with_context(|| format!("Failed to create file: {:?}", temp))?;
+        if let Ok(meta) = fs::metadata(p) {
+            // Keep the permissions of the file being replaced
+            let _ = fs::set_permissions(&temp, meta.permissions());
+        }
+        Ok(Output {
+            writer: io::BufWriter::new(Box::new(file)),
+            file: Some((temp, p.to_path_buf())),
+        })
+    }
+
+    /// Flush everything written and put the file in place.
+    fn finish(mut self) -> Result<()> {
+        self.writer.flush().context("Failed to write output")?;
+        if let Some((temp, path)) = self.file.take() {
+            // Close the file before renaming it, which Windows requires
+            self.writer = io::BufWriter::new(Box::new(io::sink()));
+            if let Err(e) = fs::rename(&temp, &path) {
+                let _ = fs::remove_file(&temp);
+                return Err(e).with_context(|| format!("Failed to write file: {:?}", path));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl Drop for Output {
+    fn drop(&mut self) {
+        if let Some((temp, _)) = &self.file {
+            let _ = fs::remove_file(temp);
+        }
+    }
+}
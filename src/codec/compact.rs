@@ -1,129 +1,344 @@
+use super::embedded::Layout;
+use super::events::CompactParser;
+use super::input::Input;
+use super::tabular::TABULAR_MAGIC;
+use crate::schema::Schema;
 use anyhow::{Context, Result};
 use serde_json::Value;
+use std::io;
 
-const MAGIC: &[u8] = b"TOON\x01";
+pub(crate) const MAGIC: &[u8] = b"TOON\x01";
 
 // Type tags
-const TAG_NULL: u8 = 0;
-const TAG_FALSE: u8 = 1;
-const TAG_TRUE: u8 = 2;
-const TAG_NUMBER: u8 = 3;
-const TAG_STRING: u8 = 4;
-const TAG_ARRAY: u8 = 5;
-const TAG_OBJECT: u8 = 6;
+pub(crate) const TAG_NULL: u8 = 0;
+pub(crate) const TAG_FALSE: u8 = 1;
+pub(crate) const TAG_TRUE: u8 = 2;
+pub(crate) const TAG_NUMBER: u8 = 3;
+pub(crate) const TAG_STRING: u8 = 4;
+pub(crate) const TAG_ARRAY: u8 = 5;
+pub(crate) const TAG_OBJECT: u8 = 6;
+/// Raw bytes, written by the serde serializer for `serialize_bytes`; decoded
+/// to JSON as an array of numbers
+pub(crate) const TAG_BYTES: u8 = 7;
+/// Array or object followed by an offset table, written with
+/// [`encode_indexed`]
+pub(crate) const TAG_INDEXED_ARRAY: u8 = 8;
+pub(crate) const TAG_INDEXED_OBJECT: u8 = 9;
+/// Embedded schema section, right after the magic header: its byte size and
+/// the schema as a compact value. Written with [`encode_with_schema`]
+pub(crate) const TAG_SCHEMA: u8 = 10;
+
+/// Smallest container that [`encode_indexed`] gives an offset table.
+pub const INDEX_THRESHOLD: usize = 8;
 
 pub fn encode(value: &Value) -> Result<Vec<u8>> {
     let mut buf = Vec::new();
-    buf.extend_from_slice(MAGIC);
-    encode_value(&mut buf, value)?;
+    encode_to_writer(&mut buf, value)?;
     Ok(buf)
 }
 
-fn encode_value(buf: &mut Vec<u8>, value: &Value) -> Result<()> {
+/// Encode into a writer, writing each value as it is produced.
+pub fn encode_to_writer<W: io::Write>(out: &mut W, value: &Value) -> Result<()> {
+    out.write_all(MAGIC)?;
+    encode_value(out, value)
+}
+
+/// Encode with an offset table in every array and object of at least
+/// [`INDEX_THRESHOLD`] entries, so that readers such as
+/// [`CompactView`](super::view::CompactView) can jump to an element or a key
+/// without reading the entries before it.
+///
+/// An indexed container is its tag, the entry count, the byte size of the
+/// entries, one u32 offset per entry relative to the first entry, and then
+/// the entries as usual. Object keys are sorted by their bytes.
+pub fn encode_indexed(value: &Value) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    encode_indexed_to_writer(&mut buf, value)?;
+    Ok(buf)
+}
+
+/// Encode with offset tables into a writer. Each indexed container is
+/// assembled in memory first, since its table comes before its entries.
+pub fn encode_indexed_to_writer<W: io::Write>(out: &mut W, value: &Value) -> Result<()> {
+    out.write_all(MAGIC)?;
+    encode_value_indexed(out, value)
+}
+
+fn encode_value_indexed<W: io::Write>(out: &mut W, value: &Value) -> Result<()> {
+    match value {
+        Value::Array(arr) if arr.len() >= INDEX_THRESHOLD => {
+            let entries = arr.iter().map(|item| (None, item));
+            write_indexed(out, TAG_INDEXED_ARRAY, entries, |buf, _, _, value| {
+                encode_value_indexed(buf, value)
+            })
+        }
+        Value::Object(obj) if obj.len() >= INDEX_THRESHOLD => {
+            let mut keys: Vec<_> = obj.keys().collect();
+            keys.sort();
+            let entries = keys.into_iter().map(|key| (Some(key.as_str()), &obj[key]));
+            write_indexed(out, TAG_INDEXED_OBJECT, entries, |buf, _, _, value| {
+                encode_value_indexed(buf, value)
+            })
+        }
+        Value::Array(arr) => {
+            out.write_all(&[TAG_ARRAY])?;
+            write_u32(out, arr.len() as u32)?;
+            for item in arr {
+                encode_value_indexed(out, item)?;
+            }
+            Ok(())
+        }
+        Value::Object(obj) => {
+            out.write_all(&[TAG_OBJECT])?;
+            write_u32(out, obj.len() as u32)?;
+            let mut keys: Vec<_> = obj.keys().collect();
+            keys.sort();
+            for key in keys {
+                write_string(out, key)?;
+                encode_value_indexed(out, &obj[key])?;
+            }
+            Ok(())
+        }
+        _ => encode_value(out, value),
+    }
+}
+
+/// Write an indexed container, encoding the value of entry `i` with
+/// `encode_entry(body, i, key, value)`.
+fn write_indexed<'a, W: io::Write>(
+    out: &mut W,
+    tag: u8,
+    entries: impl ExactSizeIterator<Item = (Option<&'a str>, &'a Value)>,
+    mut encode_entry: impl FnMut(&mut Vec<u8>, usize, Option<&str>, &Value) -> Result<()>,
+) -> Result<()> {
+    let mut offsets = Vec::with_capacity(entries.len());
+    let mut body = Vec::new();
+    for (i, (key, value)) in entries.enumerate() {
+        offsets.push(u32::try_from(body.len()).context("Container too large to index")?);
+        if let Some(key) = key {
+            write_string(&mut body, key)?;
+        }
+        encode_entry(&mut body, i, key, value)?;
+    }
+
+    out.write_all(&[tag])?;
+    write_u32(out, offsets.len() as u32)?;
+    write_u32(out, u32::try_from(body.len()).context("Container too large to index")?)?;
+    for offset in offsets {
+        write_u32(out, offset)?;
+    }
+    out.write_all(&body)?;
+    Ok(())
+}
+
+/// Encode with `schema` embedded after the magic header, leaving out the
+/// type tag of every value whose subschema has a single `type` other than
+/// `boolean`. A null is then left out entirely.
+///
+/// The section is the tag 10, the u32 byte size of the schema and the
+/// schema as a compact value. Values must have the types the schema gives
+/// them; the schema is otherwise not checked here, which
+/// [`encode_json_to_toon`](crate::encode_json_to_toon) does before encoding.
+pub fn encode_with_schema(value: &Value, schema: &Value) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    encode_with_schema_to_writer(&mut buf, value, schema, false)?;
+    Ok(buf)
+}
+
+/// Encode with an embedded schema into a writer, giving containers the
+/// schema leaves tagged an offset table like [`encode_indexed`] when
+/// `indexed` is set. Containers without a tag are never indexed.
+pub fn encode_with_schema_to_writer<W: io::Write>(
+    out: &mut W,
+    value: &Value,
+    schema: &Value,
+    indexed: bool,
+) -> Result<()> {
+    let mut section = Vec::new();
+    encode_value(&mut section, schema)?;
+    out.write_all(MAGIC)?;
+    out.write_all(&[TAG_SCHEMA])?;
+    write_u32(out, u32::try_from(section.len()).context("Schema too large to embed")?)?;
+    out.write_all(&section)?;
+
+    let layout = Layout::new(&section, 0);
+    encode_value_typed(out, value, &layout, Some(0), indexed)
+}
+
+/// Encode a value that the subschema `node` describes.
+fn encode_value_typed<W: io::Write>(
+    out: &mut W,
+    value: &Value,
+    layout: &Layout,
+    node: Option<usize>,
+    indexed: bool,
+) -> Result<()> {
+    let Some(tag) = layout.tag(node) else {
+        return match value {
+            Value::Array(arr) if indexed && arr.len() >= INDEX_THRESHOLD => {
+                let entries = arr.iter().map(|item| (None, item));
+                write_indexed(out, TAG_INDEXED_ARRAY, entries, |buf, i, _, item| {
+                    encode_value_typed(buf, item, layout, layout.item(node, i), true)
+                })
+            }
+            Value::Object(obj) if indexed && obj.len() >= INDEX_THRESHOLD => {
+                let mut keys: Vec<_> = obj.keys().collect();
+                keys.sort();
+                let entries = keys.into_iter().map(|key| (Some(key.as_str()), &obj[key]));
+                write_indexed(out, TAG_INDEXED_OBJECT, entries, |buf, _, key, item| {
+                    let node = layout.property(node, key.unwrap_or_default());
+                    encode_value_typed(buf, item, layout, node, true)
+                })
+            }
+            Value::Array(_) | Value::Object(_) => {
+                let tag = if value.is_array() { TAG_ARRAY } else { TAG_OBJECT };
+                out.write_all(&[tag])?;
+                encode_children_typed(out, value, layout, node, indexed)
+            }
+            _ => encode_value(out, value),
+        };
+    };
+
+    match (tag, value) {
+        (TAG_NULL, Value::Null) => {}
+        (TAG_NUMBER, Value::Number(n)) => write_string(out, &n.to_string())?,
+        (TAG_STRING, Value::String(s)) => write_string(out, s)?,
+        (TAG_ARRAY, Value::Array(_)) | (TAG_OBJECT, Value::Object(_)) => {
+            encode_children_typed(out, value, layout, node, indexed)?
+        }
+        _ => anyhow::bail!("Value does not have the type the embedded schema gives it"),
+    }
+    Ok(())
+}
+
+/// Encode the count and entries of an array or object after its tag, if
+/// it has one.
+fn encode_children_typed<W: io::Write>(
+    out: &mut W,
+    value: &Value,
+    layout: &Layout,
+    node: Option<usize>,
+    indexed: bool,
+) -> Result<()> {
+    match value {
+        Value::Array(arr) => {
+            write_u32(out, arr.len() as u32)?;
+            for (i, item) in arr.iter().enumerate() {
+                encode_value_typed(out, item, layout, layout.item(node, i), indexed)?;
+            }
+        }
+        Value::Object(obj) => {
+            write_u32(out, obj.len() as u32)?;
+            let mut keys: Vec<_> = obj.keys().collect();
+            keys.sort();
+            for key in keys {
+                write_string(out, key)?;
+                let node = layout.property(node, key);
+                encode_value_typed(out, &obj[key], layout, node, indexed)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+pub(crate) fn encode_value<W: io::Write>(out: &mut W, value: &Value) -> Result<()> {
     match value {
-        Value::Null => buf.push(TAG_NULL),
-        Value::Bool(false) => buf.push(TAG_FALSE),
-        Value::Bool(true) => buf.push(TAG_TRUE),
+        Value::Null => out.write_all(&[TAG_NULL])?,
+        Value::Bool(false) => out.write_all(&[TAG_FALSE])?,
+        Value::Bool(true) => out.write_all(&[TAG_TRUE])?,
         Value::Number(n) => {
-            buf.push(TAG_NUMBER);
+            out.write_all(&[TAG_NUMBER])?;
             let s = n.to_string();
-            write_string(buf, &s);
+            write_string(out, &s)?;
         }
         Value::String(s) => {
-            buf.push(TAG_STRING);
-            write_string(buf, s);
+            out.write_all(&[TAG_STRING])?;
+            write_string(out, s)?;
         }
         Value::Array(arr) => {
-            buf.push(TAG_ARRAY);
-            write_u32(buf, arr.len() as u32);
+            out.write_all(&[TAG_ARRAY])?;
+            write_u32(out, arr.len() as u32)?;
             for item in arr {
-                encode_value(buf, item)?;
+                encode_value(out, item)?;
             }
         }
         Value::Object(obj) => {
-            buf.push(TAG_OBJECT);
-            write_u32(buf, obj.len() as u32);
-            
+            out.write_all(&[TAG_OBJECT])?;
+            write_u32(out, obj.len() as u32)?;
+
             // Sort keys for deterministic output
             let mut keys: Vec<_> = obj.keys().collect();
             keys.sort();
-            
+
             for key in keys {
-                write_string(buf, key);
-                encode_value(buf, &obj[key])?;
+                write_string(out, key)?;
+                encode_value(out, &obj[key])?;
             }
         }
     }
     Ok(())
 }
 
-fn write_u32(buf: &mut Vec<u8>, val: u32) {
-    buf.extend_from_slice(&val.to_le_bytes());
+pub(crate) fn write_u32<W: io::Write>(out: &mut W, val: u32) -> io::Result<()> {
+    out.write_all(&val.to_le_bytes())
+}
+
+pub(crate) fn write_string<W: io::Write>(out: &mut W, s: &str) -> io::Result<()> {
+    write_u32(out, s.len() as u32)?;
+    out.write_all(s.as_bytes())
 }
 
-fn write_string(buf: &mut Vec<u8>, s: &str) {
-    write_u32(buf, s.len() as u32);
-    buf.extend_from_slice(s.as_bytes());
+/// Parse a TOON-Compact or TOON-TAB document into events, reading from
+/// `reader` as the parser needs more input.
+pub fn events<R: io::Read>(reader: R) -> Result<CompactParser<R>> {
+    let mut input = Input::new(reader);
+    if input.starts_with(TABULAR_MAGIC)? {
+        input.consume(TABULAR_MAGIC.len());
+        return CompactParser::tabular(input);
+    }
+    if !input.starts_with(MAGIC)? {
+        anyhow::bail!("Invalid compact TOON magic header");
+    }
+    input.consume(MAGIC.len());
+    CompactParser::document(input)
+}
+
+/// The schema embedded in a compact document, read without decoding the
+/// rest of it. Documents without one, and TOON-TAB, give `None`.
+pub fn embedded_schema(bytes: &[u8]) -> Result<Option<Schema>> {
+    Ok(events(bytes)?.schema().cloned())
 }
 
 pub fn decode(bytes: &[u8]) -> Result<Value> {
     if bytes.len() < MAGIC.len() {
         anyhow::bail!("Input too short for compact TOON");
     }
-    if &bytes[..MAGIC.len()] != MAGIC {
+    if !bytes.starts_with(MAGIC) {
         anyhow::bail!("Invalid compact TOON magic header");
     }
 
-    let mut pos = MAGIC.len();
-    decode_value(bytes, &mut pos)
+    events(bytes)?
+        .next_value()?
+        .context("Unexpected end of input")
 }
 
-fn decode_value(bytes: &[u8], pos: &mut usize) -> Result<Value> {
-    if *pos >= bytes.len() {
-        anyhow::bail!("Unexpected end of input");
-    }
-
-    let tag = bytes[*pos];
-    *pos += 1;
-
-    match tag {
-        TAG_NULL => Ok(Value::Null),
-        TAG_FALSE => Ok(Value::Bool(false)),
-        TAG_TRUE => Ok(Value::Bool(true)),
-        TAG_NUMBER => {
-            let s = read_string(bytes, pos)?;
-            let n: serde_json::Number = s
-                .parse()
-                .with_context(|| format!("Invalid number in compact TOON: {}", s))?;
-            Ok(Value::Number(n))
-        }
-        TAG_STRING => {
-            let s = read_string(bytes, pos)?;
-            Ok(Value::String(s))
+/// Read the entry count of an array or object whose `tag` has been read,
+/// skipping the size and offset table of an indexed container.
+pub(crate) fn read_count(bytes: &[u8], pos: &mut usize, tag: u8) -> Result<u32> {
+    let count = read_u32(bytes, pos)?;
+    if tag == TAG_INDEXED_ARRAY || tag == TAG_INDEXED_OBJECT {
+        read_u32(bytes, pos)?;
+        let table = count as usize * 4;
+        if bytes.len() - *pos < table {
+            anyhow::bail!("Unexpected end of input reading offset table");
         }
-        TAG_ARRAY => {
-            let len = read_u32(bytes, pos)? as usize;
-            let mut arr = Vec::with_capacity(len);
-            for _ in 0..len {
-                arr.push(decode_value(bytes, pos)?);
-            }
-            Ok(Value::Array(arr))
-        }
-        TAG_OBJECT => {
-            let len = read_u32(bytes, pos)? as usize;
-            let mut obj = serde_json::Map::new();
-            for _ in 0..len {
-                let key = read_string(bytes, pos)?;
-                let value = decode_value(bytes, pos)?;
-                obj.insert(key, value);
-            }
-            Ok(Value::Object(obj))
-        }
-        _ => anyhow::bail!("Unknown type tag: {}", tag),
+        *pos += table;
     }
+    Ok(count)
 }
 
-fn read_u32(bytes: &[u8], pos: &mut usize) -> Result<u32> {
+pub(crate) fn read_u32(bytes: &[u8], pos: &mut usize) -> Result<u32> {
     if *pos + 4 > bytes.len() {
         anyhow::bail!("Unexpected end of input reading u32");
     }
@@ -137,7 +352,8 @@ fn read_u32(bytes: &[u8], pos: &mut usize) -> Result<u32> {
     Ok(val)
 }
 
-fn read_string(bytes: &[u8], pos: &mut usize) -> Result<String> {
+/// Read a length-prefixed string, borrowing it from the input.
+pub(crate) fn read_str<'a>(bytes: &'a [u8], pos: &mut usize) -> Result<&'a str> {
     let len = read_u32(bytes, pos)? as usize;
     if *pos + len > bytes.len() {
         anyhow::bail!("Unexpected end of input reading string");
@@ -145,5 +361,16 @@ fn read_string(bytes: &[u8], pos: &mut usize) -> Result<String> {
     let s = std::str::from_utf8(&bytes[*pos..*pos + len])
         .context("Invalid UTF-8 in string")?;
     *pos += len;
-    Ok(s.to_string())
+    Ok(s)
+}
+
+/// Read a length-prefixed byte string, borrowing it from the input.
+pub(crate) fn read_bytes<'a>(bytes: &'a [u8], pos: &mut usize) -> Result<&'a [u8]> {
+    let len = read_u32(bytes, pos)? as usize;
+    if *pos + len > bytes.len() {
+        anyhow::bail!("Unexpected end of input reading bytes");
+    }
+    let b = &bytes[*pos..*pos + len];
+    *pos += len;
+    Ok(b)
 }
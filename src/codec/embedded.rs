@@ -0,0 +1,102 @@
+//! Reading a compact document's embedded schema as a layout.
+//!
+//! A document written with
+//! [`encode_with_schema`](super::compact::encode_with_schema) stores its JSON
+//! Schema, itself encoded as a compact value, right after the magic header.
+//! Wherever a subschema's `type` is a single type other than `boolean`, the
+//! values it describes are written without their type tag: a null takes no
+//! bytes at all, a number or string is just its length-prefixed text, and an
+//! array or object is its count and entries. The writer and every reader
+//! walk the schema with [`Layout`] to agree on which values those are.
+//!
+//! The child schema of an object entry is `properties[key]`, or else an
+//! `additionalProperties` schema; that of an array element is
+//! `prefixItems[i]`, or else an `items` schema. A subschema with `$ref` is
+//! read as the subschema it points to. Anything else, such as `anyOf` or a
+//! list of types, leaves the values it describes tagged.
+
+use super::compact::{TAG_ARRAY, TAG_NULL, TAG_NUMBER, TAG_OBJECT, TAG_STRING};
+use super::view::CompactView;
+use crate::query::pointer_tokens;
+
+/// Longest chain of `$ref`s followed for one subschema, which stops a
+/// reference cycle such as `{"$ref": "#"}`.
+const MAX_REFS: usize = 32;
+
+/// Navigation of a validated compact schema. Subschemas are identified by
+/// the offset of their tag in `bytes`, and `None` stands for a value that
+/// the schema does not describe.
+#[derive(Clone, Copy)]
+pub(crate) struct Layout<'a> {
+    bytes: &'a [u8],
+    /// Offset of the root schema
+    root: usize,
+}
+
+impl<'a> Layout<'a> {
+    pub(crate) fn new(bytes: &'a [u8], root: usize) -> Self {
+        Layout { bytes, root }
+    }
+
+    fn view(&self, node: usize) -> CompactView<'a> {
+        CompactView::at(self.bytes, node)
+    }
+
+    /// The subschema at `node` with its `$ref`s followed.
+    fn resolve(&self, node: Option<usize>) -> Option<CompactView<'a>> {
+        let mut schema = self.view(node?);
+        for _ in 0..MAX_REFS {
+            let Some(reference) = schema.get("$ref") else {
+                return Some(schema);
+            };
+            schema = self.target(reference.as_str()?)?;
+        }
+        None
+    }
+
+    /// The subschema a `$ref` points to.
+    fn target(&self, reference: &str) -> Option<CompactView<'a>> {
+        let mut schema = self.view(self.root);
+        for token in pointer_tokens(reference.strip_prefix('#')?).ok()? {
+            schema = if schema.is_array() {
+                schema.index(token.parse().ok()?)?
+            } else {
+                schema.get(&token)?
+            };
+        }
+        Some(schema)
+    }
+
+    /// The tag every value at `node` has, when the schema fixes it and the
+    /// tag is therefore left out.
+    pub(crate) fn tag(&self, node: Option<usize>) -> Option<u8> {
+        match self.resolve(node)?.get("type")?.as_str()? {
+            "null" => Some(TAG_NULL),
+            "integer" | "number" => Some(TAG_NUMBER),
+            "string" => Some(TAG_STRING),
+            "array" => Some(TAG_ARRAY),
+            "object" => Some(TAG_OBJECT),
+            _ => None,
+        }
+    }
+
+    /// The subschema of the entry `key` of an object at `node`.
+    pub(crate) fn property(&self, node: Option<usize>, key: &str) -> Option<usize> {
+        let schema = self.resolve(node)?;
+        schema
+            .get("properties")
+            .and_then(|properties| properties.get(key))
+            .or_else(|| schema.get("additionalProperties").filter(|s| s.is_object()))
+            .map(|s| s.position())
+    }
+
+    /// The subschema of element `index` of an array at `node`.
+    pub(crate) fn item(&self, node: Option<usize>, index: usize) -> Option<usize> {
+        let schema = self.resolve(node)?;
+        schema
+            .get("prefixItems")
+            .and_then(|prefix| prefix.index(index))
+            .or_else(|| schema.get("items").filter(|s| s.is_object()))
+            .map(|s| s.position())
+    }
+}
@@ -0,0 +1,874 @@
+//! Pull parsers that read TOON from an `io::Read` one event at a time.
+//!
+//! Both formats are parsed into the same [`Event`] sequence: containers open
+//! and close around their contents, object entries start with a `Key`, and a
+//! tabular block is an array whose header and rows come as single events.
+//! The parsers are iterators, so a document can be walked like with a SAX
+//! parser without building a `serde_json::Value`:
+//!
+//! ```
+//! use toon::codec::events::Event;
+//!
+//! let mut keys = Vec::new();
+//! for event in toon::codec::text::events(&b"{id: 1, name: Ada}"[..]) {
+//!     if let Event::Key(key) = event? {
+//!         keys.push(key);
+//!     }
+//! }
+//! assert_eq!(keys, ["id", "name"]);
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use super::compact::{
+    MAGIC, TAG_ARRAY, TAG_BYTES, TAG_FALSE, TAG_INDEXED_ARRAY, TAG_INDEXED_OBJECT, TAG_NULL,
+    TAG_NUMBER, TAG_OBJECT, TAG_SCHEMA, TAG_STRING, TAG_TRUE,
+};
+use super::embedded::Layout;
+use super::input::Input;
+use super::tabular::{ColumnType, OPEN_ROW_COUNT, TABULAR_MAGIC};
+use super::text;
+use super::view::CompactView;
+use crate::schema::Schema;
+use crate::DecodeOptions;
+use anyhow::{Context, Result};
+use serde_json::{Map, Value};
+use std::fmt::Display;
+use std::io::Read;
+
+/// One step of a TOON document.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// Start of an object; its entries follow as `Key` and value pairs
+    StartObject,
+    /// Key of the next object entry
+    Key(String),
+    EndObject,
+    /// Start of an array, followed by its elements or by a `TabularHeader`
+    StartArray,
+    /// Column names of a tabular block. Follows `StartArray`; the elements
+    /// of the array then come as `Row`s. Column types of a typed header are
+    /// available from [`Parser::column_types`].
+    TabularHeader(Vec<String>),
+    /// One row of a tabular block, a cell per column
+    Row(Vec<Value>),
+    EndArray,
+    /// Null, a boolean, a number or a string
+    Scalar(Value),
+}
+
+/// A pull parser for either format, chosen from the start of the input.
+pub enum Parser<R> {
+    Text(TextParser<R>),
+    Compact(CompactParser<R>),
+}
+
+impl<R: Read> Parser<R> {
+    /// Detect the format from the first bytes of `reader`. Compact input is
+    /// recognised by its magic header; with `opt.compact` anything else is an
+    /// error, and with `opt.strict` text indentation must not mix tabs and
+    /// spaces and tabular cells must match their column types.
+    pub fn new(reader: R, opt: &DecodeOptions) -> Result<Self> {
+        let mut input = Input::new(reader);
+        if input.peek_byte()?.is_none() {
+            anyhow::bail!("Empty input");
+        }
+
+        if input.starts_with(TABULAR_MAGIC)? {
+            input.consume(TABULAR_MAGIC.len());
+            return CompactParser::tabular(input).map(Parser::Compact);
+        }
+        if input.starts_with(MAGIC)? {
+            input.consume(MAGIC.len());
+            return CompactParser::document(input).map(Parser::Compact);
+        }
+        if opt.compact {
+            anyhow::bail!("Invalid compact TOON magic header");
+        }
+
+        if opt.strict {
+            input.check_indentation();
+        }
+        let mut parser = TextParser::new(input);
+        parser.strict = opt.strict;
+        Ok(Parser::Text(parser))
+    }
+
+    /// The next event, or `None` after the end of the document.
+    pub fn next_event(&mut self) -> Result<Option<Event>> {
+        match self {
+            Parser::Text(p) => p.next_event(),
+            Parser::Compact(p) => p.next_event(),
+        }
+    }
+
+    /// Read the whole value that starts with the next event.
+    pub fn next_value(&mut self) -> Result<Option<Value>> {
+        match self {
+            Parser::Text(p) => p.next_value(),
+            Parser::Compact(p) => p.next_value(),
+        }
+    }
+
+    /// Column types of the innermost tabular block being read, from a text
+    /// header. TOON-TAB headers have no types.
+    pub fn column_types(&self) -> Option<&[Option<ColumnType>]> {
+        match self {
+            Parser::Text(p) => p.column_types(),
+            Parser::Compact(_) => None,
+        }
+    }
+
+    /// The schema embedded in a compact document, if it has one.
+    pub fn schema(&self) -> Option<&Schema> {
+        match self {
+            Parser::Text(_) => None,
+            Parser::Compact(p) => p.schema(),
+        }
+    }
+}
+
+impl<R: Read> Iterator for Parser<R> {
+    type Item = Result<Event>;
+
+    fn next(&mut self) -> Option<Result<Event>> {
+        self.next_event().transpose()
+    }
+}
+
+/// Build a value from its first event and the events that follow.
+pub fn build_value(first: Event, mut next: impl FnMut() -> Result<Option<Event>>) -> Result<Value> {
+    enum Partial {
+        Array(Vec<Value>),
+        Object(Map<String, Value>, Option<String>),
+        Tabular(Vec<String>, Vec<Value>),
+    }
+
+    let mut stack = Vec::new();
+    let mut event = first;
+    loop {
+        let value = match event {
+            Event::StartObject => {
+                stack.push(Partial::Object(Map::new(), None));
+                None
+            }
+            Event::StartArray => {
+                stack.push(Partial::Array(Vec::new()));
+                None
+            }
+            Event::TabularHeader(keys) => {
+                match stack.last_mut() {
+                    Some(top @ Partial::Array(_)) => *top = Partial::Tabular(keys, Vec::new()),
+                    _ => anyhow::bail!("Tabular header outside an array"),
+                }
+                None
+            }
+            Event::Row(cells) => match stack.last_mut() {
+                Some(Partial::Tabular(keys, rows)) => {
+                    rows.push(Value::Object(keys.iter().cloned().zip(cells).collect()));
+                    None
+                }
+                _ => anyhow::bail!("Tabular row outside a tabular block"),
+            },
+            Event::Key(key) => match stack.last_mut() {
+                Some(Partial::Object(_, pending)) => {
+                    *pending = Some(key);
+                    None
+                }
+                _ => anyhow::bail!("Key outside an object"),
+            },
+            Event::EndObject => match stack.pop() {
+                Some(Partial::Object(obj, _)) => Some(Value::Object(obj)),
+                _ => anyhow::bail!("Unbalanced end of object"),
+            },
+            Event::EndArray => match stack.pop() {
+                Some(Partial::Array(arr) | Partial::Tabular(_, arr)) => Some(Value::Array(arr)),
+                _ => anyhow::bail!("Unbalanced end of array"),
+            },
+            Event::Scalar(value) => Some(value),
+        };
+
+        if let Some(value) = value {
+            match stack.last_mut() {
+                None => return Ok(value),
+                Some(Partial::Array(arr) | Partial::Tabular(_, arr)) => arr.push(value),
+                Some(Partial::Object(obj, key)) => {
+                    let key = key.take().context("Value without a key in object")?;
+                    obj.insert(key, value);
+                }
+            }
+        }
+
+        event = next()?.context("Unexpected end of input")?;
+    }
+}
+
+/// Where the text parser is within the document.
+#[derive(Clone, Copy)]
+enum TextState {
+    /// A value starts next
+    Value,
+    /// An object key or the closing brace comes next
+    Key,
+    /// An array element or the closing bracket comes next
+    Element,
+    /// A tabular row or the closing bracket comes next
+    Row,
+    /// A value just ended; a separator or closing delimiter follows
+    AfterValue,
+    Done,
+}
+
+enum TextFrame {
+    Array,
+    Object,
+    /// A tabular block with the given column names and types
+    Tabular(Vec<String>, Vec<Option<ColumnType>>),
+}
+
+/// Pull parser for TOON-Text, created by [`text::events`].
+pub struct TextParser<R> {
+    input: Input<R>,
+    stack: Vec<TextFrame>,
+    state: TextState,
+    pending: Option<Event>,
+    /// Check tabular cells against their column types
+    strict: bool,
+    /// Byte offset of the key or value read last
+    token_start: u64,
+}
+
+impl<R: Read> TextParser<R> {
+    pub(crate) fn new(input: Input<R>) -> Self {
+        TextParser {
+            input,
+            stack: Vec::new(),
+            state: TextState::Value,
+            pending: None,
+            strict: false,
+            token_start: 0,
+        }
+    }
+
+    /// Byte offset where the last `Key`, or the value starting with the
+    /// last event, begins.
+    pub(crate) fn token_start(&self) -> u64 {
+        self.token_start
+    }
+
+    /// Byte offset just past the input read so far.
+    pub(crate) fn offset(&self) -> u64 {
+        self.input.offset()
+    }
+
+    /// Column types of the innermost tabular block being read.
+    pub fn column_types(&self) -> Option<&[Option<ColumnType>]> {
+        self.stack.iter().rev().find_map(|frame| match frame {
+            TextFrame::Tabular(_, types) => Some(types.as_slice()),
+            _ => None,
+        })
+    }
+
+    fn error(&self, msg: impl Display) -> anyhow::Error {
+        anyhow::anyhow!("{} at line {}", msg, self.input.line())
+    }
+
+    /// The next event, or `None` after the end of the document. After an
+    /// error, the parser returns `None`.
+    pub fn next_event(&mut self) -> Result<Option<Event>> {
+        let result = self.advance();
+        if result.is_err() {
+            self.state = TextState::Done;
+            self.pending = None;
+        }
+        result
+    }
+
+    /// Read the whole value that starts with the next event.
+    pub fn next_value(&mut self) -> Result<Option<Value>> {
+        match self.next_event()? {
+            Some(first) => build_value(first, || self.next_event()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn advance(&mut self) -> Result<Option<Event>> {
+        if let Some(event) = self.pending.take() {
+            return Ok(Some(event));
+        }
+
+        loop {
+            match self.state {
+                TextState::Value => {
+                    self.input.skip_whitespace()?;
+                    self.token_start = self.input.offset();
+                    return self.value().map(Some);
+                }
+                TextState::Key => {
+                    self.input.skip_whitespace()?;
+                    if self.input.peek_byte()? == Some(b'}') {
+                        return self.close().map(|_| Some(Event::EndObject));
+                    }
+                    self.token_start = self.input.offset();
+                    let key = self.key()?;
+                    self.input.skip_whitespace()?;
+                    if self.input.peek_byte()? != Some(b':') {
+                        return Err(self.error("Expected ':' after object key"));
+                    }
+                    self.input.consume(1);
+                    self.state = TextState::Value;
+                    return Ok(Some(Event::Key(key)));
+                }
+                TextState::Element => {
+                    self.input.skip_whitespace()?;
+                    if self.input.peek_byte()? == Some(b']') {
+                        return self.close().map(|_| Some(Event::EndArray));
+                    }
+                    self.state = TextState::Value;
+                }
+                TextState::Row => {
+                    self.input.skip_whitespace()?;
+                    if self.input.peek_byte()? == Some(b']') {
+                        return self.close().map(|_| Some(Event::EndArray));
+                    }
+                    return self.row().map(Some);
+                }
+                TextState::AfterValue => {
+                    let (close, expected) = match self.stack.last() {
+                        None => {
+                            self.state = TextState::Done;
+                            continue;
+                        }
+                        Some(TextFrame::Array) => (b']', "Expected ',' or ']' in array"),
+                        Some(TextFrame::Object) => (b'}', "Expected ',' or '}' in object"),
+                        Some(TextFrame::Tabular(..)) => {
+                            self.state = TextState::Row;
+                            continue;
+                        }
+                    };
+                    self.input.skip_whitespace()?;
+                    match self.input.peek_byte()? {
+                        Some(b',') => self.input.consume(1),
+                        Some(b) if b == close => {}
+                        _ => return Err(self.error(expected)),
+                    }
+                    self.state = match close {
+                        b']' => TextState::Element,
+                        _ => TextState::Key,
+                    };
+                }
+                TextState::Done => return Ok(None),
+            }
+        }
+    }
+
+    /// Consume the closing delimiter of the innermost container.
+    fn close(&mut self) -> Result<()> {
+        self.input.consume(1);
+        self.stack.pop();
+        self.state = TextState::AfterValue;
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<Event> {
+        self.input.skip_whitespace()?;
+        let c = match self.input.peek_char()? {
+            Some(c) => c,
+            None => return Err(self.error("Unexpected end of input")),
+        };
+
+        let event = match c {
+            '{' => {
+                self.input.consume(1);
+                self.stack.push(TextFrame::Object);
+                self.state = TextState::Key;
+                return Ok(Event::StartObject);
+            }
+            '[' => {
+                self.input.consume(1);
+                self.input.skip_whitespace()?;
+                if self.input.peek_byte()? == Some(b'#') {
+                    self.input.consume(1);
+                    let (keys, types) = self.header()?;
+                    self.stack.push(TextFrame::Tabular(keys.clone(), types));
+                    self.state = TextState::Row;
+                    self.pending = Some(Event::TabularHeader(keys));
+                } else {
+                    self.stack.push(TextFrame::Array);
+                    self.state = TextState::Element;
+                }
+                return Ok(Event::StartArray);
+            }
+            '"' => Value::String(self.quoted()?),
+            't' if self.keyword("true")? => Value::Bool(true),
+            'f' if self.keyword("false")? => Value::Bool(false),
+            'n' if self.keyword("null")? => Value::Null,
+            '-' | '0'..='9' => self.number_or_string()?,
+            _ => Value::String(self.unquoted()?),
+        };
+        self.state = TextState::AfterValue;
+        Ok(Event::Scalar(event))
+    }
+
+    fn keyword(&mut self, word: &str) -> Result<bool> {
+        let found = self.input.starts_with(word.as_bytes())?;
+        if found {
+            self.input.consume(word.len());
+        }
+        Ok(found)
+    }
+
+    /// Read a quoted string, from the opening to the closing quote.
+    fn quoted(&mut self) -> Result<String> {
+        let mut raw = String::new();
+        let mut escaped = false;
+        self.input.next_char()?; // opening quote
+        raw.push('"');
+        loop {
+            let c = match self.input.next_char()? {
+                Some(c) => c,
+                None => return Err(self.error("Unterminated string")),
+            };
+            raw.push(c);
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => break,
+                _ => {}
+            }
+        }
+
+        match text::parse_quoted_str(&raw) {
+            Ok((s, _)) => Ok(s.into_owned()),
+            Err(e) => Err(self.error(e)),
+        }
+    }
+
+    fn unquoted(&mut self) -> Result<String> {
+        let mut s = String::new();
+        self.input.take_while(&mut s, |c| {
+            !(c.is_whitespace() || matches!(c, ',' | '}' | ']' | ':'))
+        })?;
+        if s.is_empty() {
+            return Err(self.error("Expected value"));
+        }
+        Ok(s)
+    }
+
+    /// A number, or a bare string that merely starts like one.
+    fn number_or_string(&mut self) -> Result<Value> {
+        let mut s = String::new();
+        self.input
+            .take_while(&mut s, |c| matches!(c, '0'..='9' | '-' | '+' | '.' | 'e' | 'E'))?;
+        if let Ok((n, _)) = text::parse_number_str(&s) {
+            return Ok(Value::Number(n));
+        }
+        s.push_str(&self.unquoted().unwrap_or_default());
+        Ok(Value::String(s))
+    }
+
+    fn key(&mut self) -> Result<String> {
+        if self.input.peek_byte()? == Some(b'"') {
+            return self.quoted();
+        }
+        let mut key = String::new();
+        self.input
+            .take_while(&mut key, |c| !(c.is_whitespace() || c == ':'))?;
+        if key.is_empty() {
+            return Err(self.error("Expected key"));
+        }
+        Ok(key)
+    }
+
+    /// Read the `key, ...` line of a tabular header, after the `#`, with the
+    /// `:type` of each key that has one.
+    fn header(&mut self) -> Result<(Vec<String>, Vec<Option<ColumnType>>)> {
+        let mut keys = Vec::new();
+        let mut types = Vec::new();
+        loop {
+            self.input.skip_inline_whitespace()?;
+            let (key, ty) = if self.input.peek_byte()? == Some(b'"') {
+                let key = self.quoted()?;
+                let mut ty = None;
+                if self.input.peek_byte()? == Some(b':') {
+                    self.input.consume(1);
+                    let mut name = String::new();
+                    self.input
+                        .take_while(&mut name, |c| c.is_ascii_alphanumeric() || c == '?')?;
+                    ty = Some(name.parse().map_err(|e| self.error(e))?);
+                }
+                (key, ty)
+            } else {
+                let mut token = String::new();
+                self.input
+                    .take_while(&mut token, |c| !(c.is_whitespace() || c == ','))?;
+                if token.is_empty() {
+                    return Err(self.error("Expected column name in tabular header"));
+                }
+                let (key, ty) = text::split_header_token(&token);
+                (key.to_string(), ty)
+            };
+            keys.push(key);
+            types.push(ty);
+
+            self.input.skip_inline_whitespace()?;
+            if self.input.peek_byte()? != Some(b',') {
+                break;
+            }
+            self.input.consume(1);
+        }
+
+        match self.input.peek_byte()? {
+            None | Some(b'\n' | b'\r') => Ok((keys, types)),
+            Some(_) => Err(self.error("Expected newline after tabular header")),
+        }
+    }
+
+    /// Check the cells of a row against the column types of its block.
+    fn check_cells(&self, cells: &[Value]) -> Result<()> {
+        let Some(TextFrame::Tabular(keys, types)) = self.stack.last() else {
+            return Ok(());
+        };
+        for ((key, ty), cell) in keys.iter().zip(types).zip(cells) {
+            if let Some(ty) = ty.filter(|ty| !ty.matches(cell)) {
+                return Err(self.error(format_args!(
+                    "Column {:?} is {}, but the cell is {}",
+                    key, ty, cell
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Read one tabular row, leaving the parser before the next one.
+    fn row(&mut self) -> Result<Event> {
+        let columns = match self.stack.last() {
+            Some(TextFrame::Tabular(keys, _)) => keys.len(),
+            _ => unreachable!("rows are only read inside a tabular block"),
+        };
+
+        let mut cells = Vec::with_capacity(columns);
+        for i in 0..columns {
+            if i > 0 {
+                self.input.skip_inline_whitespace()?;
+                if self.input.peek_byte()? != Some(b',') {
+                    return Err(self.error(format_args!("Expected {} cells in tabular row", columns)));
+                }
+                self.input.consume(1);
+            }
+            self.input.skip_inline_whitespace()?;
+            if matches!(self.input.peek_byte()?, None | Some(b'\n' | b'\r')) {
+                return Err(self.error(format_args!("Expected {} cells in tabular row", columns)));
+            }
+
+            self.state = TextState::Value;
+            let first = self.value()?;
+            cells.push(build_value(first, || self.next_event())?);
+        }
+
+        self.input.skip_inline_whitespace()?;
+        match self.input.peek_byte()? {
+            Some(b',') => self.input.consume(1),
+            Some(b'\n' | b'\r' | b']') => {}
+            _ => return Err(self.error("Expected ',' or end of line after tabular row")),
+        }
+        if self.strict {
+            self.check_cells(&cells)?;
+        }
+        self.state = TextState::Row;
+        Ok(Event::Row(cells))
+    }
+}
+
+enum CompactFrame {
+    /// An array with the given number of elements left, its subschema in
+    /// the embedded schema, and the index of the next element
+    Array(u32, Option<usize>, usize),
+    /// An object with the given number of entries left, whether a key comes
+    /// next, its subschema, and the subschema of the next value
+    Object(u32, bool, Option<usize>, Option<usize>),
+    /// A byte string, read back as an array of numbers
+    Bytes(std::vec::IntoIter<u8>),
+    /// Tabular rows with the given number of columns and rows left, or
+    /// `None` when the rows run to the end of the input
+    Rows(usize, Option<u32>),
+}
+
+/// The embedded schema of a compact document.
+struct Embedded {
+    /// The schema as a compact value, walked with a [`Layout`]
+    bytes: Vec<u8>,
+    /// Subschema of the value read first
+    root: usize,
+    /// The whole schema, for checking a decoded document. Not kept when
+    /// reading a single value out of a document.
+    schema: Option<Schema>,
+}
+
+/// Pull parser for TOON-Compact and TOON-TAB, created by
+/// [`compact::events`](super::compact::events).
+pub struct CompactParser<R> {
+    input: Input<R>,
+    stack: Vec<CompactFrame>,
+    /// Events to return before reading more, last first
+    pending: Vec<Event>,
+    done: bool,
+    embedded: Option<Embedded>,
+}
+
+impl<R: Read> CompactParser<R> {
+    pub(crate) fn new(input: Input<R>) -> Self {
+        CompactParser {
+            input,
+            stack: Vec::new(),
+            pending: Vec::new(),
+            done: false,
+            embedded: None,
+        }
+    }
+
+    /// Parser for a TOON-Compact document after its magic header, reading
+    /// the embedded schema first if there is one.
+    pub(crate) fn document(mut input: Input<R>) -> Result<Self> {
+        if input.peek_byte()? != Some(TAG_SCHEMA) {
+            return Ok(CompactParser::new(input));
+        }
+        input.consume(1);
+        let bytes = input.read_bytes().context("Invalid embedded schema")?;
+        let value = CompactView::from_raw(&bytes)
+            .context("Invalid embedded schema")?
+            .to_value();
+        let schema = Schema::new(value).context("Invalid embedded schema")?;
+
+        let mut parser = CompactParser::new(input);
+        parser.embedded = Some(Embedded {
+            bytes,
+            root: 0,
+            schema: Some(schema),
+        });
+        Ok(parser)
+    }
+
+    /// Parser for a value that the subschema at `root` of the compact
+    /// `schema` describes.
+    pub(crate) fn typed(input: Input<R>, schema: Vec<u8>, root: usize) -> Self {
+        let mut parser = CompactParser::new(input);
+        parser.embedded = Some(Embedded {
+            bytes: schema,
+            root,
+            schema: None,
+        });
+        parser
+    }
+
+    /// Parser for a TOON-TAB document: a tabular block of rows.
+    pub(crate) fn tabular(input: Input<R>) -> Result<Self> {
+        let mut parser = CompactParser::new(input);
+        let key_count = parser.input.read_u32()?;
+        let mut keys = Vec::new();
+        for _ in 0..key_count {
+            keys.push(parser.input.read_string()?);
+        }
+        let rows = match parser.input.read_u32()? {
+            OPEN_ROW_COUNT => None,
+            rows => Some(rows),
+        };
+
+        parser.stack.push(CompactFrame::Rows(keys.len(), rows));
+        parser.pending = vec![Event::TabularHeader(keys), Event::StartArray];
+        Ok(parser)
+    }
+
+    fn error(&self, e: impl Display) -> anyhow::Error {
+        anyhow::anyhow!("{} at byte {}", e, self.input.offset())
+    }
+
+    /// The schema embedded in the document, if it has one.
+    pub fn schema(&self) -> Option<&Schema> {
+        self.embedded.as_ref()?.schema.as_ref()
+    }
+
+    fn layout(&self) -> Option<Layout<'_>> {
+        self.embedded
+            .as_ref()
+            .map(|embedded| Layout::new(&embedded.bytes, 0))
+    }
+
+    /// The next event, or `None` after the end of the document. After an
+    /// error, the parser returns `None`.
+    pub fn next_event(&mut self) -> Result<Option<Event>> {
+        let result = self.advance();
+        if result.is_err() {
+            self.done = true;
+            self.pending.clear();
+        }
+        result
+    }
+
+    /// Read the whole value that starts with the next event. Reading the
+    /// whole document also checks it against its embedded schema.
+    pub fn next_value(&mut self) -> Result<Option<Value>> {
+        let whole = self.stack.is_empty() && self.pending.is_empty() && !self.done;
+        let value = match self.next_event()? {
+            Some(first) => build_value(first, || self.next_event())?,
+            None => return Ok(None),
+        };
+        if let Some(schema) = self.schema().filter(|_| whole) {
+            schema
+                .check(&value)
+                .context("Document does not match its embedded schema")?;
+        }
+        Ok(Some(value))
+    }
+
+    fn advance(&mut self) -> Result<Option<Event>> {
+        if let Some(event) = self.pending.pop() {
+            return Ok(Some(event));
+        }
+        if self.done {
+            return Ok(None);
+        }
+
+        let event = match self.stack.last_mut() {
+            None => {
+                let root = self.embedded.as_ref().map(|embedded| embedded.root);
+                self.value(root)?
+            }
+            Some(CompactFrame::Array(0, ..) | CompactFrame::Rows(_, Some(0))) => {
+                self.stack.pop();
+                Event::EndArray
+            }
+            Some(CompactFrame::Object(0, ..)) => {
+                self.stack.pop();
+                Event::EndObject
+            }
+            Some(CompactFrame::Array(remaining, node, index)) => {
+                *remaining -= 1;
+                let (node, i) = (*node, *index);
+                *index += 1;
+                let child = self.layout().and_then(|layout| layout.item(node, i));
+                self.value(child)?
+            }
+            Some(CompactFrame::Object(_, key_next @ true, node, _)) => {
+                *key_next = false;
+                let node = *node;
+                let key = self.input.read_string().map_err(|e| self.error(e))?;
+                let child = self.layout().and_then(|layout| layout.property(node, &key));
+                if let Some(CompactFrame::Object(.., next)) = self.stack.last_mut() {
+                    *next = child;
+                }
+                return Ok(Some(Event::Key(key)));
+            }
+            Some(CompactFrame::Object(remaining, key_next, _, next)) => {
+                *remaining -= 1;
+                *key_next = true;
+                let child = *next;
+                self.value(child)?
+            }
+            Some(CompactFrame::Bytes(bytes)) => match bytes.next() {
+                Some(b) => Event::Scalar(Value::from(b)),
+                None => {
+                    self.stack.pop();
+                    Event::EndArray
+                }
+            },
+            Some(CompactFrame::Rows(columns, Some(remaining))) => {
+                let columns = *columns;
+                *remaining -= 1;
+                self.row(columns)?
+            }
+            Some(CompactFrame::Rows(columns, None)) => {
+                let columns = *columns;
+                if self.input.peek_byte()?.is_none() {
+                    self.stack.pop();
+                    Event::EndArray
+                } else {
+                    self.row(columns)?
+                }
+            }
+        };
+
+        if self.stack.is_empty() {
+            self.done = true;
+        }
+        Ok(Some(event))
+    }
+
+    /// Read a tag, unless the subschema `node` fixes it, and return the
+    /// event that starts the value.
+    fn value(&mut self, node: Option<usize>) -> Result<Event> {
+        let tag = match self.layout().and_then(|layout| layout.tag(node)) {
+            Some(tag) => tag,
+            None => self.input.read_u8().map_err(|e| self.error(e))?,
+        };
+        let value = match tag {
+            TAG_NULL => Value::Null,
+            TAG_FALSE => Value::Bool(false),
+            TAG_TRUE => Value::Bool(true),
+            TAG_NUMBER => {
+                let s = self.input.read_string().map_err(|e| self.error(e))?;
+                match s.parse() {
+                    Ok(n) => Value::Number(n),
+                    Err(_) => {
+                        return Err(self.error(format_args!("Invalid number in compact TOON: {}", s)))
+                    }
+                }
+            }
+            TAG_STRING => Value::String(self.input.read_string().map_err(|e| self.error(e))?),
+            TAG_BYTES => {
+                let bytes = self.input.read_bytes().map_err(|e| self.error(e))?;
+                self.stack.push(CompactFrame::Bytes(bytes.into_iter()));
+                return Ok(Event::StartArray);
+            }
+            TAG_ARRAY | TAG_INDEXED_ARRAY => {
+                let len = self.container_len(tag)?;
+                self.stack.push(CompactFrame::Array(len, node, 0));
+                return Ok(Event::StartArray);
+            }
+            TAG_OBJECT | TAG_INDEXED_OBJECT => {
+                let len = self.container_len(tag)?;
+                self.stack.push(CompactFrame::Object(len, true, node, None));
+                return Ok(Event::StartObject);
+            }
+            _ => return Err(self.error(format_args!("Unknown type tag: {}", tag))),
+        };
+        Ok(Event::Scalar(value))
+    }
+
+    /// Read the entry count of a container. Reading in order has no use for
+    /// an offset table, so it is skipped.
+    fn container_len(&mut self, tag: u8) -> Result<u32> {
+        let len = self.input.read_u32().map_err(|e| self.error(e))?;
+        if tag == TAG_INDEXED_ARRAY || tag == TAG_INDEXED_OBJECT {
+            self.input.read_u32().map_err(|e| self.error(e))?;
+            self.input
+                .skip(u64::from(len) * 4)
+                .map_err(|e| self.error(e))?;
+        }
+        Ok(len)
+    }
+
+    fn row(&mut self, columns: usize) -> Result<Event> {
+        let mut cells = Vec::with_capacity(columns);
+        for _ in 0..columns {
+            let first = self.value(None)?;
+            cells.push(build_value(first, || self.next_event())?);
+        }
+        Ok(Event::Row(cells))
+    }
+}
+
+impl<R: Read> Iterator for TextParser<R> {
+    type Item = Result<Event>;
+
+    fn next(&mut self) -> Option<Result<Event>> {
+        self.next_event().transpose()
+    }
+}
+
+impl<R: Read> Iterator for CompactParser<R> {
+    type Item = Result<Event>;
+
+    fn next(&mut self) -> Option<Result<Event>> {
+        self.next_event().transpose()
+    }
+}
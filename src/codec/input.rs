@@ -0,0 +1,234 @@
+//! Buffered input for the streaming decoders.
+//!
+//! [`Input`] reads from any `io::Read` through a small fixed buffer, so the
+//! decoders can look a few bytes ahead without holding the whole document in
+//! memory. It tracks the line and byte offset for error messages and, when
+//! asked, checks that indentation does not mix tabs and spaces.
+
+use anyhow::{Context, Result};
+use std::io::{self, Read};
+
+const CAPACITY: usize = 8 * 1024;
+
+pub(crate) struct Input<R> {
+    reader: R,
+    buf: Vec<u8>,
+    pos: usize,
+    end: usize,
+    line: usize,
+    offset: u64,
+    line_start: bool,
+    /// Whether to check indentation while skipping whitespace
+    check_indent: bool,
+    /// Indentation character of the first indented line
+    indent_char: Option<char>,
+}
+
+impl<R: Read> Input<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Input {
+            reader,
+            buf: vec![0; CAPACITY],
+            pos: 0,
+            end: 0,
+            line: 1,
+            offset: 0,
+            line_start: true,
+            check_indent: false,
+            indent_char: None,
+        }
+    }
+
+    /// Fail on lines indented with a different character than the first
+    /// indented line, like [`check_indentation`](super::text::check_indentation).
+    pub(crate) fn check_indentation(&mut self) {
+        self.check_indent = true;
+    }
+
+    /// Current line, starting at 1.
+    pub(crate) fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Number of bytes consumed so far.
+    pub(crate) fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Make at least `n` bytes available, unless the input ends first.
+    fn ensure(&mut self, n: usize) -> Result<bool> {
+        while self.end - self.pos < n {
+            if self.pos > 0 {
+                self.buf.copy_within(self.pos..self.end, 0);
+                self.end -= self.pos;
+                self.pos = 0;
+            }
+            if self.buf.len() < n {
+                self.buf.resize(n, 0);
+            }
+
+            let read = loop {
+                match self.reader.read(&mut self.buf[self.end..]) {
+                    Ok(read) => break read,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e).context("Failed to read TOON input"),
+                }
+            };
+            if read == 0 {
+                return Ok(false);
+            }
+            self.end += read;
+        }
+        Ok(true)
+    }
+
+    pub(crate) fn peek_byte(&mut self) -> Result<Option<u8>> {
+        Ok(if self.ensure(1)? {
+            Some(self.buf[self.pos])
+        } else {
+            None
+        })
+    }
+
+    pub(crate) fn starts_with(&mut self, prefix: &[u8]) -> Result<bool> {
+        Ok(self.ensure(prefix.len())? && self.buf[self.pos..].starts_with(prefix))
+    }
+
+    /// Consume `n` bytes that have already been peeked.
+    pub(crate) fn consume(&mut self, n: usize) {
+        let consumed = &self.buf[self.pos..self.pos + n];
+        self.line += consumed.iter().filter(|&&b| b == b'\n').count();
+        if let Some(&last) = consumed.last() {
+            self.line_start = last == b'\n';
+        }
+        self.pos += n;
+        self.offset += n as u64;
+    }
+
+    pub(crate) fn peek_char(&mut self) -> Result<Option<char>> {
+        let first = match self.peek_byte()? {
+            Some(b) => b,
+            None => return Ok(None),
+        };
+        let width = match first {
+            0x00..=0x7f => return Ok(Some(first as char)),
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => anyhow::bail!("Invalid UTF-8 in TOON text"),
+        };
+        if !self.ensure(width)? {
+            anyhow::bail!("Invalid UTF-8 in TOON text");
+        }
+        let s = std::str::from_utf8(&self.buf[self.pos..self.pos + width])
+            .context("Invalid UTF-8 in TOON text")?;
+        Ok(s.chars().next())
+    }
+
+    pub(crate) fn next_char(&mut self) -> Result<Option<char>> {
+        let c = self.peek_char()?;
+        if let Some(c) = c {
+            self.consume(c.len_utf8());
+        }
+        Ok(c)
+    }
+
+    /// Skip whitespace, including newlines.
+    pub(crate) fn skip_whitespace(&mut self) -> Result<()> {
+        while let Some(c) = self.peek_char()? {
+            if !c.is_whitespace() {
+                break;
+            }
+            if self.check_indent && self.line_start && (c == ' ' || c == '\t') {
+                match self.indent_char {
+                    None => self.indent_char = Some(c),
+                    Some(e) if e != c => anyhow::bail!(
+                        "Mixed indentation at line {}: expected {}",
+                        self.line,
+                        if e == '\t' { "tabs" } else { "spaces" }
+                    ),
+                    Some(_) => {}
+                }
+                self.pos += 1;
+                self.offset += 1;
+                continue;
+            }
+            self.consume(c.len_utf8());
+        }
+        Ok(())
+    }
+
+    /// Skip spaces and tabs on the current line.
+    pub(crate) fn skip_inline_whitespace(&mut self) -> Result<()> {
+        while let Some(b' ' | b'\t') = self.peek_byte()? {
+            self.consume(1);
+        }
+        Ok(())
+    }
+
+    /// Append characters to `out` while `pred` holds.
+    pub(crate) fn take_while(&mut self, out: &mut String, pred: impl Fn(char) -> bool) -> Result<()> {
+        while let Some(c) = self.peek_char()? {
+            if !pred(c) {
+                break;
+            }
+            out.push(c);
+            self.consume(c.len_utf8());
+        }
+        Ok(())
+    }
+
+    pub(crate) fn read_u8(&mut self) -> Result<u8> {
+        match self.peek_byte()? {
+            Some(b) => {
+                self.consume(1);
+                Ok(b)
+            }
+            None => anyhow::bail!("Unexpected end of input"),
+        }
+    }
+
+    pub(crate) fn read_u32(&mut self) -> Result<u32> {
+        if !self.ensure(4)? {
+            anyhow::bail!("Unexpected end of input reading u32");
+        }
+        let bytes = self.buf[self.pos..self.pos + 4].try_into().unwrap();
+        self.consume(4);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    /// Read a length-prefixed byte string. The buffer grows with the data
+    /// actually read, so a corrupt length cannot force a huge allocation.
+    pub(crate) fn read_bytes(&mut self) -> Result<Vec<u8>> {
+        let len = self.read_u32()? as usize;
+        let mut out = Vec::new();
+        while out.len() < len {
+            if !self.ensure(1)? {
+                anyhow::bail!("Unexpected end of input reading string");
+            }
+            let n = (len - out.len()).min(self.end - self.pos);
+            out.extend_from_slice(&self.buf[self.pos..self.pos + n]);
+            self.pos += n;
+            self.offset += n as u64;
+        }
+        Ok(out)
+    }
+
+    /// Skip `n` bytes of binary data.
+    pub(crate) fn skip(&mut self, mut n: u64) -> Result<()> {
+        while n > 0 {
+            if !self.ensure(1)? {
+                anyhow::bail!("Unexpected end of input");
+            }
+            let step = n.min((self.end - self.pos) as u64);
+            self.pos += step as usize;
+            self.offset += step;
+            n -= step;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn read_string(&mut self) -> Result<String> {
+        String::from_utf8(self.read_bytes()?).context("Invalid UTF-8 in string")
+    }
+}
@@ -0,0 +1,178 @@
+//! Width-aware layout engine in the style of Wadler's "prettier printer".
+//!
+//! A [`Doc`] describes text with optional line breaks. Each [`Doc::Group`] is
+//! rendered flat (breaks become spaces or nothing) when its contents fit in
+//! the remaining width, and broken (breaks become newlines) otherwise.
+
+use std::str::FromStr;
+
+/// Columns a tab is assumed to occupy when fitting lines to a width.
+pub const TAB_WIDTH: usize = 4;
+
+/// One level of indentation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Indent {
+    /// The given number of spaces
+    Spaces(u8),
+    /// A single tab character
+    Tab,
+}
+
+impl Default for Indent {
+    fn default() -> Self {
+        Indent::Spaces(2)
+    }
+}
+
+impl From<u8> for Indent {
+    fn from(spaces: u8) -> Self {
+        Indent::Spaces(spaces)
+    }
+}
+
+impl FromStr for Indent {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        if s == "tab" {
+            return Ok(Indent::Tab);
+        }
+        s.parse()
+            .map(Indent::Spaces)
+            .map_err(|_| anyhow::anyhow!("Invalid indent '{}' (expected a number or 'tab')", s))
+    }
+}
+
+impl Indent {
+    /// The indentation for `levels` levels of nesting.
+    pub fn repeat(self, levels: usize) -> String {
+        match self {
+            Indent::Spaces(n) => " ".repeat(levels * n as usize),
+            Indent::Tab => "\t".repeat(levels),
+        }
+    }
+
+    /// Columns occupied by `levels` levels of indentation.
+    pub fn columns(self, levels: usize) -> usize {
+        match self {
+            Indent::Spaces(n) => levels * n as usize,
+            Indent::Tab => levels * TAB_WIDTH,
+        }
+    }
+}
+
+/// A document to be laid out.
+#[derive(Clone, Debug)]
+pub enum Doc {
+    /// Literal text without newlines
+    Text(String),
+    /// A space when flat, a newline when broken
+    Line,
+    /// Nothing when flat, a newline when broken
+    SoftLine,
+    /// Documents laid out one after another
+    Concat(Vec<Doc>),
+    /// Indent line breaks inside by the given number of levels
+    Nest(usize, Box<Doc>),
+    /// Lay out the contents flat if they fit, broken otherwise
+    Group(Box<Doc>),
+}
+
+impl Doc {
+    pub fn text(s: impl Into<String>) -> Doc {
+        Doc::Text(s.into())
+    }
+
+    pub fn nest(levels: usize, doc: Doc) -> Doc {
+        Doc::Nest(levels, Box::new(doc))
+    }
+
+    pub fn group(doc: Doc) -> Doc {
+        Doc::Group(Box::new(doc))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+/// Render a document, breaking groups that do not fit in `max_width` columns.
+pub fn render(doc: &Doc, max_width: usize, indent: Indent) -> String {
+    let mut out = String::new();
+    let mut column = 0;
+    let mut stack = vec![(0, Mode::Break, doc)];
+
+    while let Some((levels, mode, doc)) = stack.pop() {
+        match doc {
+            Doc::Text(s) => {
+                out.push_str(s);
+                column += s.chars().count();
+            }
+            Doc::Line | Doc::SoftLine if mode == Mode::Flat => {
+                if matches!(doc, Doc::Line) {
+                    out.push(' ');
+                    column += 1;
+                }
+            }
+            Doc::Line | Doc::SoftLine => {
+                out.push('\n');
+                out.push_str(&indent.repeat(levels));
+                column = indent.columns(levels);
+            }
+            Doc::Concat(docs) => {
+                for d in docs.iter().rev() {
+                    stack.push((levels, mode, d));
+                }
+            }
+            Doc::Nest(extra, d) => stack.push((levels + extra, mode, d)),
+            Doc::Group(d) => {
+                let remaining = max_width.saturating_sub(column);
+                let mode = if mode == Mode::Flat || fits(d, remaining, &stack) {
+                    Mode::Flat
+                } else {
+                    Mode::Break
+                };
+                stack.push((levels, mode, d));
+            }
+        }
+    }
+
+    out
+}
+
+/// Check whether `doc` laid out flat, followed by the rest of the current
+/// line, fits in `width` columns.
+fn fits(doc: &Doc, width: usize, rest: &[(usize, Mode, &Doc)]) -> bool {
+    let mut width = width.min(isize::MAX as usize) as isize;
+    let mut pending = vec![(Mode::Flat, doc)];
+    let mut rest = rest.iter().rev();
+
+    loop {
+        let (mode, doc) = match pending.pop() {
+            Some(item) => item,
+            None => match rest.next() {
+                Some(&(_, mode, doc)) => (mode, doc),
+                None => return true,
+            },
+        };
+
+        match doc {
+            Doc::Text(s) => width -= s.chars().count() as isize,
+            Doc::Line | Doc::SoftLine if mode == Mode::Break => return true,
+            Doc::Line => width -= 1,
+            Doc::SoftLine => {}
+            Doc::Concat(docs) => {
+                for d in docs.iter().rev() {
+                    pending.push((mode, d));
+                }
+            }
+            Doc::Nest(_, d) | Doc::Group(d) => pending.push((mode, d)),
+        }
+
+        if width < 0 {
+            return false;
+        }
+    }
+}
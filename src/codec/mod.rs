@@ -1,3 +1,9 @@
 pub mod compact;
+pub(crate) mod embedded;
+pub mod events;
+pub(crate) mod input;
+pub mod layout;
+pub mod quoting;
 pub mod tabular;
 pub mod text;
+pub mod view;
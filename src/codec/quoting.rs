@@ -0,0 +1,107 @@
+//! Quoting rules for strings written by the text and tabular encoders.
+
+use std::fmt::Write as FmtWrite;
+use std::io;
+use std::str::FromStr;
+
+/// When a string is written in quotes.
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuotePolicy {
+    /// Quote only strings that would otherwise be read back differently
+    #[default]
+    Minimal,
+    /// Quote every string, including object keys
+    AlwaysStrings,
+    /// Quote strings that need it or contain non-ASCII characters
+    NonAscii,
+    /// Like `NonAscii`, and also escape non-ASCII characters as `\uXXXX`
+    /// (surrogate pairs outside the BMP) so the output is pure ASCII
+    Ascii,
+}
+
+impl FromStr for QuotePolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "minimal" => Ok(QuotePolicy::Minimal),
+            "always" => Ok(QuotePolicy::AlwaysStrings),
+            "non-ascii" => Ok(QuotePolicy::NonAscii),
+            "ascii" => Ok(QuotePolicy::Ascii),
+            _ => anyhow::bail!(
+                "Unknown quoting policy '{}' (expected minimal, always, non-ascii or ascii)",
+                s
+            ),
+        }
+    }
+}
+
+/// Check whether `s` must be quoted under `policy`.
+pub fn needs_quotes(s: &str, policy: QuotePolicy) -> bool {
+    match policy {
+        QuotePolicy::Minimal => is_ambiguous(s),
+        QuotePolicy::AlwaysStrings => true,
+        QuotePolicy::NonAscii | QuotePolicy::Ascii => !s.is_ascii() || is_ambiguous(s),
+    }
+}
+
+/// Check whether `s` written bare would be read back as something else.
+fn is_ambiguous(s: &str) -> bool {
+    let first = match s.chars().next() {
+        Some(c) => c,
+        None => return true,
+    };
+
+    // Bare words starting like a keyword or a number are parsed as one
+    let starts_like_literal = ["true", "false", "null"]
+        .iter()
+        .any(|kw| s.starts_with(kw))
+        || first.is_ascii_digit()
+        || matches!(first, '-' | '+' | '#');
+
+    starts_like_literal
+        || s.chars().any(|c| {
+            c.is_whitespace()
+                || c.is_control()
+                || matches!(c, '"' | ':' | ',' | '{' | '}' | '[' | ']')
+        })
+}
+
+/// Write `s` to a writer, quoted and escaped as by [`write_string`].
+pub fn write_string_to<W: io::Write>(out: &mut W, s: &str, policy: QuotePolicy) -> io::Result<()> {
+    if !needs_quotes(s, policy) {
+        return out.write_all(s.as_bytes());
+    }
+
+    let mut quoted = String::with_capacity(s.len() + 2);
+    write_string(&mut quoted, s, policy);
+    out.write_all(quoted.as_bytes())
+}
+
+/// Write `s` to `out`, quoting and escaping it if `policy` requires.
+pub fn write_string(out: &mut String, s: &str, policy: QuotePolicy) {
+    if !needs_quotes(s, policy) {
+        out.push_str(s);
+        return;
+    }
+
+    out.push('"');
+    for ch in s.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c if !c.is_ascii() && policy == QuotePolicy::Ascii => {
+                let mut units = [0; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    write!(out, "\\u{:04x}", unit).unwrap();
+                }
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
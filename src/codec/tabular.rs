@@ -1,8 +1,155 @@
-use anyhow::Result;
-use serde_json::Value;
+use super::compact::{self, write_string, write_u32};
+use super::events::{Event, Parser};
+use super::input::Input;
+use super::layout::Indent;
+use super::quoting::{self, QuotePolicy};
+use super::text;
+use crate::DecodeOptions;
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
 use std::collections::HashSet;
+use std::fmt;
+use std::io::{self, SeekFrom};
+use std::marker::PhantomData;
+use std::str::FromStr;
 
-const TABULAR_MAGIC: &[u8] = b"TOON-TAB\x01";
+pub const TABULAR_MAGIC: &[u8] = b"TOON-TAB\x01";
+
+/// Row count of a TOON-TAB document whose rows run to the end of the input.
+/// [`TabularWriter`] writes it so rows can be appended without patching the
+/// header.
+pub const OPEN_ROW_COUNT: u32 = u32::MAX;
+
+/// Type annotation of a tabular column, written after its name in a text
+/// header: `# id:int, name:str, score:float?`. A `?` allows nulls.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ColumnType {
+    pub kind: ColumnKind,
+    pub nullable: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColumnKind {
+    /// Numbers without a fraction or exponent
+    Int,
+    /// Any number
+    Float,
+    Str,
+    Bool,
+}
+
+/// What a cell holds, as far as column types go.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum CellKind {
+    Null,
+    Scalar(ColumnKind),
+    /// An array or object, which no column type allows
+    Container,
+}
+
+impl CellKind {
+    pub(crate) fn of(value: &Value) -> Self {
+        match value {
+            Value::Null => CellKind::Null,
+            Value::Bool(_) => CellKind::Scalar(ColumnKind::Bool),
+            Value::Number(n) if n.is_f64() => CellKind::Scalar(ColumnKind::Float),
+            Value::Number(_) => CellKind::Scalar(ColumnKind::Int),
+            Value::String(_) => CellKind::Scalar(ColumnKind::Str),
+            Value::Array(_) | Value::Object(_) => CellKind::Container,
+        }
+    }
+}
+
+impl ColumnType {
+    /// The type every cell has, or `None` if the cells mix types, hold
+    /// arrays or objects, or are all null.
+    pub fn infer<'a>(cells: impl IntoIterator<Item = &'a Value>) -> Option<Self> {
+        Self::from_kinds(cells.into_iter().map(CellKind::of))
+    }
+
+    /// Like [`infer`](Self::infer), from what each cell holds.
+    pub(crate) fn from_kinds(cells: impl IntoIterator<Item = CellKind>) -> Option<Self> {
+        let mut kind = None;
+        let mut nullable = false;
+        for cell in cells {
+            let cell_kind = match cell {
+                CellKind::Null => {
+                    nullable = true;
+                    continue;
+                }
+                CellKind::Scalar(kind) => kind,
+                CellKind::Container => return None,
+            };
+            kind = match (kind, cell_kind) {
+                (None, k) => Some(k),
+                (Some(a), b) if a == b => Some(a),
+                (Some(ColumnKind::Int | ColumnKind::Float), ColumnKind::Int | ColumnKind::Float) => {
+                    Some(ColumnKind::Float)
+                }
+                _ => return None,
+            };
+        }
+        kind.map(|kind| ColumnType { kind, nullable })
+    }
+
+    /// Whether `value` is a valid cell of this column.
+    pub fn matches(&self, value: &Value) -> bool {
+        match (self.kind, value) {
+            (_, Value::Null) => self.nullable,
+            (ColumnKind::Int, Value::Number(n)) => !n.is_f64(),
+            (ColumnKind::Float, Value::Number(_))
+            | (ColumnKind::Str, Value::String(_))
+            | (ColumnKind::Bool, Value::Bool(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.kind {
+            ColumnKind::Int => "int",
+            ColumnKind::Float => "float",
+            ColumnKind::Str => "str",
+            ColumnKind::Bool => "bool",
+        };
+        f.write_str(name)?;
+        if self.nullable {
+            f.write_str("?")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for ColumnType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, nullable) = match s.strip_suffix('?') {
+            Some(name) => (name, true),
+            None => (s, false),
+        };
+        let kind = match name {
+            "int" => ColumnKind::Int,
+            "float" => ColumnKind::Float,
+            "str" => ColumnKind::Str,
+            "bool" => ColumnKind::Bool,
+            _ => anyhow::bail!("Unknown column type {:?}: expected int, float, str or bool", s),
+        };
+        Ok(ColumnType { kind, nullable })
+    }
+}
+
+/// The type of each column of `rows`, for a typed header. A row without a
+/// column has a null cell there.
+pub fn column_types(rows: &[Value], columns: &[String]) -> Vec<Option<ColumnType>> {
+    columns
+        .iter()
+        .map(|key| ColumnType::infer(rows.iter().map(|row| row.get(key).unwrap_or(&Value::Null))))
+        .collect()
+}
 
 pub fn is_uniform_object_array(arr: &[Value]) -> bool {
     if arr.is_empty() {
@@ -37,79 +184,143 @@ pub fn is_uniform_object_array(arr: &[Value]) -> bool {
     true
 }
 
-pub fn encode_tabular_text(arr: &[Value], indent: u8) -> Result<Vec<u8>> {
-    if arr.is_empty() {
-        return Ok(b"[]".to_vec());
-    }
+pub fn encode_tabular_text(
+    arr: &[Value],
+    indent: Indent,
+    quoting: QuotePolicy,
+    typed: bool,
+) -> Result<Vec<u8>> {
+    let mut output = Vec::new();
+    write_tabular_text(&mut output, arr, indent, quoting, typed)?;
+    Ok(output)
+}
 
-    let keys = extract_keys(&arr[0])?;
-    let mut output = String::new();
+/// Write a tabular text block, one row at a time. With `typed`, columns
+/// whose cells share a type are annotated with it in the header.
+pub fn write_tabular_text<W: io::Write>(
+    out: &mut W,
+    arr: &[Value],
+    indent: Indent,
+    quoting: QuotePolicy,
+    typed: bool,
+) -> Result<()> {
+    let keys = match arr.first() {
+        Some(first) => extract_keys(first)?,
+        None => Vec::new(),
+    };
+    let types = if typed {
+        column_types(arr, &keys)
+    } else {
+        Vec::new()
+    };
+    write_text_block(out, arr, &keys, &types, indent, quoting)
+}
 
-    // Header
-    output.push_str("[\n");
-    let indent_str = " ".repeat(indent as usize);
-    output.push_str(&indent_str);
-    output.push_str("# ");
-    for (i, key) in keys.iter().enumerate() {
-        if i > 0 {
-            output.push_str(", ");
-        }
-        output.push_str(key);
+/// Write a tabular text block with the given columns and column types.
+pub(crate) fn write_text_block<W: io::Write>(
+    out: &mut W,
+    arr: &[Value],
+    keys: &[String],
+    types: &[Option<ColumnType>],
+    indent: Indent,
+    quoting: QuotePolicy,
+) -> Result<()> {
+    if arr.is_empty() {
+        out.write_all(b"[]")?;
+        return Ok(());
     }
-    output.push('\n');
+
+    let mut line = String::new();
+    write_text_header(&mut line, keys, types, indent, quoting);
+    out.write_all(line.as_bytes())?;
+    let indent_str = indent.repeat(1);
 
     // Rows
     for (row_idx, item) in arr.iter().enumerate() {
-        output.push_str(&indent_str);
+        line.clear();
+        line.push_str(&indent_str);
         if let Value::Object(obj) = item {
             for (i, key) in keys.iter().enumerate() {
                 if i > 0 {
-                    output.push_str(", ");
+                    line.push_str(", ");
                 }
                 if let Some(val) = obj.get(key) {
-                    append_value_inline(&mut output, val)?;
+                    append_value_inline(&mut line, val, quoting)?;
                 } else {
-                    output.push_str("null");
+                    line.push_str("null");
                 }
             }
         }
         if row_idx < arr.len() - 1 {
-            output.push(',');
+            line.push(',');
         }
-        output.push('\n');
+        line.push('\n');
+        out.write_all(line.as_bytes())?;
     }
 
-    output.push(']');
-    Ok(output.into_bytes())
+    out.write_all(b"]")?;
+    Ok(())
+}
+
+/// Write the opening bracket and `# key, ...` header line of a tabular block,
+/// with the type of each key that has one in `types`.
+pub(crate) fn write_text_header(
+    out: &mut String,
+    keys: &[String],
+    types: &[Option<ColumnType>],
+    indent: Indent,
+    quoting: QuotePolicy,
+) {
+    out.push_str("[\n");
+    out.push_str(&indent.repeat(1));
+    out.push_str("# ");
+    for (i, key) in keys.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        quoting::write_string(out, key, quoting);
+        if let Some(ty) = types.get(i).copied().flatten() {
+            out.push(':');
+            out.push_str(&ty.to_string());
+        }
+    }
+    out.push('\n');
 }
 
 pub fn encode_tabular_compact(arr: &[Value]) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    write_tabular_compact(&mut buf, arr)?;
+    Ok(buf)
+}
+
+/// Write a TOON-TAB document, one row at a time.
+pub fn write_tabular_compact<W: io::Write>(out: &mut W, arr: &[Value]) -> Result<()> {
     if arr.is_empty() {
-        return Ok(b"[]".to_vec());
+        out.write_all(b"[]")?;
+        return Ok(());
     }
 
     let keys = extract_keys(&arr[0])?;
-    let mut buf = Vec::new();
 
-    buf.extend_from_slice(TABULAR_MAGIC);
+    out.write_all(TABULAR_MAGIC)?;
 
     // Write key count and keys
-    write_u32(&mut buf, keys.len() as u32);
+    write_u32(out, keys.len() as u32)?;
     for key in &keys {
-        write_string(&mut buf, key);
+        write_string(out, key)?;
     }
 
     // Write row count
-    write_u32(&mut buf, arr.len() as u32);
+    write_u32(out, arr.len() as u32)?;
 
     // Write rows
     for item in arr {
         if let Value::Object(obj) = item {
             for key in &keys {
                 if let Some(val) = obj.get(key) {
-                    encode_compact_value(&mut buf, val)?;
+                    encode_compact_value(out, val)?;
                 } else {
-                    buf.push(0); // TAG_NULL
+                    out.write_all(&[0])?; // TAG_NULL
                 }
             }
         } else {
@@ -117,10 +328,355 @@ pub fn encode_tabular_compact(arr: &[Value]) -> Result<Vec<u8>> {
         }
     }
 
-    Ok(buf)
+    Ok(())
+}
+
+pub fn decode_tabular_compact(bytes: &[u8]) -> Result<Value> {
+    if !bytes.starts_with(TABULAR_MAGIC) {
+        anyhow::bail!("Invalid tabular TOON magic header");
+    }
+
+    compact::events(bytes)?
+        .next_value()?
+        .context("Unexpected end of input")
 }
 
-fn extract_keys(value: &Value) -> Result<Vec<String>> {
+/// Reads a tabular document one row at a time, either a text tabular block or
+/// a TOON-TAB document. The header is read once; only the current row is
+/// held in memory.
+pub struct TabularReader<R> {
+    parser: Parser<R>,
+    columns: Vec<String>,
+    column_types: Vec<Option<ColumnType>>,
+    rows_read: usize,
+    done: bool,
+}
+
+impl<R: io::Read> TabularReader<R> {
+    /// Read the header, detecting the format like [`crate::decode_from_reader`].
+    /// An empty array is read as a table without columns or rows.
+    pub fn new(reader: R) -> Result<Self> {
+        let mut parser = Parser::new(reader, &DecodeOptions::default())?;
+        if parser.next_event()? != Some(Event::StartArray) {
+            anyhow::bail!("Expected a tabular array");
+        }
+        let (columns, done) = match parser.next_event()? {
+            Some(Event::TabularHeader(columns)) => (columns, false),
+            Some(Event::EndArray) => (Vec::new(), true),
+            _ => anyhow::bail!("Expected a tabular header"),
+        };
+        let column_types = match parser.column_types() {
+            Some(types) => types.to_vec(),
+            None => vec![None; columns.len()],
+        };
+
+        Ok(TabularReader {
+            parser,
+            columns,
+            column_types,
+            rows_read: 0,
+            done,
+        })
+    }
+
+    /// Column names from the header, in order.
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// Type of each column from a typed text header; `None` for columns
+    /// without one and for every column of TOON-TAB.
+    pub fn column_types(&self) -> &[Option<ColumnType>] {
+        &self.column_types
+    }
+
+    /// Read the next row as an object keyed by column name, or `None` after
+    /// the last row.
+    pub fn next_row(&mut self) -> Result<Option<Map<String, Value>>> {
+        if self.done {
+            return Ok(None);
+        }
+        let event = self.parser.next_event();
+        if !matches!(event, Ok(Some(Event::Row(_)))) {
+            self.done = true;
+        }
+
+        match event? {
+            Some(Event::Row(cells)) => {
+                self.rows_read += 1;
+                Ok(Some(self.columns.iter().cloned().zip(cells).collect()))
+            }
+            Some(Event::EndArray) => Ok(None),
+            _ => anyhow::bail!("Expected a tabular row"),
+        }
+    }
+
+    /// Deserialize each row into a `T`, as if the row were a JSON object.
+    pub fn deserialize<T: DeserializeOwned>(self) -> DeserializeRows<R, T> {
+        DeserializeRows {
+            reader: self,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<R: io::Read> Iterator for TabularReader<R> {
+    type Item = Result<Map<String, Value>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_row().transpose()
+    }
+}
+
+/// Iterator over the rows of a [`TabularReader`] as typed values.
+pub struct DeserializeRows<R, T> {
+    reader: TabularReader<R>,
+    _marker: PhantomData<T>,
+}
+
+impl<R: io::Read, T: DeserializeOwned> Iterator for DeserializeRows<R, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        let row = self.reader.next_row().transpose()?;
+        let number = self.reader.rows_read;
+        Some(row.and_then(|row| {
+            serde_json::from_value(Value::Object(row))
+                .with_context(|| format!("Invalid tabular row {}", number))
+        }))
+    }
+}
+
+enum WriterFormat {
+    Text {
+        indent: Indent,
+        quoting: QuotePolicy,
+    },
+    Compact,
+}
+
+/// Writes a tabular document one row at a time, either as a text tabular
+/// block or as TOON-TAB.
+///
+/// The columns come from the keys of the first row unless they are given
+/// with [`with_columns`](Self::with_columns), and every row must have
+/// exactly those keys. A text block gets a typed header from
+/// [`with_column_types`](Self::with_column_types). Call [`finish`](Self::finish) to complete the
+/// document. A TOON-TAB document is written with [`OPEN_ROW_COUNT`], so it
+/// is readable after every row and [`append`](Self::append) can add more.
+pub struct TabularWriter<W: io::Write> {
+    out: W,
+    format: WriterFormat,
+    columns: Option<Vec<String>>,
+    column_types: Vec<Option<ColumnType>>,
+    header_written: bool,
+    rows: usize,
+    /// Scratch buffer for one compact row
+    buf: Vec<u8>,
+}
+
+impl<W: io::Write> TabularWriter<W> {
+    /// Write a text tabular block, laid out like [`encode_tabular_text`].
+    pub fn text(out: W, indent: Indent, quoting: QuotePolicy) -> Self {
+        Self::new(out, WriterFormat::Text { indent, quoting })
+    }
+
+    /// Write a TOON-TAB document.
+    pub fn compact(out: W) -> Self {
+        Self::new(out, WriterFormat::Compact)
+    }
+
+    fn new(out: W, format: WriterFormat) -> Self {
+        TabularWriter {
+            out,
+            format,
+            columns: None,
+            column_types: Vec::new(),
+            header_written: false,
+            rows: 0,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Use these columns instead of the keys of the first row.
+    pub fn with_columns(mut self, columns: Vec<String>) -> Result<Self> {
+        if self.header_written {
+            anyhow::bail!("Tabular header has already been written");
+        }
+        self.columns = Some(columns);
+        Ok(self)
+    }
+
+    /// Annotate the text header with these types, one per column given to
+    /// [`with_columns`](Self::with_columns). Every cell must then match its
+    /// column's type. TOON-TAB has no column types.
+    pub fn with_column_types(mut self, types: Vec<Option<ColumnType>>) -> Result<Self> {
+        if self.header_written {
+            anyhow::bail!("Tabular header has already been written");
+        }
+        if let WriterFormat::Compact = self.format {
+            anyhow::bail!("TOON-TAB does not support column types");
+        }
+        let columns = match &self.columns {
+            Some(columns) => columns,
+            None => anyhow::bail!("Column types need explicit columns"),
+        };
+        if types.len() != columns.len() {
+            anyhow::bail!(
+                "Expected {} column types, got {}",
+                columns.len(),
+                types.len()
+            );
+        }
+        self.column_types = types;
+        Ok(self)
+    }
+
+    /// Number of rows written by this writer, not counting rows that were
+    /// already in an appended document.
+    pub fn rows_written(&self) -> usize {
+        self.rows
+    }
+
+    /// Write one row, a JSON object with one entry per column.
+    pub fn write_row(&mut self, row: &Value) -> Result<()> {
+        let obj = match row {
+            Value::Object(obj) => obj,
+            _ => anyhow::bail!("Non-object in tabular array"),
+        };
+        if self.columns.is_none() {
+            self.columns = Some(extract_keys(row)?);
+        }
+        self.write_header()?;
+
+        let columns = self.columns.as_deref().unwrap_or_default();
+        if obj.len() != columns.len() || !columns.iter().all(|key| obj.contains_key(key)) {
+            anyhow::bail!("Row keys do not match tabular columns {:?}", columns);
+        }
+        for (key, ty) in columns.iter().zip(&self.column_types) {
+            if let Some(ty) = ty.filter(|ty| !ty.matches(&obj[key])) {
+                anyhow::bail!(
+                    "Cell {} of column {:?} is not of type {}",
+                    obj[key],
+                    key,
+                    ty
+                );
+            }
+        }
+        match self.format {
+            WriterFormat::Text { indent, quoting } => {
+                let mut line = String::new();
+                if self.rows > 0 {
+                    line.push_str(",\n");
+                }
+                line.push_str(&indent.repeat(1));
+                for (i, key) in columns.iter().enumerate() {
+                    if i > 0 {
+                        line.push_str(", ");
+                    }
+                    append_value_inline(&mut line, &obj[key], quoting)?;
+                }
+                self.out.write_all(line.as_bytes())?;
+            }
+            WriterFormat::Compact => {
+                // Buffer the row so a failed cell writes nothing
+                self.buf.clear();
+                for key in columns {
+                    encode_compact_value(&mut self.buf, &obj[key])?;
+                }
+                self.out.write_all(&self.buf)?;
+            }
+        }
+        self.rows += 1;
+        Ok(())
+    }
+
+    /// Serialize `row` and write it like [`write_row`](Self::write_row).
+    pub fn serialize<T: Serialize + ?Sized>(&mut self, row: &T) -> Result<()> {
+        let row = serde_json::to_value(row).context("Failed to serialize tabular row")?;
+        self.write_row(&row)
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+
+    /// Write the end of the document and return the underlying writer.
+    pub fn finish(mut self) -> Result<W> {
+        match self.format {
+            WriterFormat::Text { .. } if self.columns.is_none() => self.out.write_all(b"[]")?,
+            WriterFormat::Text { .. } => {
+                self.write_header()?;
+                if self.rows > 0 {
+                    self.out.write_all(b"\n")?;
+                }
+                self.out.write_all(b"]")?;
+            }
+            WriterFormat::Compact => self.write_header()?,
+        }
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    fn write_header(&mut self) -> Result<()> {
+        if self.header_written {
+            return Ok(());
+        }
+        let columns = self.columns.as_deref().unwrap_or_default();
+        match self.format {
+            WriterFormat::Text { indent, quoting } => {
+                let mut line = String::new();
+                write_text_header(&mut line, columns, &self.column_types, indent, quoting);
+                self.out.write_all(line.as_bytes())?;
+            }
+            WriterFormat::Compact => {
+                self.out.write_all(TABULAR_MAGIC)?;
+                write_u32(&mut self.out, columns.len() as u32)?;
+                for key in columns {
+                    write_string(&mut self.out, key)?;
+                }
+                write_u32(&mut self.out, OPEN_ROW_COUNT)?;
+            }
+        }
+        self.header_written = true;
+        Ok(())
+    }
+}
+
+impl<F: io::Read + io::Write + io::Seek> TabularWriter<F> {
+    /// Reopen a TOON-TAB document to append rows with its columns. A row
+    /// count written by [`encode_tabular_compact`] is replaced with
+    /// [`OPEN_ROW_COUNT`] first, so the document stays readable throughout.
+    pub fn append(mut file: F) -> Result<Self> {
+        file.seek(SeekFrom::Start(0))?;
+        let mut input = Input::new(&mut file);
+        if !input.starts_with(TABULAR_MAGIC)? {
+            anyhow::bail!("Invalid tabular TOON magic header");
+        }
+        input.consume(TABULAR_MAGIC.len());
+        let key_count = input.read_u32()?;
+        let mut columns = Vec::new();
+        for _ in 0..key_count {
+            columns.push(input.read_string()?);
+        }
+        let count_offset = input.offset();
+        let count = input.read_u32()?;
+
+        if count != OPEN_ROW_COUNT {
+            file.seek(SeekFrom::Start(count_offset))?;
+            write_u32(&mut file, OPEN_ROW_COUNT)?;
+        }
+        file.seek(SeekFrom::End(0))?;
+
+        let mut writer = Self::compact(file);
+        writer.columns = Some(columns);
+        writer.header_written = true;
+        Ok(writer)
+    }
+}
+
+pub(crate) fn extract_keys(value: &Value) -> Result<Vec<String>> {
     match value {
         Value::Object(obj) => {
             let mut keys: Vec<_> = obj.keys().cloned().collect();
@@ -131,71 +687,38 @@ fn extract_keys(value: &Value) -> Result<Vec<String>> {
     }
 }
 
-fn append_value_inline(out: &mut String, val: &Value) -> Result<()> {
+fn append_value_inline(out: &mut String, val: &Value, quoting: QuotePolicy) -> Result<()> {
     match val {
         Value::Null => out.push_str("null"),
         Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
         Value::Number(n) => out.push_str(&n.to_string()),
-        Value::String(s) => {
-            if needs_quotes(s) {
-                out.push('"');
-                for ch in s.chars() {
-                    match ch {
-                        '"' => out.push_str("\\\""),
-                        '\\' => out.push_str("\\\\"),
-                        '\n' => out.push_str("\\n"),
-                        '\r' => out.push_str("\\r"),
-                        '\t' => out.push_str("\\t"),
-                        c => out.push(c),
-                    }
-                }
-                out.push('"');
-            } else {
-                out.push_str(s);
-            }
-        }
+        Value::String(s) => quoting::write_string(out, s, quoting),
         Value::Array(_) | Value::Object(_) => {
-            // Nested structures as JSON
-            out.push_str(&serde_json::to_string(val)?);
+            // Nested structures on a single line
+            text::encode_inline(out, val, quoting)?;
         }
     }
     Ok(())
 }
 
-fn needs_quotes(s: &str) -> bool {
-    s.is_empty()
-        || s.chars()
-            .any(|c| c.is_whitespace() || c == '"' || c == ',' || c == '[' || c == ']')
-}
-
-// Compact encoding helpers
-fn write_u32(buf: &mut Vec<u8>, val: u32) {
-    buf.extend_from_slice(&val.to_le_bytes());
-}
-
-fn write_string(buf: &mut Vec<u8>, s: &str) {
-    write_u32(buf, s.len() as u32);
-    buf.extend_from_slice(s.as_bytes());
-}
-
-fn encode_compact_value(buf: &mut Vec<u8>, value: &Value) -> Result<()> {
+fn encode_compact_value<W: io::Write>(out: &mut W, value: &Value) -> Result<()> {
     match value {
-        Value::Null => buf.push(0),
-        Value::Bool(false) => buf.push(1),
-        Value::Bool(true) => buf.push(2),
+        Value::Null => out.write_all(&[0])?,
+        Value::Bool(false) => out.write_all(&[1])?,
+        Value::Bool(true) => out.write_all(&[2])?,
         Value::Number(n) => {
-            buf.push(3);
-            write_string(buf, &n.to_string());
+            out.write_all(&[3])?;
+            write_string(out, &n.to_string())?;
         }
         Value::String(s) => {
-            buf.push(4);
-            write_string(buf, s);
+            out.write_all(&[4])?;
+            write_string(out, s)?;
         }
         Value::Array(_) | Value::Object(_) => {
             // Nested structures as JSON string
-            buf.push(4);
+            out.write_all(&[4])?;
             let json = serde_json::to_string(value)?;
-            write_string(buf, &json);
+            write_string(out, &json)?;
         }
     }
     Ok(())
@@ -1,221 +1,371 @@
+use super::events::{Event, TextParser};
+use super::input::Input;
+use super::layout::{self, Doc, Indent};
+use super::quoting::{self, QuotePolicy};
+use super::tabular;
 use anyhow::{Context, Result};
 use serde_json::Value;
-use std::fmt::Write as FmtWrite;
+use std::borrow::Cow;
+use std::io;
 
-pub fn encode(value: &Value, indent_size: u8) -> Result<Vec<u8>> {
-    let mut output = String::new();
-    encode_value(&mut output, value, 0, indent_size)?;
-    Ok(output.into_bytes())
+pub fn encode(value: &Value, indent: Indent, quoting: QuotePolicy) -> Result<Vec<u8>> {
+    let mut output = Vec::new();
+    encode_to_writer(&mut output, value, indent, quoting)?;
+    Ok(output)
 }
 
-fn encode_value(out: &mut String, value: &Value, depth: usize, indent: u8) -> Result<()> {
-    match value {
-        Value::Null => out.push_str("null"),
-        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
-        Value::Number(n) => write!(out, "{}", n).unwrap(),
-        Value::String(s) => encode_string(out, s)?,
-        Value::Array(arr) => encode_array(out, arr, depth, indent)?,
-        Value::Object(obj) => encode_object(out, obj, depth, indent)?,
-    }
-    Ok(())
+/// Encode into a writer, writing each token as it is produced.
+pub fn encode_to_writer<W: io::Write>(
+    out: &mut W,
+    value: &Value,
+    indent: Indent,
+    quoting: QuotePolicy,
+) -> Result<()> {
+    encode_value(out, value, 0, indent, quoting)
 }
 
-fn encode_string(out: &mut String, s: &str) -> Result<()> {
-    // Quote strings that need it
-    let is_keyword = matches!(s, "true" | "false" | "null");
+/// Encode with a width-aware layout: each array or object is written on one
+/// line when it fits within `max_width` columns, and broken across indented
+/// lines otherwise.
+pub fn encode_pretty(
+    value: &Value,
+    indent: Indent,
+    max_width: usize,
+    quoting: QuotePolicy,
+) -> Result<Vec<u8>> {
+    let doc = value_doc(value, quoting)?;
+    Ok(layout::render(&doc, max_width, indent).into_bytes())
+}
 
-    // Check if string could be confused with a number
-    let looks_like_number = !s.is_empty() && {
-        let first = s.chars().next().unwrap();
-        (first.is_ascii_digit() || first == '-' || first == '+') &&
-        s.chars().all(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
-    };
+/// Encode a value on a single line, e.g. `{a: [1, 2]}`.
+pub fn encode_inline(out: &mut String, value: &Value, quoting: QuotePolicy) -> Result<()> {
+    let doc = value_doc(value, quoting)?;
+    out.push_str(&layout::render(&doc, usize::MAX, Indent::default()));
+    Ok(())
+}
 
-    let needs_quote = s.is_empty()
-    || looks_like_number
-    || is_keyword
-    || s.chars().any(|c| {
-        c.is_whitespace() || c == '"' || c == ':' || c == ',' || c == '{' || c == '}'
-    || c == '[' || c == ']'
-    });
-
-    if needs_quote {
-        out.push('"');
-        for ch in s.chars() {
-            match ch {
-                '"' => out.push_str("\\\""),
-                '\\' => out.push_str("\\\\"),
-                '\n' => out.push_str("\\n"),
-                '\r' => out.push_str("\\r"),
-                '\t' => out.push_str("\\t"),
-                c if c.is_control() => write!(out, "\\u{:04x}", c as u32).unwrap(),
-                c => out.push(c),
+fn value_doc(value: &Value, quoting: QuotePolicy) -> Result<Doc> {
+    let doc = match value {
+        Value::Array(arr) if !arr.is_empty() => {
+            let items = arr
+                .iter()
+                .map(|item| value_doc(item, quoting))
+                .collect::<Result<Vec<_>>>()?;
+            container_doc('[', items, ']')
+        }
+        Value::Object(obj) if !obj.is_empty() => {
+            let mut keys: Vec<_> = obj.keys().collect();
+            keys.sort(); // Deterministic output
+
+            let mut entries = Vec::with_capacity(keys.len());
+            for key in keys {
+                let mut prefix = String::new();
+                quoting::write_string(&mut prefix, key, quoting);
+                prefix.push_str(": ");
+                entries.push(Doc::Concat(vec![
+                    Doc::Text(prefix),
+                    value_doc(&obj[key], quoting)?,
+                ]));
             }
+            container_doc('{', entries, '}')
         }
-        out.push('"');
-    } else {
-        out.push_str(s);
+        _ => {
+            let mut out = Vec::new();
+            encode_value(&mut out, value, 0, Indent::default(), quoting)?;
+            Doc::Text(String::from_utf8(out)?)
+        }
+    };
+    Ok(doc)
+}
+
+fn container_doc(open: char, items: Vec<Doc>, close: char) -> Doc {
+    let mut body = Vec::with_capacity(items.len() * 3);
+    for (i, item) in items.into_iter().enumerate() {
+        if i > 0 {
+            body.push(Doc::text(","));
+            body.push(Doc::Line);
+        }
+        body.push(item);
+    }
+
+    Doc::group(Doc::Concat(vec![
+        Doc::text(open),
+        Doc::nest(1, Doc::Concat(vec![Doc::SoftLine, Doc::Concat(body)])),
+        Doc::SoftLine,
+        Doc::text(close),
+    ]))
+}
+
+fn encode_value<W: io::Write>(
+    out: &mut W,
+    value: &Value,
+    depth: usize,
+    indent: Indent,
+    quoting: QuotePolicy,
+) -> Result<()> {
+    match value {
+        Value::Null => out.write_all(b"null")?,
+        Value::Bool(b) => out.write_all(if *b { b"true" } else { b"false" })?,
+        Value::Number(n) => write!(out, "{}", n)?,
+        Value::String(s) => quoting::write_string_to(out, s, quoting)?,
+        Value::Array(arr) => encode_array(out, arr, depth, indent, quoting)?,
+        Value::Object(obj) => encode_object(out, obj, depth, indent, quoting)?,
     }
     Ok(())
 }
 
-fn encode_array(out: &mut String, arr: &[Value], depth: usize, indent: u8) -> Result<()> {
+fn encode_array<W: io::Write>(
+    out: &mut W,
+    arr: &[Value],
+    depth: usize,
+    indent: Indent,
+    quoting: QuotePolicy,
+) -> Result<()> {
     if arr.is_empty() {
-        out.push_str("[]");
+        out.write_all(b"[]")?;
         return Ok(());
     }
 
-    out.push('[');
-    let indent_str = " ".repeat((depth + 1) * indent as usize);
+    out.write_all(b"[")?;
+    let indent_str = indent.repeat(depth + 1);
 
     for (i, item) in arr.iter().enumerate() {
-        out.push('\n');
-        out.push_str(&indent_str);
-        encode_value(out, item, depth + 1, indent)?;
+        out.write_all(b"\n")?;
+        out.write_all(indent_str.as_bytes())?;
+        encode_value(out, item, depth + 1, indent, quoting)?;
         if i < arr.len() - 1 {
-            out.push(',');
+            out.write_all(b",")?;
         }
     }
 
-    out.push('\n');
-    out.push_str(&" ".repeat(depth * indent as usize));
-    out.push(']');
+    out.write_all(b"\n")?;
+    out.write_all(indent.repeat(depth).as_bytes())?;
+    out.write_all(b"]")?;
     Ok(())
 }
 
-fn encode_object(
-    out: &mut String,
+fn encode_object<W: io::Write>(
+    out: &mut W,
     obj: &serde_json::Map<String, Value>,
     depth: usize,
-    indent: u8,
+    indent: Indent,
+    quoting: QuotePolicy,
 ) -> Result<()> {
     if obj.is_empty() {
-        out.push_str("{}");
+        out.write_all(b"{}")?;
         return Ok(());
     }
 
-    out.push('{');
-    let indent_str = " ".repeat((depth + 1) * indent as usize);
+    out.write_all(b"{")?;
+    let indent_str = indent.repeat(depth + 1);
 
     let mut keys: Vec<_> = obj.keys().collect();
     keys.sort(); // Deterministic output
 
     for (i, key) in keys.iter().enumerate() {
         let value = &obj[*key];
-        out.push('\n');
-        out.push_str(&indent_str);
-        encode_string(out, key)?;
-        out.push_str(": ");
-        encode_value(out, value, depth + 1, indent)?;
+        out.write_all(b"\n")?;
+        out.write_all(indent_str.as_bytes())?;
+        quoting::write_string_to(out, key, quoting)?;
+        out.write_all(b": ")?;
+        encode_value(out, value, depth + 1, indent, quoting)?;
         if i < keys.len() - 1 {
-            out.push(',');
+            out.write_all(b",")?;
         }
     }
 
-    out.push('\n');
-    out.push_str(&" ".repeat(depth * indent as usize));
-    out.push('}');
+    out.write_all(b"\n")?;
+    out.write_all(indent.repeat(depth).as_bytes())?;
+    out.write_all(b"}")?;
     Ok(())
 }
 
-pub fn decode(bytes: &[u8]) -> Result<Value> {
-    let s = std::str::from_utf8(bytes).context("Invalid UTF-8 in TOON text")?;
-    parse_value(s.trim()).map(|(v, _)| v)
+/// Rewrite a TOON-Text document in canonical form.
+///
+/// The canonical form is what [`encode`] produces with the given indentation
+/// unit: object keys sorted, strings quoted only when needed, numbers in
+/// their shortest round-trip form, and a single trailing newline. A document
+/// whose top level is a tabular block stays tabular and keeps the column
+/// types of its header.
+pub fn canonicalize(bytes: &[u8], indent: Indent) -> Result<Vec<u8>> {
+    let value = decode(bytes)?;
+    let mut out = Vec::new();
+    match &value {
+        Value::Array(arr) if is_tabular_document(bytes) => {
+            let keys = match arr.first() {
+                Some(first) => tabular::extract_keys(first)?,
+                None => Vec::new(),
+            };
+            let declared = header_column_types(bytes);
+            let types: Vec<_> = keys
+                .iter()
+                .map(|key| declared.iter().find(|(name, _)| name == key)?.1)
+                .collect();
+            tabular::write_text_block(&mut out, arr, &keys, &types, indent, QuotePolicy::Minimal)?
+        }
+        _ => encode_to_writer(&mut out, &value, indent, QuotePolicy::Minimal)?,
+    }
+    out.push(b'\n');
+    Ok(out)
 }
 
-fn parse_value(s: &str) -> Result<(Value, &str)> {
-    let s = s.trim_start();
-    if s.is_empty() {
-        anyhow::bail!("Unexpected end of input");
-    }
+/// Check whether a TOON-Text document is already in canonical form.
+pub fn is_canonical(bytes: &[u8], indent: Indent) -> Result<bool> {
+    Ok(canonicalize(bytes, indent)? == bytes)
+}
 
-    match s.chars().next().unwrap() {
-        '{' => parse_object(s),
-        '[' => parse_array(s),
-        '"' => parse_quoted_string(s),
-        't' if s.starts_with("true") => Ok((Value::Bool(true), &s[4..])),
-        'f' if s.starts_with("false") => Ok((Value::Bool(false), &s[5..])),
-        'n' if s.starts_with("null") => Ok((Value::Null, &s[4..])),
-        '-' | '0'..='9' => {
-            // Try number first, fall back to unquoted string
-            match parse_number(s) {
-                Ok(result) => Ok(result),
-                Err(_) => parse_unquoted_string(s),
+/// Check that every line is indented with the same character, either all
+/// spaces or all tabs. The first indented line decides which one.
+pub fn check_indentation(bytes: &[u8]) -> Result<()> {
+    let s = std::str::from_utf8(bytes).context("Invalid UTF-8 in TOON text")?;
+    let mut expected: Option<char> = None;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut line_start = true;
+    let mut line = 1;
+
+    for c in s.chars() {
+        if line_start && !in_string && (c == ' ' || c == '\t') {
+            match expected {
+                None => expected = Some(c),
+                Some(e) if e != c => anyhow::bail!(
+                    "Mixed indentation at line {}: expected {}",
+                    line,
+                    if e == '\t' { "tabs" } else { "spaces" }
+                ),
+                Some(_) => {}
             }
+            continue;
         }
-        _ => parse_unquoted_string(s),
-    }
-}
-
-fn parse_object(s: &str) -> Result<(Value, &str)> {
-    let mut s = &s[1..]; // skip '{'
-    let mut obj = serde_json::Map::new();
+        line_start = false;
 
-    loop {
-        s = s.trim_start();
-        if s.starts_with('}') {
-            return Ok((Value::Object(obj), &s[1..]));
+        match c {
+            '\n' => {
+                line += 1;
+                line_start = true;
+            }
+            '\\' if in_string => escaped = !escaped,
+            '"' if !escaped => in_string = !in_string,
+            _ => {}
+        }
+        if c != '\\' {
+            escaped = false;
         }
+    }
 
-        // Parse key
-        let (key, rest) = parse_key(s)?;
-        s = rest.trim_start();
+    Ok(())
+}
 
-        if !s.starts_with(':') {
-            anyhow::bail!("Expected ':' after object key");
-        }
-        s = &s[1..];
+/// The indentation unit of a document, taken from its first indented line.
+pub(crate) fn detect_indent(bytes: &[u8]) -> Option<Indent> {
+    let line = bytes
+        .split(|&b| b == b'\n')
+        .find(|line| line.first().is_some_and(|&b| b == b' ' || b == b'\t'))?;
+    if line[0] == b'\t' {
+        return Some(Indent::Tab);
+    }
+    let spaces = line.iter().take_while(|&&b| b == b' ').count();
+    u8::try_from(spaces).ok().map(Indent::Spaces)
+}
 
-        // Parse value
-        let (value, rest) = parse_value(s)?;
-        obj.insert(key, value);
-        s = rest.trim_start();
+/// Whether the top level of a document is a tabular block.
+pub(crate) fn is_tabular_document(bytes: &[u8]) -> bool {
+    let s = String::from_utf8_lossy(bytes);
+    s.trim_start()
+        .strip_prefix('[')
+        .is_some_and(|rest| rest.trim_start().starts_with('#'))
+}
 
-        if s.starts_with(',') {
-            s = &s[1..];
-        } else if !s.starts_with('}') {
-            anyhow::bail!("Expected ',' or '}}' in object");
-        }
+/// The columns of a top-level tabular header, each with its type if the
+/// header gives one.
+pub(crate) fn header_column_types(bytes: &[u8]) -> Vec<(String, Option<tabular::ColumnType>)> {
+    let mut parser = events(bytes);
+    if !matches!(parser.next_event(), Ok(Some(Event::StartArray))) {
+        return Vec::new();
     }
+    let Ok(Some(Event::TabularHeader(keys))) = parser.next_event() else {
+        return Vec::new();
+    };
+    let types = parser.column_types().unwrap_or_default();
+    keys.into_iter().zip(types.iter().copied()).collect()
 }
 
-fn parse_array(s: &str) -> Result<(Value, &str)> {
-    let mut s = &s[1..]; // skip '['
-    let mut arr = Vec::new();
+/// Parse a TOON-Text document into events, reading from `reader` as the
+/// parser needs more input.
+pub fn events<R: io::Read>(reader: R) -> TextParser<R> {
+    TextParser::new(Input::new(reader))
+}
 
-    loop {
-        s = s.trim_start();
-        if s.starts_with(']') {
-            return Ok((Value::Array(arr), &s[1..]));
-        }
+pub fn decode(bytes: &[u8]) -> Result<Value> {
+    events(bytes)
+        .next_value()?
+        .context("Unexpected end of input")
+}
 
-        let (value, rest) = parse_value(s)?;
-        arr.push(value);
-        s = rest.trim_start();
+/// A column of a tabular header: its name, and its type if it has one.
+pub(crate) type HeaderColumn<'a> = (Cow<'a, str>, Option<tabular::ColumnType>);
 
-        if s.starts_with(',') {
-            s = &s[1..];
-        } else if !s.starts_with(']') {
-            anyhow::bail!("Expected ',' or ']' in array");
-        }
+/// Parse one column of a tabular header: its name, and its type if a `:`
+/// and a type name follow.
+pub(crate) fn parse_header_column(s: &str) -> Result<(HeaderColumn<'_>, &str)> {
+    if s.starts_with('"') {
+        let (key, rest) = parse_key(s)?;
+        let Some(rest) = rest.strip_prefix(':') else {
+            return Ok(((key, None), rest));
+        };
+        let end = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '?'))
+            .unwrap_or(rest.len());
+        return Ok(((key, Some(rest[..end].parse()?)), &rest[end..]));
     }
+    let end = s
+        .find(|c: char| c.is_whitespace() || c == ',')
+        .unwrap_or(s.len());
+
+    if end == 0 {
+        anyhow::bail!("Expected column name in tabular header");
+    }
+
+    let (key, ty) = split_header_token(&s[..end]);
+    Ok(((Cow::Borrowed(key), ty), &s[end..]))
 }
 
-fn parse_key(s: &str) -> Result<(String, &str)> {
+/// Split a bare header token such as `id:int` into a column name and type.
+/// The last `:` only starts a type when a type name follows it, so a key
+/// like `a:b` from a header without types is read whole.
+pub(crate) fn split_header_token(token: &str) -> (&str, Option<tabular::ColumnType>) {
+    match token.rsplit_once(':') {
+        Some((key, name)) if !key.is_empty() => match name.parse() {
+            Ok(ty) => (key, Some(ty)),
+            Err(_) => (token, None),
+        },
+        _ => (token, None),
+    }
+}
+
+pub(crate) fn skip_inline_whitespace(s: &str) -> &str {
+    s.trim_start_matches([' ', '\t'])
+}
+
+pub(crate) fn parse_key(s: &str) -> Result<(Cow<'_, str>, &str)> {
     let s = s.trim_start();
     if s.starts_with('"') {
-        let (val, rest) = parse_quoted_string(s)?;
-        match val {
-            Value::String(k) => Ok((k, rest)),
-            _ => unreachable!(),
-        }
+        parse_quoted_str(s)
     } else {
-        parse_unquoted_key(s)
+        parse_unquoted_key(s).map(|(key, rest)| (Cow::Borrowed(key), rest))
     }
 }
 
-fn parse_quoted_string(s: &str) -> Result<(Value, &str)> {
-    let mut chars = s[1..].chars();
+/// Parse a quoted string, borrowing it from the input when it has no escapes.
+pub(crate) fn parse_quoted_str(s: &str) -> Result<(Cow<'_, str>, &str)> {
+    let body = &s[1..];
+    if let Some(end) = body.find(['"', '\\']) {
+        if body[end..].starts_with('"') {
+            return Ok((Cow::Borrowed(&body[..end]), &body[end + 1..]));
+        }
+    }
+
+    let mut chars = body.chars();
     let mut result = String::new();
     let mut escaped = false;
 
@@ -224,7 +374,7 @@ fn parse_quoted_string(s: &str) -> Result<(Value, &str)> {
             None => anyhow::bail!("Unterminated string"),
             Some('"') if !escaped => {
                 let consumed = s.len() - chars.as_str().len();
-                return Ok((Value::String(result), &s[consumed..]));
+                return Ok((Cow::Owned(result), &s[consumed..]));
             }
             Some('\\') if !escaped => {
                 escaped = true;
@@ -237,15 +387,7 @@ fn parse_quoted_string(s: &str) -> Result<(Value, &str)> {
                     't' => result.push('\t'),
                     '"' => result.push('"'),
                     '\\' => result.push('\\'),
-                    'u' => {
-                        let hex: String = chars.by_ref().take(4).collect();
-                        let code = u32::from_str_radix(&hex, 16)
-                        .context("Invalid unicode escape")?;
-                        result.push(
-                            char::from_u32(code)
-                            .ok_or_else(|| anyhow::anyhow!("Invalid unicode codepoint"))?,
-                        );
-                    }
+                    'u' => result.push(parse_unicode_escape(&mut chars)?),
                     _ => anyhow::bail!("Invalid escape sequence: \\{}", c),
                 }
             }
@@ -256,36 +398,79 @@ fn parse_quoted_string(s: &str) -> Result<(Value, &str)> {
     }
 }
 
-fn parse_unquoted_string(s: &str) -> Result<(Value, &str)> {
+/// Parse the code point of a `\\u` escape whose `\\u` has been consumed,
+/// combining a UTF-16 surrogate pair written as two escapes.
+fn parse_unicode_escape(chars: &mut std::str::Chars) -> Result<char> {
+    let high = parse_hex4(chars)?;
+    let code = match high {
+        0xD800..=0xDBFF => {
+            let rest = chars.as_str();
+            let low = match rest.strip_prefix("\\u") {
+                Some(after) => {
+                    let mut after = after.chars();
+                    let low = parse_hex4(&mut after)?;
+                    if (0xDC00..=0xDFFF).contains(&low) {
+                        *chars = after;
+                        Some(low)
+                    } else {
+                        None
+                    }
+                }
+                None => None,
+            };
+            match low {
+                Some(low) => 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00),
+                None => anyhow::bail!(
+                    "Lone surrogate \\u{:04x}: high surrogate not followed by a low surrogate",
+                    high
+                ),
+            }
+        }
+        0xDC00..=0xDFFF => anyhow::bail!(
+            "Lone surrogate \\u{:04x}: low surrogate without a preceding high surrogate",
+            high
+        ),
+        code => code,
+    };
+    char::from_u32(code).ok_or_else(|| anyhow::anyhow!("Invalid unicode codepoint: {:x}", code))
+}
+
+fn parse_hex4(chars: &mut std::str::Chars) -> Result<u32> {
+    let hex: String = chars.by_ref().take(4).collect();
+    if hex.len() != 4 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        anyhow::bail!("Invalid unicode escape: \\u{}", hex);
+    }
+    Ok(u32::from_str_radix(&hex, 16).unwrap())
+}
+
+pub(crate) fn parse_unquoted_str(s: &str) -> Result<(&str, &str)> {
     let end = s
-    .find(|c: char| c.is_whitespace() || c == ',' || c == '}' || c == ']' || c == ':')
-    .unwrap_or(s.len());
+        .find(|c: char| c.is_whitespace() || c == ',' || c == '}' || c == ']' || c == ':')
+        .unwrap_or(s.len());
 
     if end == 0 {
         anyhow::bail!("Expected value");
     }
 
-    Ok((Value::String(s[..end].to_string()), &s[end..]))
+    Ok((&s[..end], &s[end..]))
 }
 
-fn parse_unquoted_key(s: &str) -> Result<(String, &str)> {
+fn parse_unquoted_key(s: &str) -> Result<(&str, &str)> {
     let end = s
-    .find(|c: char| c.is_whitespace() || c == ':')
-    .unwrap_or(s.len());
+        .find(|c: char| c.is_whitespace() || c == ':')
+        .unwrap_or(s.len());
 
     if end == 0 {
         anyhow::bail!("Expected key");
     }
 
-    Ok((s[..end].to_string(), &s[end..]))
+    Ok((&s[..end], &s[end..]))
 }
 
-fn parse_number(s: &str) -> Result<(Value, &str)> {
+pub(crate) fn parse_number_str(s: &str) -> Result<(serde_json::Number, &str)> {
     let end = s
-    .find(|c: char| {
-        !matches!(c, '0'..='9' | '-' | '+' | '.' | 'e' | 'E')
-    })
-    .unwrap_or(s.len());
+        .find(|c: char| !matches!(c, '0'..='9' | '-' | '+' | '.' | 'e' | 'E'))
+        .unwrap_or(s.len());
 
     if end == 0 {
         anyhow::bail!("Expected number");
@@ -293,7 +478,7 @@ fn parse_number(s: &str) -> Result<(Value, &str)> {
 
     let num_str = &s[..end];
     let num: serde_json::Number = num_str
-    .parse()
-    .with_context(|| format!("Invalid number: {}", num_str))?;
-    Ok((Value::Number(num), &s[end..]))
+        .parse()
+        .with_context(|| format!("Invalid number: {}", num_str))?;
+    Ok((num, &s[end..]))
 }
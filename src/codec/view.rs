@@ -0,0 +1,585 @@
+//! Borrowed, lazy access to a TOON-Compact document.
+//!
+//! [`CompactView`] validates a buffer once and then reads values in place:
+//! strings are borrowed from the buffer and containers are walked only as
+//! far as a lookup needs, without building a `serde_json::Value` tree.
+//! Containers written with an offset table, by
+//! [`encode_indexed`](super::compact::encode_indexed), answer `index` in
+//! constant time and `get` with a binary search.
+//!
+//! In a document with an embedded schema, values are found through the
+//! schema wherever it leaves their type tag out, so views work the same on
+//! both kinds of document. The view checks that the document follows the
+//! layout its schema implies, but not that its values match the rest of the
+//! schema, such as `minimum` or `required`: that would mean decoding the
+//! whole document, which a view is there to avoid. Decode the document to
+//! check it.
+
+use super::compact::{
+    read_bytes, read_str, read_u32, MAGIC, TAG_ARRAY, TAG_BYTES, TAG_FALSE, TAG_INDEXED_ARRAY,
+    TAG_INDEXED_OBJECT, TAG_NULL, TAG_NUMBER, TAG_OBJECT, TAG_SCHEMA, TAG_STRING, TAG_TRUE,
+};
+use std::cmp::Ordering;
+use super::embedded::Layout;
+use super::events::CompactParser;
+use super::input::Input;
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+/// A value inside a validated compact document.
+///
+/// Lookups return `None` when the value has a different type, so they chain:
+///
+/// ```
+/// use serde_json::json;
+/// use toon::CompactView;
+///
+/// let bytes = toon::codec::compact::encode(&json!({"user": {"name": "Ada", "tags": ["x"]}}))?;
+/// let doc = CompactView::new(&bytes)?;
+/// assert_eq!(doc.get("user").and_then(|u| u.get("name")).and_then(|n| n.as_str()), Some("Ada"));
+/// assert_eq!(doc.get("user").and_then(|u| u.get("tags")).and_then(|t| t.len()), Some(1));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Clone, Copy)]
+pub struct CompactView<'a> {
+    bytes: &'a [u8],
+    /// Offset of the value's tag, or of its contents when the embedded
+    /// schema leaves the tag out
+    pos: usize,
+    /// Offset of the subschema describing the value, in a document with an
+    /// embedded schema
+    node: Option<usize>,
+    /// The tag the schema fixes for the value, which is then not written
+    implied: Option<u8>,
+}
+
+/// Offset of the embedded schema in a document that has one: after the
+/// magic header, the section tag and the section size.
+const SCHEMA_START: usize = MAGIC.len() + 5;
+
+impl<'a> CompactView<'a> {
+    /// Validate a whole compact document, magic header included, and return
+    /// a view of its top-level value. An embedded schema is only used for
+    /// the layout; values are not checked against it.
+    pub fn new(bytes: &'a [u8]) -> Result<Self> {
+        if !bytes.starts_with(MAGIC) {
+            anyhow::bail!("Invalid compact TOON magic header");
+        }
+        let (pos, node) = if bytes.get(MAGIC.len()) == Some(&TAG_SCHEMA) {
+            let size = read_u32(bytes, &mut { MAGIC.len() + 1 })
+                .context("Truncated embedded schema")?;
+            let end =
+                skip_value(bytes, SCHEMA_START, None, true).context("Invalid embedded schema")?;
+            if end - SCHEMA_START != size as usize {
+                anyhow::bail!("Size of the embedded schema is wrong");
+            }
+            (end, Some(SCHEMA_START))
+        } else {
+            (MAGIC.len(), None)
+        };
+
+        let end = skip_value(bytes, pos, node, true)?;
+        if end != bytes.len() {
+            anyhow::bail!("Trailing bytes after compact document at byte {}", end);
+        }
+        Ok(CompactView {
+            bytes,
+            pos,
+            node,
+            implied: Layout::new(bytes, SCHEMA_START).tag(node),
+        })
+    }
+
+    /// Validate a single value without a magic header, as returned by
+    /// [`raw`](Self::raw) for a value the schema does not describe.
+    pub(crate) fn from_raw(bytes: &'a [u8]) -> Result<Self> {
+        let end = skip_value(bytes, 0, None, true)?;
+        if end != bytes.len() {
+            anyhow::bail!("Trailing bytes after compact value at byte {}", end);
+        }
+        Ok(CompactView::at(bytes, 0))
+    }
+
+    /// View of the tagged value at `pos` of an already validated buffer.
+    pub(crate) fn at(bytes: &'a [u8], pos: usize) -> Self {
+        CompactView {
+            bytes,
+            pos,
+            node: None,
+            implied: None,
+        }
+    }
+
+    /// Offset of the value in its buffer.
+    pub(crate) fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// View of a child value at `pos`, described by the subschema `node`.
+    fn child(&self, pos: usize, node: Option<usize>) -> CompactView<'a> {
+        CompactView {
+            bytes: self.bytes,
+            pos,
+            node,
+            implied: self.layout().tag(node),
+        }
+    }
+
+    fn layout(&self) -> Layout<'a> {
+        Layout::new(self.bytes, SCHEMA_START)
+    }
+
+    /// Subschema of the entry `key`, if this object is described by one.
+    fn property(&self, key: &str) -> Option<usize> {
+        self.layout().property(self.node, key)
+    }
+
+    /// Subschema of element `index`, if this array is described by one.
+    fn item(&self, index: usize) -> Option<usize> {
+        self.layout().item(self.node, index)
+    }
+
+    fn tag(&self) -> u8 {
+        match self.implied {
+            Some(tag) => tag,
+            None => self.bytes[self.pos],
+        }
+    }
+
+    /// Offset just after the tag, if there is one.
+    fn body(&self) -> usize {
+        match self.implied {
+            Some(_) => self.pos,
+            None => self.pos + 1,
+        }
+    }
+
+    /// Read a u32 from the validated buffer.
+    fn u32_at(&self, pos: usize) -> u32 {
+        read_u32(self.bytes, &mut { pos }).expect("validated compact document")
+    }
+
+    /// Read a string from the validated buffer.
+    fn str_at(&self, pos: &mut usize) -> &'a str {
+        read_str(self.bytes, pos).expect("validated compact document")
+    }
+
+    /// Number of elements or entries, if this is a container.
+    fn count(&self) -> Option<u32> {
+        match self.tag() {
+            TAG_ARRAY | TAG_OBJECT | TAG_INDEXED_ARRAY | TAG_INDEXED_OBJECT => {
+                Some(self.u32_at(self.body()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether this is an array or object with an offset table.
+    pub fn is_indexed(&self) -> bool {
+        matches!(self.tag(), TAG_INDEXED_ARRAY | TAG_INDEXED_OBJECT)
+    }
+
+    /// Offset of the first entry of a container.
+    fn first_entry(&self, count: u32) -> usize {
+        if self.is_indexed() {
+            self.body() + 8 + count as usize * 4
+        } else {
+            self.body() + 4
+        }
+    }
+
+    /// Offset of entry `i` of an indexed container, read from its table.
+    fn entry_at(&self, count: u32, i: usize) -> usize {
+        self.first_entry(count) + self.u32_at(self.body() + 8 + i * 4) as usize
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.tag() == TAG_NULL
+    }
+
+    pub fn is_array(&self) -> bool {
+        matches!(self.tag(), TAG_ARRAY | TAG_INDEXED_ARRAY)
+    }
+
+    pub fn is_object(&self) -> bool {
+        matches!(self.tag(), TAG_OBJECT | TAG_INDEXED_OBJECT)
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self.tag() {
+            TAG_FALSE => Some(false),
+            TAG_TRUE => Some(true),
+            _ => None,
+        }
+    }
+
+    /// The string, borrowed from the buffer.
+    pub fn as_str(&self) -> Option<&'a str> {
+        match self.tag() {
+            TAG_STRING => Some(self.str_at(&mut self.body())),
+            _ => None,
+        }
+    }
+
+    /// The number as written in the document.
+    pub fn as_number(&self) -> Option<&'a str> {
+        match self.tag() {
+            TAG_NUMBER => Some(self.str_at(&mut self.body())),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        self.as_number()?.parse().ok()
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        self.as_number()?.parse().ok()
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        self.as_number()?.parse().ok()
+    }
+
+    /// A byte string written by serde's `serialize_bytes`, borrowed from the
+    /// buffer.
+    pub fn as_bytes(&self) -> Option<&'a [u8]> {
+        match self.tag() {
+            TAG_BYTES => {
+                Some(read_bytes(self.bytes, &mut self.body()).expect("validated compact document"))
+            }
+            _ => None,
+        }
+    }
+
+    /// Number of elements of an array or entries of an object.
+    pub fn len(&self) -> Option<usize> {
+        self.count().map(|n| n as usize)
+    }
+
+    /// Whether this is an empty array or object.
+    pub fn is_empty(&self) -> Option<bool> {
+        self.count().map(|n| n == 0)
+    }
+
+    /// The value of `key`, if this is an object that has it. Indexed
+    /// objects are searched in O(log n), others in order.
+    pub fn get(&self, key: &str) -> Option<CompactView<'a>> {
+        if self.tag() != TAG_INDEXED_OBJECT {
+            return self
+                .entries()?
+                .find(|(k, _)| *k == key)
+                .map(|(_, value)| value);
+        }
+
+        let count = self.count()?;
+        let (mut lo, mut hi) = (0, count as usize);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let mut pos = self.entry_at(count, mid);
+            match self.str_at(&mut pos).cmp(key) {
+                Ordering::Less => lo = mid + 1,
+                Ordering::Greater => hi = mid,
+                Ordering::Equal => return Some(self.child(pos, self.property(key))),
+            }
+        }
+        None
+    }
+
+    /// The element at `index`, if this is an array that long. Indexed
+    /// arrays jump straight to the element.
+    pub fn index(&self, index: usize) -> Option<CompactView<'a>> {
+        if self.tag() != TAG_INDEXED_ARRAY {
+            return self.elements()?.nth(index);
+        }
+
+        let count = self.count()?;
+        if index >= count as usize {
+            return None;
+        }
+        Some(self.child(self.entry_at(count, index), self.item(index)))
+    }
+
+    /// The elements, if this is an array.
+    pub fn elements(&self) -> Option<Elements<'a>> {
+        match self.tag() {
+            TAG_ARRAY | TAG_INDEXED_ARRAY => {
+                let count = self.count()?;
+                Some(Elements {
+                    parent: *self,
+                    pos: self.first_entry(count),
+                    remaining: count,
+                    index: 0,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// The keys and values, if this is an object.
+    pub fn entries(&self) -> Option<Entries<'a>> {
+        match self.tag() {
+            TAG_OBJECT | TAG_INDEXED_OBJECT => {
+                let count = self.count()?;
+                Some(Entries {
+                    parent: *self,
+                    pos: self.first_entry(count),
+                    remaining: count,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// The encoded bytes of this value, without the magic header. In a
+    /// document with an embedded schema, these leave out the tags the schema
+    /// fixes, so they only decode together with the schema.
+    pub fn raw(&self) -> &'a [u8] {
+        let end = skip_value(self.bytes, self.pos, self.node, false)
+            .expect("validated compact document");
+        &self.bytes[self.pos..end]
+    }
+
+    /// Decode this value into a `serde_json::Value`.
+    pub fn to_value(&self) -> Value {
+        let input = Input::new(self.raw());
+        let mut parser = match self.node {
+            None => CompactParser::new(input),
+            Some(node) => {
+                let size = self.u32_at(MAGIC.len() + 1) as usize;
+                let schema = self.bytes[SCHEMA_START..SCHEMA_START + size].to_vec();
+                CompactParser::typed(input, schema, node - SCHEMA_START)
+            }
+        };
+        parser
+            .next_value()
+            .ok()
+            .flatten()
+            .expect("validated compact document")
+    }
+}
+
+impl std::fmt::Debug for CompactView<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CompactView({})", self.to_value())
+    }
+}
+
+/// Iterator over the elements of an array view.
+pub struct Elements<'a> {
+    parent: CompactView<'a>,
+    pos: usize,
+    remaining: u32,
+    index: usize,
+}
+
+impl<'a> Iterator for Elements<'a> {
+    type Item = CompactView<'a>;
+
+    fn next(&mut self) -> Option<CompactView<'a>> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let view = self.parent.child(self.pos, self.parent.item(self.index));
+        self.index += 1;
+        self.pos = skip_value(view.bytes, self.pos, view.node, false)
+            .expect("validated compact document");
+        Some(view)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining as usize, Some(self.remaining as usize))
+    }
+}
+
+/// Iterator over the keys and values of an object view.
+pub struct Entries<'a> {
+    parent: CompactView<'a>,
+    pos: usize,
+    remaining: u32,
+}
+
+impl<'a> Iterator for Entries<'a> {
+    type Item = (&'a str, CompactView<'a>);
+
+    fn next(&mut self) -> Option<(&'a str, CompactView<'a>)> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let key = read_str(self.parent.bytes, &mut self.pos).expect("validated compact document");
+        let view = self.parent.child(self.pos, self.parent.property(key));
+        self.pos = skip_value(view.bytes, self.pos, view.node, false)
+            .expect("validated compact document");
+        Some((key, view))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining as usize, Some(self.remaining as usize))
+    }
+}
+
+/// A container being skipped.
+struct Frame<'a> {
+    /// Values left to skip
+    remaining: u32,
+    in_object: bool,
+    /// Subschema of the container, or of the value itself for the outermost
+    /// frame
+    node: Option<usize>,
+    /// Index of the next element of an array
+    next: usize,
+    /// Offset table to check, when validating an indexed container
+    index: Option<IndexCheck<'a>>,
+}
+
+struct IndexCheck<'a> {
+    /// Offset of the container's tag
+    at: usize,
+    /// Offset of the offset table
+    table: usize,
+    /// Offset of the first entry
+    start: usize,
+    size: usize,
+    count: u32,
+    last_key: Option<&'a str>,
+}
+
+/// Return the offset just past the value starting at `pos`, which the
+/// subschema `node` of the document's embedded schema describes. With
+/// `validate`, also check that strings are UTF-8, numbers parse, and offset
+/// tables point at their entries in key order. Without it, indexed
+/// containers are skipped in one step. Nested containers are tracked on a
+/// heap stack, so deep documents cannot overflow the call stack.
+fn skip_value(bytes: &[u8], mut pos: usize, node: Option<usize>, validate: bool) -> Result<usize> {
+    let layout = Layout::new(bytes, SCHEMA_START);
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut frame = Frame {
+        remaining: 1,
+        in_object: false,
+        node,
+        next: 0,
+        index: None,
+    };
+
+    loop {
+        while frame.remaining == 0 {
+            if let Some(index) = &frame.index {
+                if pos - index.start != index.size {
+                    anyhow::bail!("Size of the indexed container at byte {} is wrong", index.at);
+                }
+            }
+            match stack.pop() {
+                Some(parent) => frame = parent,
+                None => return Ok(pos),
+            }
+        }
+        if let Some(index) = &frame.index {
+            let i = (index.count - frame.remaining) as usize;
+            if read_u32(bytes, &mut (index.table + i * 4))? as usize != pos - index.start {
+                anyhow::bail!(
+                    "Offset of entry {} of the indexed container at byte {} is wrong",
+                    i,
+                    index.at
+                );
+            }
+        }
+        frame.remaining -= 1;
+        let mut node = frame.node;
+        if frame.in_object {
+            let key_pos = pos;
+            let key = read_str(bytes, &mut pos)
+                .with_context(|| format!("Invalid object key at byte {}", key_pos))?;
+            if let Some(index) = &mut frame.index {
+                if index.last_key.is_some_and(|last| last >= key) {
+                    anyhow::bail!(
+                        "Keys of the indexed object at byte {} are not sorted",
+                        index.at
+                    );
+                }
+                index.last_key = Some(key);
+            }
+            node = layout.property(frame.node, key);
+        } else if !stack.is_empty() {
+            node = layout.item(frame.node, frame.next);
+            frame.next += 1;
+        }
+
+        let start = pos;
+        let tag = match layout.tag(node) {
+            Some(tag) => tag,
+            None => {
+                let tag = *bytes
+                    .get(pos)
+                    .with_context(|| format!("Unexpected end of input at byte {}", pos))?;
+                pos += 1;
+                tag
+            }
+        };
+        match tag {
+            TAG_NULL | TAG_FALSE | TAG_TRUE => {}
+            TAG_NUMBER => {
+                let s = read_str(bytes, &mut pos).with_context(|| format!("at byte {}", start))?;
+                if validate && s.parse::<serde_json::Number>().is_err() {
+                    anyhow::bail!("Invalid number in compact TOON: {} at byte {}", s, start);
+                }
+            }
+            TAG_STRING => {
+                if validate {
+                    read_str(bytes, &mut pos).with_context(|| format!("at byte {}", start))?;
+                } else {
+                    read_bytes(bytes, &mut pos)?;
+                }
+            }
+            TAG_BYTES => {
+                read_bytes(bytes, &mut pos).with_context(|| format!("at byte {}", start))?;
+            }
+            TAG_ARRAY | TAG_OBJECT => {
+                let count =
+                    read_u32(bytes, &mut pos).with_context(|| format!("at byte {}", start))?;
+                let child = Frame {
+                    remaining: count,
+                    in_object: tag == TAG_OBJECT,
+                    node,
+                    next: 0,
+                    index: None,
+                };
+                stack.push(std::mem::replace(&mut frame, child));
+            }
+            TAG_INDEXED_ARRAY | TAG_INDEXED_OBJECT => {
+                let count =
+                    read_u32(bytes, &mut pos).with_context(|| format!("at byte {}", start))?;
+                let size =
+                    read_u32(bytes, &mut pos).with_context(|| format!("at byte {}", start))?;
+                let table = pos;
+                let first = (count as usize)
+                    .checked_mul(4)
+                    .and_then(|n| n.checked_add(table))
+                    .filter(|&first| first <= bytes.len())
+                    .with_context(|| format!("Truncated offset table at byte {}", start))?;
+                if !validate {
+                    pos = first + size as usize;
+                    continue;
+                }
+                let child = Frame {
+                    remaining: count,
+                    in_object: tag == TAG_INDEXED_OBJECT,
+                    node,
+                    next: 0,
+                    index: Some(IndexCheck {
+                        at: start,
+                        table,
+                        start: first,
+                        size: size as usize,
+                        count,
+                        last_key: None,
+                    }),
+                };
+                stack.push(std::mem::replace(&mut frame, child));
+                pos = first;
+            }
+            _ => anyhow::bail!("Unknown type tag: {} at byte {}", tag, start),
+        }
+    }
+}
@@ -0,0 +1,793 @@
+//! Serialize and deserialize Rust values as TOON-Compact with serde.
+//!
+//! [`to_vec`] writes the same bytes as converting to a `serde_json::Value`
+//! and encoding that in compact mode, including sorted object keys, except
+//! that byte strings from `serialize_bytes` keep a dedicated tag instead of
+//! becoming arrays of numbers. [`from_slice`] reads strings and byte strings
+//! without copying, so `&str` and `&[u8]` fields borrow from the input. Any
+//! other type reads a byte string as an array of numbers, as
+//! [`codec::compact::decode`](crate::codec::compact::decode) does.
+//!
+//! A document with an embedded schema is decoded and checked against it
+//! first, then deserialized from the decoded value; borrowed fields cannot be
+//! read from such a document.
+
+use crate::codec::compact::{
+    self, MAGIC, TAG_ARRAY, TAG_BYTES, TAG_FALSE, TAG_INDEXED_ARRAY, TAG_INDEXED_OBJECT, TAG_NULL,
+    TAG_NUMBER, TAG_OBJECT, TAG_SCHEMA, TAG_STRING, TAG_TRUE,
+};
+use crate::de::KeyDeserializer;
+use crate::ser::KeySerializer;
+use anyhow::Result;
+use serde::de::value::{BorrowedStrDeserializer, SeqDeserializer};
+use serde::de::{self, DeserializeSeed, Visitor};
+use serde::ser::{self, Serialize};
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::fmt::{self, Display};
+use std::io::Write;
+
+/// Serialize a value as TOON-Compact.
+pub fn to_vec<T: ?Sized + Serialize>(value: &T) -> Result<Vec<u8>> {
+    let mut ser = Serializer { buf: MAGIC.to_vec() };
+    value.serialize(&mut ser)?;
+    Ok(ser.buf)
+}
+
+/// Deserialize a value from TOON-Compact.
+pub fn from_slice<'de, T: Deserialize<'de>>(bytes: &'de [u8]) -> Result<T> {
+    if !bytes.starts_with(MAGIC) {
+        anyhow::bail!("Invalid compact TOON magic header");
+    }
+    if bytes.get(MAGIC.len()) == Some(&TAG_SCHEMA) {
+        let value = compact::decode(bytes)?;
+        return Ok(T::deserialize(value)?);
+    }
+
+    let mut de = Deserializer {
+        bytes,
+        pos: MAGIC.len(),
+    };
+    let value = T::deserialize(&mut de).map_err(|e| e.at(de.pos))?;
+    if de.pos != bytes.len() {
+        return Err(de.error("Trailing bytes after compact TOON value").into());
+    }
+    Ok(value)
+}
+
+#[derive(Debug)]
+struct Error {
+    msg: String,
+    offset: Option<usize>,
+}
+
+impl Error {
+    fn at(mut self, offset: usize) -> Self {
+        self.offset.get_or_insert(offset);
+        self
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.msg)?;
+        if let Some(offset) = self.offset {
+            write!(f, " at byte {}", offset)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error {
+            msg: msg.to_string(),
+            offset: None,
+        }
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        <Error as ser::Error>::custom(msg)
+    }
+}
+
+struct Serializer {
+    buf: Vec<u8>,
+}
+
+impl Serializer {
+    fn write_u32(&mut self, val: u32) {
+        self.buf.extend_from_slice(&val.to_le_bytes());
+    }
+
+    /// Reserve a u32 to be filled in by [`patch_u32`](Self::patch_u32).
+    fn placeholder_u32(&mut self) -> usize {
+        let at = self.buf.len();
+        self.write_u32(0);
+        at
+    }
+
+    fn patch_u32(&mut self, at: usize, val: usize) -> Result<(), Error> {
+        let val = u32::try_from(val).map_err(|_| ser::Error::custom("length exceeds u32"))?;
+        self.buf[at..at + 4].copy_from_slice(&val.to_le_bytes());
+        Ok(())
+    }
+
+    fn write_bytes(&mut self, tag: u8, bytes: &[u8]) -> Result<(), Error> {
+        self.buf.push(tag);
+        let at = self.placeholder_u32();
+        self.buf.extend_from_slice(bytes);
+        self.patch_u32(at, bytes.len())
+    }
+
+    fn write_number(&mut self, n: impl Display) -> Result<(), Error> {
+        self.buf.push(TAG_NUMBER);
+        let at = self.placeholder_u32();
+        let start = self.buf.len();
+        write!(self.buf, "{}", n).map_err(ser::Error::custom)?;
+        self.patch_u32(at, self.buf.len() - start)
+    }
+
+    fn write_key(&mut self, key: &str) -> Result<(), Error> {
+        let at = self.placeholder_u32();
+        self.buf.extend_from_slice(key.as_bytes());
+        self.patch_u32(at, key.len())
+    }
+
+    fn begin_array(&mut self) -> SeqSerializer<'_> {
+        self.buf.push(TAG_ARRAY);
+        let count_at = self.placeholder_u32();
+        SeqSerializer {
+            ser: self,
+            count_at,
+            len: 0,
+        }
+    }
+
+    fn begin_object(&mut self) -> MapSerializer<'_> {
+        self.buf.push(TAG_OBJECT);
+        let count_at = self.placeholder_u32();
+        MapSerializer {
+            ser: self,
+            count_at,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Start the single-key object holding a variant's content.
+    fn begin_variant(&mut self, variant: &str) -> Result<(), Error> {
+        self.buf.push(TAG_OBJECT);
+        self.write_u32(1);
+        self.write_key(variant)
+    }
+}
+
+impl<'a> ser::Serializer for &'a mut Serializer {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = SeqSerializer<'a>;
+    type SerializeTuple = SeqSerializer<'a>;
+    type SerializeTupleStruct = SeqSerializer<'a>;
+    type SerializeTupleVariant = SeqSerializer<'a>;
+    type SerializeMap = MapSerializer<'a>;
+    type SerializeStruct = MapSerializer<'a>;
+    type SerializeStructVariant = MapSerializer<'a>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), Error> {
+        self.buf.push(if v { TAG_TRUE } else { TAG_FALSE });
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), Error> {
+        self.write_number(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), Error> {
+        self.write_number(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), Error> {
+        self.write_number(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), Error> {
+        self.write_number(v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<(), Error> {
+        self.write_number(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), Error> {
+        self.write_number(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), Error> {
+        self.write_number(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), Error> {
+        self.write_number(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), Error> {
+        self.write_number(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<(), Error> {
+        self.write_number(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), Error> {
+        // Widened like serde_json::Value does, so 0.1f32 is 0.10000000149011612
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), Error> {
+        // Same formatting as serde_json::Number; non-finite values become null
+        match serde_json::Number::from_f64(v) {
+            Some(n) => self.write_number(n),
+            None => self.serialize_unit(),
+        }
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), Error> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), Error> {
+        self.write_bytes(TAG_STRING, v.as_bytes())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+        self.write_bytes(TAG_BYTES, v)
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        self.buf.push(TAG_NULL);
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.begin_variant(variant)?;
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<SeqSerializer<'a>, Error> {
+        Ok(self.begin_array())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<SeqSerializer<'a>, Error> {
+        Ok(self.begin_array())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<SeqSerializer<'a>, Error> {
+        Ok(self.begin_array())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<SeqSerializer<'a>, Error> {
+        self.begin_variant(variant)?;
+        Ok(self.begin_array())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapSerializer<'a>, Error> {
+        Ok(self.begin_object())
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<MapSerializer<'a>, Error> {
+        Ok(self.begin_object())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<MapSerializer<'a>, Error> {
+        self.begin_variant(variant)?;
+        Ok(self.begin_object())
+    }
+}
+
+/// Writes array elements in place and fills in the count at the end.
+struct SeqSerializer<'a> {
+    ser: &'a mut Serializer,
+    count_at: usize,
+    len: usize,
+}
+
+impl SeqSerializer<'_> {
+    fn element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        value.serialize(&mut *self.ser)?;
+        self.len += 1;
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), Error> {
+        self.ser.patch_u32(self.count_at, self.len)
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SeqSerializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+/// Writes object entries in place and, at the end, reorders them by key and
+/// fills in the count.
+struct MapSerializer<'a> {
+    ser: &'a mut Serializer,
+    count_at: usize,
+    /// Offset of the key of each entry written so far
+    entries: Vec<usize>,
+}
+
+impl MapSerializer<'_> {
+    fn key(&mut self, key: &str) -> Result<(), Error> {
+        self.entries.push(self.ser.buf.len());
+        self.ser.write_key(key)
+    }
+
+    fn value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        value.serialize(&mut *self.ser)
+    }
+
+    fn finish(self) -> Result<(), Error> {
+        let buf = &mut self.ser.buf;
+        let end = buf.len();
+        let key_at = |start: usize| {
+            let len = u32::from_le_bytes(buf[start..start + 4].try_into().unwrap()) as usize;
+            &buf[start + 4..start + 4 + len]
+        };
+
+        if !self.entries.windows(2).all(|w| key_at(w[0]) <= key_at(w[1])) {
+            let mut spans: Vec<(usize, usize)> = self
+                .entries
+                .iter()
+                .zip(self.entries.iter().skip(1).copied().chain([end]))
+                .map(|(&start, next)| (start, next))
+                .collect();
+            spans.sort_by(|a, b| key_at(a.0).cmp(key_at(b.0)));
+
+            let mut sorted = Vec::with_capacity(end - self.entries[0]);
+            for (start, stop) in spans {
+                sorted.extend_from_slice(&buf[start..stop]);
+            }
+            buf[self.entries[0]..].copy_from_slice(&sorted);
+        }
+
+        self.ser.patch_u32(self.count_at, self.entries.len())
+    }
+}
+
+impl ser::SerializeMap for MapSerializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Error> {
+        let key = key.serialize(KeySerializer).map_err(ser::Error::custom)?;
+        self.key(&key)
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.value(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for MapSerializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.key(key)?;
+        self.value(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for MapSerializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.key(key)?;
+        self.value(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+struct Deserializer<'de> {
+    bytes: &'de [u8],
+    pos: usize,
+}
+
+impl<'de> Deserializer<'de> {
+    fn error(&self, msg: impl Display) -> Error {
+        <Error as de::Error>::custom(msg).at(self.pos)
+    }
+
+    /// Run a compact reader on the remaining input.
+    fn read<T>(&mut self, f: impl FnOnce(&'de [u8], &mut usize) -> Result<T>) -> Result<T, Error> {
+        let start = self.pos;
+        f(self.bytes, &mut self.pos).map_err(|e| {
+            self.pos = start;
+            self.error(e)
+        })
+    }
+
+    fn peek_tag(&self) -> Result<u8, Error> {
+        match self.bytes.get(self.pos) {
+            Some(&tag) => Ok(tag),
+            None => Err(self.error("Unexpected end of input")),
+        }
+    }
+
+    fn number(&mut self) -> Result<&'de str, Error> {
+        self.pos += 1;
+        self.read(compact::read_str)
+    }
+
+    /// Read the entry count of a container whose `tag` has been read.
+    fn len(&mut self, tag: u8) -> Result<usize, Error> {
+        self.read(|bytes, pos| compact::read_count(bytes, pos, tag))
+            .map(|n| n as usize)
+    }
+
+    fn key(&mut self) -> Result<&'de str, Error> {
+        self.read(compact::read_str)
+    }
+}
+
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let tag = self.peek_tag()?;
+        match tag {
+            TAG_NULL => {
+                self.pos += 1;
+                visitor.visit_unit()
+            }
+            TAG_FALSE | TAG_TRUE => {
+                self.pos += 1;
+                visitor.visit_bool(tag == TAG_TRUE)
+            }
+            TAG_NUMBER => {
+                let s = self.number()?;
+                let n: serde_json::Number = s
+                    .parse()
+                    .map_err(|_| self.error(format_args!("Invalid number in compact TOON: {}", s)))?;
+                if let Some(u) = n.as_u64() {
+                    visitor.visit_u64(u)
+                } else if let Some(i) = n.as_i64() {
+                    visitor.visit_i64(i)
+                } else {
+                    visitor.visit_f64(n.as_f64().unwrap_or(f64::NAN))
+                }
+            }
+            TAG_STRING => {
+                self.pos += 1;
+                visitor.visit_borrowed_str(self.read(compact::read_str)?)
+            }
+            TAG_BYTES => {
+                self.pos += 1;
+                let bytes = self.read(compact::read_bytes)?;
+                visitor.visit_seq(SeqDeserializer::new(bytes.iter().copied()))
+            }
+            TAG_ARRAY | TAG_INDEXED_ARRAY => {
+                self.pos += 1;
+                let remaining = self.len(tag)?;
+                let mut access = Access {
+                    de: self,
+                    remaining,
+                };
+                let value = visitor.visit_seq(&mut access)?;
+                if access.remaining > 0 {
+                    return Err(self.error("Array has more elements than expected"));
+                }
+                Ok(value)
+            }
+            TAG_OBJECT | TAG_INDEXED_OBJECT => {
+                self.pos += 1;
+                let remaining = self.len(tag)?;
+                let mut access = Access {
+                    de: self,
+                    remaining,
+                };
+                let value = visitor.visit_map(&mut access)?;
+                if access.remaining > 0 {
+                    return Err(self.error("Object has more entries than expected"));
+                }
+                Ok(value)
+            }
+            _ => Err(self.error(format_args!("Unknown type tag: {}", tag))),
+        }
+    }
+
+    fn deserialize_i128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.peek_tag()? != TAG_NUMBER {
+            return self.deserialize_any(visitor);
+        }
+        let s = self.number()?;
+        match s.parse() {
+            Ok(n) => visitor.visit_i128(n),
+            Err(_) => Err(self.error(format_args!("Invalid i128 in compact TOON: {}", s))),
+        }
+    }
+
+    fn deserialize_u128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.peek_tag()? != TAG_NUMBER {
+            return self.deserialize_any(visitor);
+        }
+        let s = self.number()?;
+        match s.parse() {
+            Ok(n) => visitor.visit_u128(n),
+            Err(_) => Err(self.error(format_args!("Invalid u128 in compact TOON: {}", s))),
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.peek_tag()? {
+            TAG_STRING => {
+                self.pos += 1;
+                visitor.visit_borrowed_bytes(self.read(compact::read_str)?.as_bytes())
+            }
+            TAG_BYTES => {
+                self.pos += 1;
+                visitor.visit_borrowed_bytes(self.read(compact::read_bytes)?)
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.peek_tag()? == TAG_NULL {
+            self.pos += 1;
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.peek_tag()? {
+            TAG_STRING => {
+                self.pos += 1;
+                let variant = self.read(compact::read_str)?;
+                visitor.visit_enum(BorrowedStrDeserializer::new(variant))
+            }
+            tag @ (TAG_OBJECT | TAG_INDEXED_OBJECT) => {
+                self.pos += 1;
+                if self.len(tag)? != 1 {
+                    return Err(self.error("Expected a single-key object for an enum variant"));
+                }
+                visitor.visit_enum(Variant { de: self })
+            }
+            _ => Err(self.error("Expected a string or single-key object for an enum")),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string unit unit_struct
+        seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+/// Access to the elements of an array or the entries of an object.
+struct Access<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    remaining: usize,
+}
+
+impl<'de> de::SeqAccess<'de> for Access<'_, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+impl<'de> de::MapAccess<'de> for Access<'_, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Error> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        let key = self.de.key()?;
+        seed.deserialize(KeyDeserializer::new(Cow::Borrowed(key)))
+            .map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        seed.deserialize(&mut *self.de)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+/// The content of an enum variant written as a single-key object.
+struct Variant<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+}
+
+impl<'de> de::EnumAccess<'de> for Variant<'_, 'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), Error> {
+        let variant = self.de.key()?;
+        let value = seed.deserialize(KeyDeserializer::new(Cow::Borrowed(variant)))?;
+        Ok((value, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Variant<'_, 'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        de::Deserialize::deserialize(self.de)
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self.de)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_seq(self.de, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_map(self.de, visitor)
+    }
+}
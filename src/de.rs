@@ -0,0 +1,633 @@
+//! Deserialize Rust values straight from TOON-Text with serde.
+//!
+//! The deserializer walks the text with the same grammar as
+//! [`decode_toon_to_json`](crate::decode_toon_to_json) without building a
+//! `serde_json::Value`. Unquoted strings and quoted strings without escapes
+//! are borrowed from the input, and errors name the path of the value that
+//! failed, e.g. ``invalid type: string "x", expected u32 at `users[1].id` ``.
+
+use crate::codec::text;
+use anyhow::{Context, Result};
+use serde::de::{self, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::fmt::{self, Display};
+use std::marker::PhantomData;
+
+/// Deserialize a value from TOON-Text.
+pub fn from_str<'de, T: Deserialize<'de>>(s: &'de str) -> Result<T> {
+    let mut de = Deserializer::new(s);
+    let value = T::deserialize(&mut de)?;
+    de.end()?;
+    Ok(value)
+}
+
+/// Deserialize a value from TOON-Text bytes.
+pub fn from_slice<'de, T: Deserialize<'de>>(bytes: &'de [u8]) -> Result<T> {
+    let s = std::str::from_utf8(bytes).context("Invalid UTF-8 in TOON text")?;
+    from_str(s)
+}
+
+#[derive(Debug)]
+struct Error {
+    msg: String,
+    /// Path from the root to the failing value, outermost first
+    path: Vec<Segment>,
+    line: Option<usize>,
+}
+
+#[derive(Debug)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+impl Error {
+    /// Record that the error happened inside `segment` of the enclosing value.
+    fn at(mut self, segment: Segment, line: usize) -> Self {
+        self.path.insert(0, segment);
+        self.line.get_or_insert(line);
+        self
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.msg)?;
+        if !self.path.is_empty() {
+            f.write_str(" at `")?;
+            for (i, segment) in self.path.iter().enumerate() {
+                match segment {
+                    Segment::Key(key) if i == 0 => f.write_str(key)?,
+                    Segment::Key(key) => write!(f, ".{}", key)?,
+                    Segment::Index(index) => write!(f, "[{}]", index)?,
+                }
+            }
+            f.write_str("`")?;
+        }
+        if let Some(line) = self.line {
+            write!(f, " (line {})", line)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error {
+            msg: msg.to_string(),
+            path: Vec::new(),
+            line: None,
+        }
+    }
+}
+
+struct Deserializer<'de> {
+    original: &'de str,
+    input: &'de str,
+}
+
+impl<'de> Deserializer<'de> {
+    fn new(input: &'de str) -> Self {
+        Deserializer {
+            original: input,
+            input,
+        }
+    }
+
+    fn line(&self) -> usize {
+        let offset = self.original.len() - self.input.len();
+        self.original[..offset].matches('\n').count() + 1
+    }
+
+    fn error(&self, msg: impl Display) -> Error {
+        Error {
+            line: Some(self.line()),
+            ..de::Error::custom(msg)
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.input = self.input.trim_start();
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.input.chars().next()
+    }
+
+    fn eat(&mut self, prefix: &str) -> bool {
+        match self.input.strip_prefix(prefix) {
+            Some(rest) => {
+                self.input = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), Error> {
+        self.skip_whitespace();
+        match self.input.strip_prefix(c) {
+            Some(rest) => {
+                self.input = rest;
+                Ok(())
+            }
+            None => Err(self.error(format_args!("Expected '{}'", c))),
+        }
+    }
+
+    /// Consume the `,` after an item of an array or object, or check that
+    /// `close` follows.
+    fn separator(&mut self, close: char) -> Result<(), Error> {
+        self.skip_whitespace();
+        if !self.eat(",") && !self.input.starts_with(close) {
+            return Err(self.error(format_args!("Expected ',' or '{}'", close)));
+        }
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<(), Error> {
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => Err(self.error("Trailing characters after TOON value")),
+        }
+    }
+
+    /// Run a text parser helper on the remaining input.
+    fn parse<T>(&mut self, f: impl FnOnce(&'de str) -> Result<(T, &'de str)>) -> Result<T, Error> {
+        match f(self.input) {
+            Ok((value, rest)) => {
+                self.input = rest;
+                Ok(value)
+            }
+            Err(e) => Err(self.error(e)),
+        }
+    }
+
+    /// Parse a quoted or bare string token.
+    fn parse_str(&mut self) -> Result<Cow<'de, str>, Error> {
+        match self.peek() {
+            Some('"') => self.parse(text::parse_quoted_str),
+            _ => self.parse(text::parse_unquoted_str).map(Cow::Borrowed),
+        }
+    }
+
+    fn parse_key(&mut self) -> Result<Cow<'de, str>, Error> {
+        let key = self.parse(text::parse_key)?;
+        self.expect(':')?;
+        Ok(key)
+    }
+}
+
+fn visit_str<'de, V: Visitor<'de>, E: de::Error>(
+    s: Cow<'de, str>,
+    visitor: V,
+) -> Result<V::Value, E> {
+    match s {
+        Cow::Borrowed(s) => visitor.visit_borrowed_str(s),
+        Cow::Owned(s) => visitor.visit_string(s),
+    }
+}
+
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.peek() {
+            None => Err(self.error("Unexpected end of input")),
+            Some('{') => {
+                self.input = &self.input[1..];
+                let value = visitor.visit_map(MapAccess {
+                    de: self,
+                    key: None,
+                })?;
+                self.expect('}')?;
+                Ok(value)
+            }
+            Some('[') => {
+                self.input = &self.input[1..];
+                if self.peek() == Some('#') {
+                    return self.deserialize_tabular(visitor);
+                }
+                let value = visitor.visit_seq(SeqAccess { de: self, index: 0 })?;
+                self.expect(']')?;
+                Ok(value)
+            }
+            Some('"') => {
+                let s = self.parse(text::parse_quoted_str)?;
+                visit_str(s, visitor)
+            }
+            Some('t') if self.eat("true") => visitor.visit_bool(true),
+            Some('f') if self.eat("false") => visitor.visit_bool(false),
+            Some('n') if self.eat("null") => visitor.visit_unit(),
+            Some('-' | '0'..='9') => match text::parse_number_str(self.input) {
+                Ok((n, rest)) => {
+                    self.input = rest;
+                    if let Some(u) = n.as_u64() {
+                        visitor.visit_u64(u)
+                    } else if let Some(i) = n.as_i64() {
+                        visitor.visit_i64(i)
+                    } else {
+                        visitor.visit_f64(n.as_f64().unwrap_or(f64::NAN))
+                    }
+                }
+                Err(_) => visitor.visit_borrowed_str(self.parse(text::parse_unquoted_str)?),
+            },
+            Some(_) => visitor.visit_borrowed_str(self.parse(text::parse_unquoted_str)?),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.peek() == Some('n') && self.eat("null") {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    /// Bare tokens are handed over as text even if they look like numbers or
+    /// keywords, so a `String` field accepts `zip: 02134`.
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.peek() {
+            Some('{' | '[') | None => self.deserialize_any(visitor),
+            Some(_) => {
+                let s = self.parse_str()?;
+                visit_str(s, visitor)
+            }
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.peek() {
+            Some('"') => match self.parse(text::parse_quoted_str)? {
+                Cow::Borrowed(s) => visitor.visit_borrowed_bytes(s.as_bytes()),
+                Cow::Owned(s) => visitor.visit_byte_buf(s.into_bytes()),
+            },
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        if self.peek() == Some('{') {
+            self.input = &self.input[1..];
+            let value = visitor.visit_enum(EnumAccess { de: self })?;
+            self.skip_whitespace();
+            self.eat(",");
+            self.expect('}')?;
+            Ok(value)
+        } else {
+            let variant = self.parse_str()?;
+            visitor.visit_enum(variant.into_deserializer())
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char unit unit_struct
+        seq tuple tuple_struct map struct ignored_any
+    }
+}
+
+impl<'de> Deserializer<'de> {
+    /// Deserialize a tabular block, whose `#` is next, as a sequence of maps.
+    fn deserialize_tabular<V: Visitor<'de>>(&mut self, visitor: V) -> Result<V::Value, Error> {
+        self.input = &self.input[1..]; // skip '#'
+        let mut keys = Vec::new();
+        loop {
+            self.input = text::skip_inline_whitespace(self.input);
+            // Column types only matter to strict decoding; the target type
+            // decides here
+            let (key, _) = self.parse(text::parse_header_column)?;
+            keys.push(key);
+            self.input = text::skip_inline_whitespace(self.input);
+            if !self.eat(",") {
+                break;
+            }
+        }
+        if !self.input.is_empty() && !self.input.starts_with(['\n', '\r']) {
+            return Err(self.error("Expected newline after tabular header"));
+        }
+
+        let value = visitor.visit_seq(TabularAccess {
+            de: self,
+            keys: &keys,
+            index: 0,
+        })?;
+        self.expect(']')?;
+        Ok(value)
+    }
+}
+
+struct SeqAccess<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    index: usize,
+}
+
+impl<'de> de::SeqAccess<'de> for SeqAccess<'_, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        if self.de.peek() == Some(']') {
+            return Ok(None);
+        }
+
+        let value = seed
+            .deserialize(&mut *self.de)
+            .map_err(|e| e.at(Segment::Index(self.index), self.de.line()))?;
+        self.index += 1;
+        self.de.separator(']')?;
+        Ok(Some(value))
+    }
+}
+
+struct MapAccess<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    key: Option<Cow<'de, str>>,
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess<'_, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        if self.de.peek() == Some('}') {
+            return Ok(None);
+        }
+
+        let key = self.de.parse_key()?;
+        self.key = Some(key.clone());
+        seed.deserialize(KeyDeserializer::new(key))
+            .map(Some)
+            .map_err(|e| self.annotate(e))
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let value = seed
+            .deserialize(&mut *self.de)
+            .map_err(|e| self.annotate(e))?;
+        self.de.separator('}')?;
+        Ok(value)
+    }
+}
+
+impl MapAccess<'_, '_> {
+    fn annotate(&self, e: Error) -> Error {
+        let key = self.key.as_deref().unwrap_or_default().to_string();
+        e.at(Segment::Key(key), self.de.line())
+    }
+}
+
+struct TabularAccess<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    keys: &'a [Cow<'de, str>],
+    index: usize,
+}
+
+impl<'de> de::SeqAccess<'de> for TabularAccess<'_, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        if self.de.peek() == Some(']') {
+            return Ok(None);
+        }
+
+        let row = RowDeserializer {
+            de: &mut *self.de,
+            keys: self.keys,
+        };
+        let value = seed
+            .deserialize(row)
+            .map_err(|e| e.at(Segment::Index(self.index), self.de.line()))?;
+        self.index += 1;
+
+        self.de.input = text::skip_inline_whitespace(self.de.input);
+        if !self.de.eat(",") && !self.de.input.starts_with(['\n', '\r', ']']) {
+            return Err(self
+                .de
+                .error("Expected ',' or end of line after tabular row"));
+        }
+        Ok(Some(value))
+    }
+}
+
+/// Presents one row of a tabular block as a map from header keys to cells.
+struct RowDeserializer<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    keys: &'a [Cow<'de, str>],
+}
+
+impl<'de> de::Deserializer<'de> for RowDeserializer<'_, 'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let mut access = RowAccess {
+            de: self.de,
+            keys: self.keys,
+            column: 0,
+        };
+        let value = visitor.visit_map(&mut access)?;
+        while access.column < self.keys.len() {
+            de::MapAccess::next_value::<de::IgnoredAny>(&mut access)?;
+        }
+        Ok(value)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct newtype_struct seq tuple tuple_struct
+        map struct enum identifier ignored_any
+    }
+}
+
+struct RowAccess<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    keys: &'a [Cow<'de, str>],
+    column: usize,
+}
+
+impl<'de> de::MapAccess<'de> for RowAccess<'_, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        match self.keys.get(self.column) {
+            Some(key) => seed.deserialize(KeyDeserializer::new(key.clone())).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let de = &mut *self.de;
+        if self.column > 0 {
+            de.input = text::skip_inline_whitespace(de.input);
+            if !de.eat(",") {
+                return Err(de.error(format_args!(
+                    "Expected {} cells in tabular row",
+                    self.keys.len()
+                )));
+            }
+        }
+        de.input = text::skip_inline_whitespace(de.input);
+        if de.input.is_empty() || de.input.starts_with(['\n', '\r']) {
+            return Err(de.error(format_args!(
+                "Expected {} cells in tabular row",
+                self.keys.len()
+            )));
+        }
+
+        let key = &self.keys[self.column];
+        let value = seed
+            .deserialize(&mut *de)
+            .map_err(|e| e.at(Segment::Key(key.to_string()), de.line()))?;
+        self.column += 1;
+        Ok(value)
+    }
+}
+
+struct EnumAccess<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess<'_, 'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), Error> {
+        let variant = self.de.parse_key()?;
+        let value = seed.deserialize(KeyDeserializer::new(variant))?;
+        Ok((value, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for EnumAccess<'_, 'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        de::Deserialize::deserialize(self.de)
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self.de)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_seq(self.de, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_map(self.de, visitor)
+    }
+}
+
+/// Deserializes an object key, parsing it when a number or bool is expected.
+pub(crate) struct KeyDeserializer<'de, E>(Cow<'de, str>, PhantomData<E>);
+
+impl<'de, E> KeyDeserializer<'de, E> {
+    pub(crate) fn new(key: Cow<'de, str>) -> Self {
+        KeyDeserializer(key, PhantomData)
+    }
+}
+
+macro_rules! deserialize_parsed_key {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+                match self.0.parse() {
+                    Ok(v) => visitor.$visit(v),
+                    Err(_) => self.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de, E: de::Error> de::Deserializer<'de> for KeyDeserializer<'de, E> {
+    type Error = E;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        visit_str(self.0, visitor)
+    }
+
+    deserialize_parsed_key! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, E> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, E> {
+        visitor.visit_enum(self.0.into_deserializer())
+    }
+
+    serde::forward_to_deserialize_any! {
+        i128 u128 f32 f64 char str string bytes byte_buf unit unit_struct seq
+        tuple tuple_struct map struct identifier ignored_any
+    }
+}
@@ -1,25 +1,41 @@
-use crate::codec::{compact, text};
+use crate::codec::events::{self, Parser};
+use crate::codec::{compact, tabular, text};
 use crate::DecodeOptions;
 use anyhow::{Context, Result};
 use serde_json::Value;
+use std::io;
 
-const COMPACT_MAGIC: &[u8] = b"TOON\x01";
-
+/// Decode a complete document held in memory.
 pub fn decode(bytes: &[u8], opt: &DecodeOptions) -> Result<Value> {
-    if bytes.is_empty() {
-        anyhow::bail!("Empty input");
+    // With the whole document at hand, check indentation up front so the
+    // error reports the offending line without parser context.
+    let is_compact = bytes.starts_with(tabular::TABULAR_MAGIC) || bytes.starts_with(compact::MAGIC);
+    if opt.strict && !opt.compact && !is_compact {
+        text::check_indentation(bytes)?;
     }
+    decode_reader(bytes, opt)
+}
 
-    // Auto-detect format if not specified
-    let is_compact = if opt.compact {
-        true
-    } else {
-        bytes.len() >= COMPACT_MAGIC.len() && &bytes[..COMPACT_MAGIC.len()] == COMPACT_MAGIC
+/// Decode from a reader, pulling input as the parser needs it. Only the
+/// decoded value is held in memory, never the whole document.
+pub fn decode_reader<R: io::Read>(reader: R, opt: &DecodeOptions) -> Result<Value> {
+    let mut parser = Parser::new(reader, opt)?;
+    let format = match parser {
+        Parser::Text(_) => "text",
+        Parser::Compact(_) => "compact",
     };
 
-    if is_compact {
-        compact::decode(bytes).context("Failed to decode compact TOON")
-    } else {
-        text::decode(bytes).context("Failed to decode text TOON")
+    let first = parser
+        .next_event()
+        .and_then(|e| e.ok_or_else(|| anyhow::anyhow!("Empty input")));
+    let value = first
+        .and_then(|first| events::build_value(first, || parser.next_event()))
+        .with_context(|| format!("Failed to decode {} TOON", format))?;
+    if let Some(schema) = parser.schema() {
+        schema.check(&value).context("Document does not match its embedded schema")?;
+    }
+    if let Some(schema) = &opt.schema {
+        schema.check(&value)?;
     }
+    Ok(value)
 }
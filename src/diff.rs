@@ -0,0 +1,281 @@
+//! Structural diff between two decoded documents.
+//!
+//! [`diff`] returns an edit script: a list of [`Change`]s that turns the
+//! first value into the second when applied in order. Each change names its
+//! target with a JSON Pointer that is valid at the point it is applied, so
+//! [`to_patch`] converts the script into an RFC 6902 JSON Patch directly.
+//!
+//! Arrays are compared by position, except that arrays of objects can be
+//! matched by a key column, as for the rows of a tabular block. Keyed rows
+//! that were reordered become moves rather than a run of changed rows.
+
+use crate::codec::text;
+use crate::query::escape_token;
+use crate::QuotePolicy;
+use serde_json::{json, Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// Options for [`diff`].
+#[derive(Default, Clone, Debug)]
+pub struct DiffOptions {
+    /// Match the elements of arrays of objects by this member instead of by
+    /// position, when every element has it and its values are unique
+    pub key: Option<String>,
+}
+
+/// One step of an edit script.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change {
+    Added {
+        path: String,
+        value: Value,
+    },
+    Removed {
+        path: String,
+        value: Value,
+    },
+    Changed {
+        path: String,
+        old: Value,
+        new: Value,
+    },
+    /// An array element moved from one index to another
+    Moved {
+        from: String,
+        path: String,
+    },
+}
+
+impl Change {
+    /// The pointer to the changed value.
+    pub fn path(&self) -> &str {
+        match self {
+            Change::Added { path, .. }
+            | Change::Removed { path, .. }
+            | Change::Changed { path, .. }
+            | Change::Moved { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    /// One line per change: `+` added, `-` removed, `~` changed, `>` moved,
+    /// with values as inline TOON.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inline = |value: &Value| {
+            let mut out = String::new();
+            text::encode_inline(&mut out, value, QuotePolicy::Minimal).map(|_| out)
+        };
+        match self {
+            Change::Added { path, value } => {
+                write!(
+                    f,
+                    "+ {}: {}",
+                    display_path(path),
+                    inline(value).map_err(|_| fmt::Error)?
+                )
+            }
+            Change::Removed { path, value } => {
+                write!(
+                    f,
+                    "- {}: {}",
+                    display_path(path),
+                    inline(value).map_err(|_| fmt::Error)?
+                )
+            }
+            Change::Changed { path, old, new } => write!(
+                f,
+                "~ {}: {} -> {}",
+                display_path(path),
+                inline(old).map_err(|_| fmt::Error)?,
+                inline(new).map_err(|_| fmt::Error)?
+            ),
+            Change::Moved { from, path } => {
+                write!(f, "> {} -> {}", display_path(from), display_path(path))
+            }
+        }
+    }
+}
+
+fn display_path(path: &str) -> &str {
+    if path.is_empty() {
+        "/"
+    } else {
+        path
+    }
+}
+
+/// The edit script that turns `old` into `new`. Equal values give an empty
+/// script.
+pub fn diff(old: &Value, new: &Value, opt: &DiffOptions) -> Vec<Change> {
+    let mut changes = Vec::new();
+    diff_values(String::new(), old, new, opt, &mut changes);
+    changes
+}
+
+/// Convert an edit script into an RFC 6902 JSON Patch.
+pub fn to_patch(changes: &[Change]) -> Value {
+    let ops = changes
+        .iter()
+        .map(|change| match change {
+            Change::Added { path, value } => json!({"op": "add", "path": path, "value": value}),
+            Change::Removed { path, .. } => json!({"op": "remove", "path": path}),
+            Change::Changed { path, new, .. } => {
+                json!({"op": "replace", "path": path, "value": new})
+            }
+            Change::Moved { from, path } => json!({"op": "move", "from": from, "path": path}),
+        })
+        .collect();
+    Value::Array(ops)
+}
+
+fn diff_values(path: String, old: &Value, new: &Value, opt: &DiffOptions, out: &mut Vec<Change>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => diff_objects(&path, old, new, opt, out),
+        (Value::Array(old), Value::Array(new)) => {
+            let keys = opt
+                .key
+                .as_deref()
+                .and_then(|key| Some((key_column(old, key)?, key_column(new, key)?)));
+            match keys {
+                Some((old_keys, new_keys)) => {
+                    diff_keyed(&path, old, new, &old_keys, &new_keys, opt, out)
+                }
+                None => diff_positional(&path, old, new, opt, out),
+            }
+        }
+        _ if old == new => {}
+        _ => out.push(Change::Changed {
+            path,
+            old: old.clone(),
+            new: new.clone(),
+        }),
+    }
+}
+
+fn child(path: &str, token: &str) -> String {
+    format!("{}/{}", path, escape_token(token))
+}
+
+fn diff_objects(
+    path: &str,
+    old: &Map<String, Value>,
+    new: &Map<String, Value>,
+    opt: &DiffOptions,
+    out: &mut Vec<Change>,
+) {
+    for (key, old_value) in old {
+        match new.get(key) {
+            Some(new_value) => diff_values(child(path, key), old_value, new_value, opt, out),
+            None => out.push(Change::Removed {
+                path: child(path, key),
+                value: old_value.clone(),
+            }),
+        }
+    }
+    for (key, new_value) in new {
+        if !old.contains_key(key) {
+            out.push(Change::Added {
+                path: child(path, key),
+                value: new_value.clone(),
+            });
+        }
+    }
+}
+
+/// Compare elements at the same index, then remove the extra elements from
+/// the end or add the new ones.
+fn diff_positional(
+    path: &str,
+    old: &[Value],
+    new: &[Value],
+    opt: &DiffOptions,
+    out: &mut Vec<Change>,
+) {
+    for (i, (old_value, new_value)) in old.iter().zip(new).enumerate() {
+        diff_values(child(path, &i.to_string()), old_value, new_value, opt, out);
+    }
+    for i in (new.len()..old.len()).rev() {
+        out.push(Change::Removed {
+            path: child(path, &i.to_string()),
+            value: old[i].clone(),
+        });
+    }
+    for (i, value) in new.iter().enumerate().skip(old.len()) {
+        out.push(Change::Added {
+            path: child(path, &i.to_string()),
+            value: value.clone(),
+        });
+    }
+}
+
+/// The key of every element, if all elements are objects with a unique
+/// value for `key`.
+fn key_column(rows: &[Value], key: &str) -> Option<Vec<String>> {
+    let mut seen = HashSet::new();
+    rows.iter()
+        .map(|row| {
+            let id = row.as_object()?.get(key)?.to_string();
+            seen.insert(id.clone()).then_some(id)
+        })
+        .collect()
+}
+
+/// Match rows by key: remove the rows that are gone, from the end so the
+/// indexes stay valid, then walk the new rows in order, adding new ones and
+/// moving kept ones into place before comparing them.
+fn diff_keyed(
+    path: &str,
+    old: &[Value],
+    new: &[Value],
+    old_keys: &[String],
+    new_keys: &[String],
+    opt: &DiffOptions,
+    out: &mut Vec<Change>,
+) {
+    let new_set: HashSet<&str> = new_keys.iter().map(String::as_str).collect();
+    let old_rows: HashMap<&str, &Value> = old_keys.iter().map(String::as_str).zip(old).collect();
+
+    for i in (0..old.len()).rev() {
+        if !new_set.contains(old_keys[i].as_str()) {
+            out.push(Change::Removed {
+                path: child(path, &i.to_string()),
+                value: old[i].clone(),
+            });
+        }
+    }
+
+    // Keys of the array as the script has left it so far
+    let mut current: Vec<&str> = old_keys
+        .iter()
+        .map(String::as_str)
+        .filter(|key| new_set.contains(key))
+        .collect();
+    for (i, (key, new_row)) in new_keys.iter().zip(new).enumerate() {
+        let row_path = child(path, &i.to_string());
+        let Some(old_row) = old_rows.get(key.as_str()) else {
+            out.push(Change::Added {
+                path: row_path,
+                value: new_row.clone(),
+            });
+            current.insert(i, key);
+            continue;
+        };
+
+        let at = current[i..]
+            .iter()
+            .position(|k| k == key)
+            .map(|p| p + i)
+            .expect("kept rows are in the current array");
+        if at != i {
+            out.push(Change::Moved {
+                from: child(path, &at.to_string()),
+                path: row_path.clone(),
+            });
+            let moved = current.remove(at);
+            current.insert(i, moved);
+        }
+        diff_values(row_path, old_row, new_row, opt, out);
+    }
+}
@@ -2,34 +2,64 @@ use crate::codec::{compact, tabular, text};
 use crate::EncodeOptions;
 use anyhow::{Context, Result};
 use serde_json::Value;
+use std::io;
 
 pub fn encode(input: &Value, opt: &EncodeOptions) -> Result<Vec<u8>> {
+    let mut output = Vec::new();
+    encode_to_writer(&mut output, input, opt)?;
+    Ok(output)
+}
+
+/// Encode into a writer. Plain text, compact and tabular output is written as
+/// it is produced; a width-aware layout is rendered in memory first, since a
+/// container's layout depends on everything inside it.
+pub fn encode_to_writer<W: io::Write>(out: &mut W, input: &Value, opt: &EncodeOptions) -> Result<()> {
+    if let Some(schema) = &opt.schema {
+        schema.check(input)?;
+    }
+
     // Check if we should use tabular mode
     if opt.tabular_arrays {
-        if let Some(result) = try_tabular_encode(input, opt)? {
-            return Ok(result);
+        if let Some(arr) = tabular_rows(input, opt)? {
+            return if opt.compact {
+                tabular::write_tabular_compact(out, arr)
+            } else {
+                let indent = opt.indent.unwrap_or_default();
+                tabular::write_tabular_text(out, arr, indent, opt.quoting, opt.typed_columns)
+            };
         }
         // Fall through to regular encoding if tabular doesn't apply
     }
 
-    if opt.compact {
-        compact::encode(input).context("Failed to encode in compact mode")
+    if opt.compact && opt.embed_schema {
+        let schema = opt
+            .schema
+            .as_ref()
+            .context("Embedding a schema requires a schema to embed")?;
+        compact::encode_with_schema_to_writer(out, input, schema.as_value(), opt.compact_index)
+            .context("Failed to encode in compact mode")
+    } else if opt.compact && opt.compact_index {
+        compact::encode_indexed_to_writer(out, input).context("Failed to encode in compact mode")
+    } else if opt.compact {
+        compact::encode_to_writer(out, input).context("Failed to encode in compact mode")
+    } else if let Some(max_width) = opt.max_width {
+        let text =
+            text::encode_pretty(input, opt.indent.unwrap_or_default(), max_width, opt.quoting)
+                .context("Failed to encode in text mode")?;
+        out.write_all(&text)?;
+        Ok(())
     } else {
-        text::encode(input, opt.indent.unwrap_or(2))
+        text::encode_to_writer(out, input, opt.indent.unwrap_or_default(), opt.quoting)
             .context("Failed to encode in text mode")
     }
 }
 
-fn try_tabular_encode(input: &Value, opt: &EncodeOptions) -> Result<Option<Vec<u8>>> {
+/// The rows to encode as a tabular block, if tabular mode applies to `input`.
+fn tabular_rows<'a>(input: &'a Value, opt: &EncodeOptions) -> Result<Option<&'a [Value]>> {
     match input {
         Value::Array(arr) => {
             if tabular::is_uniform_object_array(arr) {
-                let result = if opt.compact {
-                    tabular::encode_tabular_compact(arr)
-                } else {
-                    tabular::encode_tabular_text(arr, opt.indent.unwrap_or(2))
-                }?;
-                Ok(Some(result))
+                Ok(Some(arr))
             } else if opt.strict {
                 anyhow::bail!(
                     "Tabular mode requires uniform array of objects, but array has mixed types"
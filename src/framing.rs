@@ -0,0 +1,214 @@
+//! Several TOON documents in one stream.
+//!
+//! Text documents are separated by a line holding only `---`. The encoder
+//! always quotes the string `---`, so the separator cannot occur inside a
+//! document. Compact documents are framed by a u32 little-endian byte length
+//! followed by the document, magic header included.
+
+use crate::{decoder, encoder, DecodeOptions, EncodeOptions};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::Value;
+use std::io::{self, BufRead, Read, Write};
+
+/// Separator line between text documents.
+pub const TEXT_SEPARATOR: &str = "---";
+
+/// Writes documents to a stream one after another.
+pub struct DocumentWriter<W: Write> {
+    out: W,
+    opt: EncodeOptions,
+    documents: usize,
+    /// Encoded document, reused between writes
+    buf: Vec<u8>,
+}
+
+impl<W: Write> DocumentWriter<W> {
+    /// Documents are framed for compact output if `opt.compact` is set, and
+    /// separated by `---` lines otherwise.
+    pub fn new(out: W, opt: EncodeOptions) -> Self {
+        DocumentWriter {
+            out,
+            opt,
+            documents: 0,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Number of documents written so far.
+    pub fn documents_written(&self) -> usize {
+        self.documents
+    }
+
+    pub fn write(&mut self, value: &Value) -> Result<()> {
+        self.buf.clear();
+        encoder::encode_to_writer(&mut self.buf, value, &self.opt)?;
+
+        if self.opt.compact {
+            let len = u32::try_from(self.buf.len()).context("Document too large for a frame")?;
+            self.out.write_all(&len.to_le_bytes())?;
+            self.out.write_all(&self.buf)?;
+        } else {
+            if self.documents > 0 {
+                self.out.write_all(TEXT_SEPARATOR.as_bytes())?;
+                self.out.write_all(b"\n")?;
+            }
+            self.out.write_all(&self.buf)?;
+            self.out.write_all(b"\n")?;
+        }
+        self.documents += 1;
+        Ok(())
+    }
+
+    /// Serialize `value` and write it like [`write`](Self::write).
+    pub fn serialize<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let value = serde_json::to_value(value).context("Failed to serialize document")?;
+        self.write(&value)
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+
+    /// Flush and return the underlying writer.
+    pub fn into_inner(mut self) -> Result<W> {
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// Reads the documents of a stream one at a time.
+pub struct DocumentReader<R> {
+    input: io::BufReader<R>,
+    opt: DecodeOptions,
+    documents: usize,
+    /// Line where the next text document starts
+    line: usize,
+    done: bool,
+}
+
+impl<R: Read> DocumentReader<R> {
+    /// Read compact frames if `opt.compact` is set, and `---` separated text
+    /// documents otherwise. The framing is not detected, because a text
+    /// stream can start with bytes that look like a frame header.
+    pub fn new(reader: R, opt: DecodeOptions) -> Self {
+        DocumentReader {
+            input: io::BufReader::new(reader),
+            opt,
+            documents: 0,
+            line: 1,
+            done: false,
+        }
+    }
+
+    /// Read the next document, or `None` at the end of the stream.
+    pub fn next_document(&mut self) -> Result<Option<Value>> {
+        if self.done {
+            return Ok(None);
+        }
+        let result = if self.opt.compact {
+            self.next_frame()
+        } else {
+            self.next_text()
+        };
+        match result {
+            Ok(Some(value)) => {
+                self.documents += 1;
+                Ok(Some(value))
+            }
+            other => {
+                self.done = true;
+                other
+            }
+        }
+    }
+
+    fn next_frame(&mut self) -> Result<Option<Value>> {
+        let mut header = [0; 4];
+        let mut read = 0;
+        while read < header.len() {
+            match self.input.read(&mut header[read..]) {
+                Ok(0) if read == 0 => return Ok(None),
+                Ok(0) => anyhow::bail!("Truncated frame header after document {}", self.documents),
+                Ok(n) => read += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e).context("Failed to read TOON input"),
+            }
+        }
+
+        let len = u32::from_le_bytes(header) as u64;
+        let mut frame = Vec::new();
+        self.input
+            .by_ref()
+            .take(len)
+            .read_to_end(&mut frame)
+            .context("Failed to read TOON input")?;
+        if frame.len() as u64 != len {
+            anyhow::bail!("Truncated frame for document {}", self.documents + 1);
+        }
+
+        decoder::decode(&frame, &self.opt)
+            .with_context(|| format!("Failed to decode document {}", self.documents + 1))
+            .map(Some)
+    }
+
+    fn next_text(&mut self) -> Result<Option<Value>> {
+        let mut document = Vec::new();
+        let mut start = self.line;
+        loop {
+            let len = document.len();
+            let read = self
+                .input
+                .read_until(b'\n', &mut document)
+                .context("Failed to read TOON input")?;
+            if read == 0 {
+                break;
+            }
+            self.line += 1;
+            if is_separator(&document[len..]) {
+                document.truncate(len);
+                if is_blank(&document) {
+                    // Leading or repeated separators hold no document
+                    document.clear();
+                    start = self.line;
+                    continue;
+                }
+                break;
+            }
+        }
+
+        if is_blank(&document) {
+            return Ok(None);
+        }
+        decoder::decode(&document, &self.opt)
+            .with_context(|| {
+                format!(
+                    "Failed to decode document {} starting at line {}",
+                    self.documents + 1,
+                    start
+                )
+            })
+            .map(Some)
+    }
+}
+
+fn is_separator(line: &[u8]) -> bool {
+    let end = line
+        .iter()
+        .rposition(|b| !b.is_ascii_whitespace())
+        .map_or(0, |i| i + 1);
+    &line[..end] == TEXT_SEPARATOR.as_bytes()
+}
+
+fn is_blank(bytes: &[u8]) -> bool {
+    bytes.iter().all(u8::is_ascii_whitespace)
+}
+
+impl<R: Read> Iterator for DocumentReader<R> {
+    type Item = Result<Value>;
+
+    fn next(&mut self) -> Option<Result<Value>> {
+        self.next_document().transpose()
+    }
+}
@@ -1,26 +1,63 @@
 //! TOON - A compact, lossless JSON encoding format
-//! 
+//!
 //! TOON provides two encoding modes:
 //! - Text mode: Indentation-based, human-readable format
 //! - Compact mode: Binary length-prefixed format for maximum compression
 
+#[cfg(feature = "tokio")]
+pub mod async_io;
 pub mod codec;
+pub mod compact;
+pub mod de;
 pub mod decoder;
+pub mod diff;
 pub mod encoder;
+pub mod framing;
+pub mod ndjson;
+pub mod patch;
+pub mod query;
+pub mod schema;
+pub mod ser;
 
+pub use codec::events::Event;
+pub use codec::layout::Indent;
+pub use codec::quoting::QuotePolicy;
+pub use codec::tabular::{ColumnType, TabularReader, TabularWriter};
+pub use codec::view::CompactView;
+pub use de::{from_slice, from_str};
+pub use framing::{DocumentReader, DocumentWriter};
+pub use patch::Patch;
+pub use query::Query;
+pub use ser::{to_string, to_writer};
 use serde_json::Value;
+use std::io::Write;
 
 /// Options for encoding JSON to TOON
 #[derive(Default, Clone, Debug)]
 pub struct EncodeOptions {
     /// Use tabular layout for uniform arrays of objects
     pub tabular_arrays: bool,
+    /// Annotate the columns of a tabular header with their types, where
+    /// every cell of a column has the same type (for text mode)
+    pub typed_columns: bool,
     /// Use compact binary format
     pub compact: bool,
-    /// Indentation in spaces (for text mode)
-    pub indent: Option<u8>,
+    /// Give large compact arrays and objects an offset table for random
+    /// access (for compact mode)
+    pub compact_index: bool,
+    /// Indentation unit, spaces or a tab (for text mode)
+    pub indent: Option<Indent>,
+    /// Maximum line width; containers that fit are kept on one line (for text mode)
+    pub max_width: Option<usize>,
+    /// When strings are quoted (for text mode)
+    pub quoting: QuotePolicy,
     /// Fail on validation errors
     pub strict: bool,
+    /// Fail if the input does not match this JSON Schema
+    pub schema: Option<schema::Schema>,
+    /// Write `schema` into compact output and leave out the type tags it
+    /// fixes (for compact mode; TOON-TAB has no schema section)
+    pub embed_schema: bool,
 }
 
 /// Options for decoding TOON to JSON
@@ -30,6 +67,8 @@ pub struct DecodeOptions {
     pub compact: bool,
     /// Fail on validation errors
     pub strict: bool,
+    /// Fail if the decoded document does not match this JSON Schema
+    pub schema: Option<schema::Schema>,
 }
 
 /// Encode a JSON value to TOON format
@@ -37,7 +76,41 @@ pub fn encode_json_to_toon(input: &Value, opt: &EncodeOptions) -> anyhow::Result
     encoder::encode(input, opt)
 }
 
+/// Encode a JSON value to TOON format, streaming it into a writer
+///
+/// Output is buffered internally and flushed before returning.
+pub fn encode_to_writer<W: std::io::Write>(
+    writer: W,
+    input: &Value,
+    opt: &EncodeOptions,
+) -> anyhow::Result<()> {
+    let mut writer = std::io::BufWriter::new(writer);
+    encoder::encode_to_writer(&mut writer, input, opt)?;
+    writer.flush()?;
+    Ok(())
+}
+
 /// Decode TOON bytes to a JSON value
 pub fn decode_toon_to_json(bytes: &[u8], opt: &DecodeOptions) -> anyhow::Result<Value> {
     decoder::decode(bytes, opt)
 }
+
+/// Decode TOON from a reader to a JSON value, reading only as much input at a
+/// time as the parser needs
+pub fn decode_from_reader<R: std::io::Read>(reader: R, opt: &DecodeOptions) -> anyhow::Result<Value> {
+    decoder::decode_reader(reader, opt)
+}
+
+/// Parse TOON from a reader into a stream of events, detecting the format
+/// like [`decode_from_reader`]
+pub fn events<R: std::io::Read>(
+    reader: R,
+    opt: &DecodeOptions,
+) -> anyhow::Result<codec::events::Parser<R>> {
+    codec::events::Parser::new(reader, opt)
+}
+
+/// Rewrite a TOON-Text document in canonical form
+pub fn format_toon(bytes: &[u8], indent: Indent) -> anyhow::Result<Vec<u8>> {
+    codec::text::canonicalize(bytes, indent)
+}
@@ -0,0 +1,196 @@
+//! Conversion between newline-delimited JSON and TOON.
+//!
+//! Each NDJSON record becomes one element of a top-level TOON array, and
+//! decoding turns each element of a top-level array back into one line.
+//! Records are converted one at a time in both directions, so only one is
+//! held in memory, except for compact arrays, whose element count comes
+//! before the elements, and tabular blocks, which are only written once
+//! every record is known to fit.
+
+use crate::codec::events::{build_value, Event, Parser};
+use crate::codec::tabular;
+use crate::codec::text;
+use crate::{encoder, DecodeOptions, EncodeOptions};
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::io::{self, BufRead, Write};
+
+/// Encode NDJSON records as a TOON array.
+///
+/// With `tabular_arrays` the records are written as one tabular block when
+/// every record is an object with the same keys as the first, exactly as
+/// [`encode_json_to_toon`](crate::encode_json_to_toon) writes the array,
+/// including `typed_columns`. Otherwise they are written as a plain array,
+/// or with `strict` nothing is written and encoding fails. Text output of a
+/// plain array has one record per line, each encoded on its own. Blank lines
+/// are skipped. A schema in `opt` applies to each record rather than to the
+/// array, so it is never embedded.
+pub fn encode<R: BufRead, W: Write>(reader: R, out: W, opt: &EncodeOptions) -> Result<()> {
+    let records = reader.lines().enumerate().filter_map(|(i, line)| {
+        let record = line
+            .context("Failed to read NDJSON input")
+            .and_then(|line| match line.trim() {
+                "" => Ok(None),
+                line => {
+                    let record = serde_json::from_str::<Value>(line)
+                        .with_context(|| format!("Invalid JSON on line {}", i + 1))?;
+                    if let Some(schema) = &opt.schema {
+                        schema
+                            .check(&record)
+                            .with_context(|| format!("Invalid record on line {}", i + 1))?;
+                    }
+                    Ok(Some(record))
+                }
+            });
+        record.transpose().map(|record| (i + 1, record))
+    });
+
+    if !opt.tabular_arrays {
+        return encode_array(records, out, opt);
+    }
+
+    let (lines, records): (Vec<_>, Vec<_>) = records
+        .map(|(line, record)| record.map(|record| (line, record)))
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .unzip();
+    if tabular::is_uniform_object_array(&records) {
+        // The same tabular path as encoder::encode takes for a uniform array
+        let mut out = io::BufWriter::new(out);
+        if opt.compact {
+            tabular::write_tabular_compact(&mut out, &records)?;
+        } else {
+            let indent = opt.indent.unwrap_or_default();
+            tabular::write_tabular_text(
+                &mut out,
+                &records,
+                indent,
+                opt.quoting,
+                opt.typed_columns,
+            )?;
+        }
+        out.flush()?;
+        return Ok(());
+    }
+    if opt.strict && !records.is_empty() {
+        anyhow::bail!(
+            "Record on line {} does not fit the tabular block",
+            lines[misfit(&records)]
+        );
+    }
+    encode_array(lines.into_iter().zip(records.into_iter().map(Ok)), out, opt)
+}
+
+/// The index of the first record that keeps `records` from being a tabular
+/// block: the first one, unless it is a non-empty object, or else the first
+/// whose keys differ from it.
+fn misfit(records: &[Value]) -> usize {
+    let first = match records[0].as_object() {
+        Some(first) if !first.is_empty() => first,
+        _ => return 0,
+    };
+    records
+        .iter()
+        .position(|record| {
+            !record.as_object().is_some_and(|obj| {
+                obj.len() == first.len() && obj.keys().all(|key| first.contains_key(key))
+            })
+        })
+        .unwrap_or(0)
+}
+
+/// Encode records, numbered by line, as a plain array.
+fn encode_array<W: Write>(
+    records: impl Iterator<Item = (usize, Result<Value>)>,
+    out: W,
+    opt: &EncodeOptions,
+) -> Result<()> {
+    let mut out = io::BufWriter::new(out);
+    if opt.compact {
+        let records = records
+            .map(|(_, record)| record)
+            .collect::<Result<Vec<_>>>()?;
+        let opt = EncodeOptions {
+            schema: None,
+            embed_schema: false,
+            ..opt.clone()
+        };
+        encoder::encode_to_writer(&mut out, &Value::Array(records), &opt)?;
+    } else {
+        let indent_str = opt.indent.unwrap_or_default().repeat(1);
+        let mut line = String::new();
+        let mut count = 0;
+        for (_, record) in records {
+            line.clear();
+            line.push_str(if count == 0 { "[\n" } else { ",\n" });
+            line.push_str(&indent_str);
+            text::encode_inline(&mut line, &record?, opt.quoting)?;
+            out.write_all(line.as_bytes())?;
+            count += 1;
+        }
+        let end: &[u8] = if count == 0 { b"[]" } else { b"\n]" };
+        out.write_all(end)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Decode a TOON document to NDJSON: one line per element of a top-level
+/// array, including the rows of a tabular block, or a single line for any
+/// other value. A schema in `opt` applies to each line. A compact document
+/// with an embedded schema is read whole and checked against it before any
+/// line is written.
+pub fn decode<R: io::Read, W: Write>(reader: R, out: W, opt: &DecodeOptions) -> Result<()> {
+    let mut out = io::BufWriter::new(out);
+    let mut parser = Parser::new(reader, opt)?;
+    if parser.schema().is_some() {
+        match parser.next_value()?.context("Empty input")? {
+            Value::Array(elements) => {
+                for value in &elements {
+                    write_line(&mut out, value, opt)?;
+                }
+            }
+            value => write_line(&mut out, &value, opt)?,
+        }
+        out.flush()?;
+        return Ok(());
+    }
+
+    let first = parser
+        .next_event()?
+        .ok_or_else(|| anyhow::anyhow!("Empty input"))?;
+
+    if first != Event::StartArray {
+        let value = build_value(first, || parser.next_event())?;
+        write_line(&mut out, &value, opt)?;
+    } else {
+        let mut columns = Vec::new();
+        loop {
+            let value = match parser.next_event()? {
+                Some(Event::EndArray) => break,
+                Some(Event::TabularHeader(header)) => {
+                    columns = header;
+                    continue;
+                }
+                Some(Event::Row(cells)) => {
+                    Value::Object(columns.iter().cloned().zip(cells).collect())
+                }
+                Some(event) => build_value(event, || parser.next_event())?,
+                None => anyhow::bail!("Unexpected end of input"),
+            };
+            write_line(&mut out, &value, opt)?;
+        }
+    }
+
+    out.flush()?;
+    Ok(())
+}
+
+fn write_line<W: Write>(out: &mut W, value: &Value, opt: &DecodeOptions) -> Result<()> {
+    if let Some(schema) = &opt.schema {
+        schema.check(value)?;
+    }
+    serde_json::to_writer(&mut *out, value)?;
+    out.write_all(b"\n")?;
+    Ok(())
+}
@@ -0,0 +1,493 @@
+//! JSON Patch (RFC 6902) and JSON Merge Patch (RFC 7396) for TOON documents.
+//!
+//! Patches apply to decoded values. [`patch_document`] decodes a document,
+//! patches it and writes it back in the format it came in. A compact or
+//! TOON-TAB document is encoded again, keeping its offset tables or its
+//! embedded schema, which the patched value must still match.
+//!
+//! A text document is edited in place: the parts of it that the patch does
+//! not change keep their bytes, layout and key order. A changed value is
+//! replaced by its encoding, on one line if the old value was and indented
+//! like its line otherwise. Entries added to an object go before the first
+//! following key in sorted order, and elements appended to an array go after
+//! the last one, with the same separator as their neighbours. Any other
+//! change to an array, or a change to a tabular block, rewrites that whole
+//! array; a tabular block is written again with its column types inferred
+//! from the patched rows.
+
+use crate::codec::compact::{self, MAGIC};
+use crate::codec::events::{Event, TextParser};
+use crate::codec::layout::Indent;
+use crate::codec::quoting::{self, QuotePolicy};
+use crate::codec::tabular::{self, TABULAR_MAGIC};
+use crate::codec::text;
+use crate::codec::view::CompactView;
+use crate::query::{array_index, escape_token, pointer_tokens};
+use crate::{decoder, encoder, DecodeOptions, EncodeOptions};
+use anyhow::{Context, Result};
+use serde_json::{Map, Value};
+
+/// A patch to apply to a document.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Patch {
+    /// An array of RFC 6902 operations
+    Json(Value),
+    /// An RFC 7396 merge patch
+    Merge(Value),
+}
+
+impl Patch {
+    /// Apply the patch to `doc`. A JSON Patch is applied atomically: if any
+    /// operation fails, `doc` is left unchanged.
+    pub fn apply(&self, doc: &mut Value) -> Result<()> {
+        match self {
+            Patch::Json(patch) => apply_patch(doc, patch),
+            Patch::Merge(patch) => {
+                apply_merge_patch(doc, patch);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Apply RFC 6902 operations to `doc`, all or none of them.
+pub fn apply_patch(doc: &mut Value, patch: &Value) -> Result<()> {
+    let ops = patch
+        .as_array()
+        .context("JSON Patch must be an array of operations")?;
+    let mut patched = doc.clone();
+    for (i, op) in ops.iter().enumerate() {
+        apply_operation(&mut patched, op)
+            .with_context(|| format!("Patch operation {} failed", i + 1))?;
+    }
+    *doc = patched;
+    Ok(())
+}
+
+/// Apply an RFC 7396 merge patch to `doc`: objects are merged key by key,
+/// `null` removes a key, and any other value replaces the target.
+pub fn apply_merge_patch(doc: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *doc = patch.clone();
+        return;
+    };
+    if !doc.is_object() {
+        *doc = Value::Object(Default::default());
+    }
+    let Value::Object(target) = doc else {
+        unreachable!("target was just made an object");
+    };
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            apply_merge_patch(target.entry(key.clone()).or_insert(Value::Null), value);
+        }
+    }
+}
+
+/// Decode a document in any format, apply `patch`, and write the result in
+/// the same format.
+pub fn patch_document(bytes: &[u8], patch: &Patch, opt: &DecodeOptions) -> Result<Vec<u8>> {
+    let old = decoder::decode(bytes, opt)?;
+    let mut value = old.clone();
+    patch.apply(&mut value)?;
+
+    if bytes.starts_with(TABULAR_MAGIC) {
+        let format = EncodeOptions {
+            compact: true,
+            tabular_arrays: true,
+            ..Default::default()
+        };
+        encoder::encode(&value, &format)
+    } else if bytes.starts_with(MAGIC) {
+        let schema = compact::embedded_schema(bytes).ok().flatten();
+        let format = EncodeOptions {
+            compact: true,
+            compact_index: CompactView::new(bytes).is_ok_and(has_index),
+            embed_schema: schema.is_some(),
+            schema,
+            ..Default::default()
+        };
+        encoder::encode(&value, &format)
+    } else {
+        let mut parser = text::events(bytes);
+        let first = parser.next_event()?.context("Unexpected end of input")?;
+        let span = read_span(&mut parser, first)?;
+        let mut splicer = Splicer {
+            bytes,
+            indent: text::detect_indent(bytes).unwrap_or_default(),
+            edits: Vec::new(),
+        };
+        splicer.splice(&span, &old, &value, true)?;
+        Ok(splicer.finish())
+    }
+}
+
+/// Whether any container in `view` has an offset table.
+fn has_index(view: CompactView) -> bool {
+    let mut stack = vec![view];
+    while let Some(view) = stack.pop() {
+        if view.is_indexed() {
+            return true;
+        }
+        if let Some(elements) = view.elements() {
+            stack.extend(elements);
+        } else if let Some(entries) = view.entries() {
+            stack.extend(entries.map(|(_, value)| value));
+        }
+    }
+    false
+}
+
+/// The bytes a value of a text document takes up.
+struct Span {
+    start: usize,
+    end: usize,
+    kind: SpanKind,
+}
+
+enum SpanKind {
+    Scalar,
+    Array(Vec<Span>),
+    /// Entries with the offset of their key
+    Object(Vec<(String, usize, Span)>),
+    /// A tabular block, and whether its header has column types
+    Tabular(bool),
+}
+
+/// Read the spans of the value that starts with `first`.
+fn read_span(parser: &mut TextParser<&[u8]>, first: Event) -> Result<Span> {
+    let start = parser.token_start() as usize;
+    let next =
+        |parser: &mut TextParser<&[u8]>| parser.next_event()?.context("Unexpected end of input");
+    let kind = match first {
+        Event::StartObject => {
+            let mut entries = Vec::new();
+            loop {
+                match next(parser)? {
+                    Event::Key(key) => {
+                        let key_start = parser.token_start() as usize;
+                        let first = next(parser)?;
+                        entries.push((key, key_start, read_span(parser, first)?));
+                    }
+                    Event::EndObject => break SpanKind::Object(entries),
+                    other => anyhow::bail!("Unexpected {:?} in object", other),
+                }
+            }
+        }
+        Event::StartArray => {
+            let mut items = Vec::new();
+            loop {
+                match next(parser)? {
+                    Event::EndArray => break SpanKind::Array(items),
+                    Event::TabularHeader(_) => {
+                        let typed = parser
+                            .column_types()
+                            .is_some_and(|types| types.iter().any(Option::is_some));
+                        while next(parser)? != Event::EndArray {}
+                        break SpanKind::Tabular(typed);
+                    }
+                    first => items.push(read_span(parser, first)?),
+                }
+            }
+        }
+        _ => SpanKind::Scalar,
+    };
+    Ok(Span {
+        start,
+        end: parser.offset() as usize,
+        kind,
+    })
+}
+
+/// Edits to a text document, each replacing a byte range with new text.
+struct Splicer<'a> {
+    bytes: &'a [u8],
+    indent: Indent,
+    edits: Vec<(usize, usize, String)>,
+}
+
+impl<'a> Splicer<'a> {
+    /// Edit `span`, which holds `old`, to hold `new`. `broken` says whether
+    /// the container around it has its entries on separate lines.
+    fn splice(&mut self, span: &Span, old: &Value, new: &Value, broken: bool) -> Result<()> {
+        if old == new {
+            return Ok(());
+        }
+        let broken = self.is_broken(span, broken);
+        match (&span.kind, old, new) {
+            (SpanKind::Object(entries), Value::Object(old), Value::Object(new))
+                if entries.len() == old.len() =>
+            {
+                self.splice_object(span, entries, old, new, broken)
+            }
+            (SpanKind::Array(items), Value::Array(old), Value::Array(new))
+                if !items.is_empty() && new.len() >= old.len() =>
+            {
+                for ((item, old), new) in items.iter().zip(old).zip(new) {
+                    self.splice(item, old, new, broken)?;
+                }
+                let last = &items[items.len() - 1];
+                let prefix = self.line_prefix(items[0].start);
+                let before = items.len().checked_sub(2).map(|i| &items[i]);
+                let separator = self.separator(before, last, broken);
+                let mut text = String::new();
+                for value in &new[old.len()..] {
+                    text.push_str(&separator);
+                    text.push_str(&self.render(value, broken, prefix)?);
+                }
+                self.edit(last.end, last.end, text);
+                Ok(())
+            }
+            _ => self.replace(span, new, broken),
+        }
+    }
+
+    fn splice_object(
+        &mut self,
+        span: &Span,
+        entries: &[(String, usize, Span)],
+        old: &Map<String, Value>,
+        new: &Map<String, Value>,
+        broken: bool,
+    ) -> Result<()> {
+        let kept: Vec<usize> = (0..entries.len())
+            .filter(|&i| new.contains_key(&entries[i].0))
+            .collect();
+        let (Some(&first), Some(&last)) = (kept.first(), kept.last()) else {
+            return self.replace(span, &Value::Object(new.clone()), broken);
+        };
+        let separator = match entries.len() {
+            1 => self.separator(None, &entries[0].2, broken),
+            _ => {
+                let (before, after) = (&entries[0].2, &entries[1]);
+                String::from_utf8_lossy(&self.bytes[before.end..after.1]).into_owned()
+            }
+        };
+
+        // Entries before the first one kept go with the separator after them,
+        // and later ones with the separator before them
+        if first > 0 {
+            self.edit(entries[0].1, entries[first].1, String::new());
+        }
+        for i in first + 1..entries.len() {
+            if !new.contains_key(&entries[i].0) {
+                self.edit(entries[i - 1].2.end, entries[i].2.end, String::new());
+            }
+        }
+
+        for &i in &kept {
+            let (key, _, span) = &entries[i];
+            self.splice(span, &old[key], &new[key], broken)?;
+        }
+
+        let prefix = self.line_prefix(entries[first].1);
+        for (key, value) in new.iter().filter(|(key, _)| !old.contains_key(*key)) {
+            let mut entry = String::new();
+            quoting::write_string(&mut entry, key, QuotePolicy::Minimal);
+            entry.push_str(": ");
+            entry.push_str(&self.render(value, broken, prefix)?);
+            match kept.iter().find(|&&i| entries[i].0 > *key) {
+                Some(&i) => self.edit(entries[i].1, entries[i].1, entry + &separator),
+                None => {
+                    let end = entries[last].2.end;
+                    self.edit(end, end, separator.clone() + &entry);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Replace the whole of `span` with `value`.
+    fn replace(&mut self, span: &Span, value: &Value, broken: bool) -> Result<()> {
+        let prefix = self.line_prefix(span.start);
+        let text = match (&span.kind, value) {
+            (SpanKind::Tabular(typed), Value::Array(rows))
+                if tabular::is_uniform_object_array(rows) =>
+            {
+                let mut out = Vec::new();
+                tabular::write_tabular_text(
+                    &mut out,
+                    rows,
+                    self.indent,
+                    QuotePolicy::Minimal,
+                    *typed,
+                )?;
+                String::from_utf8(out)?.replace('\n', &format!("\n{}", prefix))
+            }
+            (SpanKind::Tabular(_), _) => self.render(value, true, prefix)?,
+            _ => self.render(value, broken, prefix)?,
+        };
+        self.edit(span.start, span.end, text);
+        Ok(())
+    }
+
+    /// Whether the value at `span` is laid out across lines. A scalar or an
+    /// empty container follows the container around it.
+    fn is_broken(&self, span: &Span, outer: bool) -> bool {
+        let empty = match &span.kind {
+            SpanKind::Scalar => true,
+            SpanKind::Array(items) => items.is_empty(),
+            SpanKind::Object(entries) => entries.is_empty(),
+            SpanKind::Tabular(_) => false,
+        };
+        self.bytes[span.start..span.end].contains(&b'\n') || (empty && outer)
+    }
+
+    /// The text between two neighbouring entries of a container, taken from
+    /// `before` and `after` when there are two, and made up otherwise.
+    fn separator(&self, before: Option<&Span>, after: &Span, broken: bool) -> String {
+        match before {
+            Some(before) => {
+                String::from_utf8_lossy(&self.bytes[before.end..after.start]).into_owned()
+            }
+            None if broken => format!(",\n{}", self.line_prefix(after.start)),
+            None => ", ".to_string(),
+        }
+    }
+
+    /// The indentation of the line that `pos` is on.
+    fn line_prefix(&self, pos: usize) -> &'a str {
+        let start = self.bytes[..pos]
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |i| i + 1);
+        let len = self.bytes[start..]
+            .iter()
+            .take_while(|&&b| b == b' ' || b == b'\t')
+            .count();
+        std::str::from_utf8(&self.bytes[start..start + len]).unwrap_or_default()
+    }
+
+    /// Encode `value` to go on a line indented with `prefix`.
+    fn render(&self, value: &Value, broken: bool, prefix: &str) -> Result<String> {
+        let mut text = String::new();
+        if broken {
+            let out = text::encode(value, self.indent, QuotePolicy::Minimal)?;
+            text = String::from_utf8(out)?.replace('\n', &format!("\n{}", prefix));
+        } else {
+            text::encode_inline(&mut text, value, QuotePolicy::Minimal)?;
+        }
+        Ok(text)
+    }
+
+    fn edit(&mut self, start: usize, end: usize, text: String) {
+        self.edits.push((start, end, text));
+    }
+
+    /// The document with every edit made.
+    fn finish(mut self) -> Vec<u8> {
+        self.edits.sort_by_key(|&(start, end, _)| (start, end));
+        let mut out = Vec::with_capacity(self.bytes.len());
+        let mut pos = 0;
+        for (start, end, text) in self.edits {
+            out.extend_from_slice(&self.bytes[pos..start]);
+            out.extend_from_slice(text.as_bytes());
+            pos = end;
+        }
+        out.extend_from_slice(&self.bytes[pos..]);
+        out
+    }
+}
+
+fn apply_operation(doc: &mut Value, op: &Value) -> Result<()> {
+    let field = |name: &str| {
+        op.get(name)
+            .with_context(|| format!("Missing {:?} in patch operation", name))
+    };
+    let pointer = |name: &str| -> Result<Vec<String>> {
+        let path = field(name)?
+            .as_str()
+            .with_context(|| format!("{:?} must be a string", name))?;
+        pointer_tokens(path)
+    };
+
+    let name = field("op")?.as_str().context("\"op\" must be a string")?;
+    let path = pointer("path")?;
+    match name {
+        "add" => add(doc, &path, field("value")?.clone()),
+        "remove" => remove(doc, &path).map(drop),
+        "replace" => {
+            *resolve_mut(doc, &path)? = field("value")?.clone();
+            Ok(())
+        }
+        "move" => {
+            let from = pointer("from")?;
+            if path.len() > from.len() && path.starts_with(&from) {
+                anyhow::bail!("Cannot move a value into one of its children");
+            }
+            let value = remove(doc, &from)?;
+            add(doc, &path, value)
+        }
+        "copy" => {
+            let value = resolve_mut(doc, &pointer("from")?)?.clone();
+            add(doc, &path, value)
+        }
+        "test" => {
+            if *resolve_mut(doc, &path)? != *field("value")? {
+                anyhow::bail!("Test failed at {}", display_pointer(&path));
+            }
+            Ok(())
+        }
+        other => anyhow::bail!("Unknown patch operation {:?}", other),
+    }
+}
+
+fn resolve_mut<'a>(doc: &'a mut Value, path: &[String]) -> Result<&'a mut Value> {
+    let mut node = doc;
+    for (depth, token) in path.iter().enumerate() {
+        let child = match node {
+            Value::Object(obj) => obj.get_mut(token),
+            Value::Array(arr) => array_index(token).and_then(|i| arr.get_mut(i)),
+            _ => None,
+        };
+        node =
+            child.with_context(|| format!("No value at {}", display_pointer(&path[..=depth])))?;
+    }
+    Ok(node)
+}
+
+fn add(doc: &mut Value, path: &[String], value: Value) -> Result<()> {
+    let Some((last, parent)) = path.split_last() else {
+        *doc = value;
+        return Ok(());
+    };
+    match resolve_mut(doc, parent)? {
+        Value::Object(obj) => {
+            obj.insert(last.clone(), value);
+        }
+        Value::Array(arr) if last == "-" => arr.push(value),
+        Value::Array(arr) => match array_index(last) {
+            Some(i) if i <= arr.len() => arr.insert(i, value),
+            _ => anyhow::bail!("Invalid array index at {}", display_pointer(path)),
+        },
+        _ => anyhow::bail!("{} is not an array or object", display_pointer(parent)),
+    }
+    Ok(())
+}
+
+fn remove(doc: &mut Value, path: &[String]) -> Result<Value> {
+    let Some((last, parent)) = path.split_last() else {
+        anyhow::bail!("Cannot remove the whole document");
+    };
+    let removed = match resolve_mut(doc, parent)? {
+        Value::Object(obj) => obj.remove(last),
+        Value::Array(arr) => match array_index(last) {
+            Some(i) if i < arr.len() => Some(arr.remove(i)),
+            _ => None,
+        },
+        _ => None,
+    };
+    removed.with_context(|| format!("No value at {}", display_pointer(path)))
+}
+
+/// Format reference tokens as a JSON Pointer.
+fn display_pointer(path: &[String]) -> String {
+    if path.is_empty() {
+        return "the document root".to_string();
+    }
+    path.iter()
+        .map(|token| format!("/{}", escape_token(token)))
+        .collect()
+}
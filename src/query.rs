@@ -0,0 +1,391 @@
+//! Path queries over decoded values and compact documents.
+//!
+//! A [`Query`] is either a JSON Pointer (RFC 6901), such as `/users/0/name`,
+//! or a JSONPath expression from this subset:
+//!
+//! - `$`: the root
+//! - `.name`, `['name']`, `["name"]`: an object member
+//! - `[2]`, `[-1]`: an array element, counting from the end if negative
+//! - `.*`, `[*]`: every member or element
+//! - `..name`, `..*`, `..[0]`: the step applied at every depth
+//!
+//! Queries run on `serde_json::Value`s and on [`CompactView`]s. On a view,
+//! only the containers along the path are walked, and subtrees off the path
+//! are skipped without being decoded.
+
+use crate::codec::compact::MAGIC;
+use crate::codec::view::CompactView;
+use crate::{decoder, DecodeOptions};
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::fmt;
+use std::str::FromStr;
+
+/// A parsed JSON Pointer or JSONPath expression.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Query {
+    source: String,
+    steps: Vec<Step>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Step {
+    /// JSON Pointer reference token: an object member, or an array index if
+    /// the token is one
+    Token(String),
+    Key(String),
+    Index(i64),
+    Wildcard,
+    /// The inner step applied to the value and all its descendants
+    Descendant(Box<Step>),
+}
+
+impl Query {
+    /// Parse a JSON Pointer, which is empty or starts with `/`, or a JSONPath
+    /// expression, which starts with `$`.
+    pub fn parse(source: &str) -> Result<Self> {
+        let steps = if source.is_empty() || source.starts_with('/') {
+            parse_pointer(source)?
+        } else if source.starts_with('$') {
+            JsonPath::new(source).parse()?
+        } else {
+            anyhow::bail!(
+                "Invalid query {:?}: expected a JSON Pointer (/a/b) or a JSONPath ($.a.b)",
+                source
+            );
+        };
+        Ok(Query {
+            source: source.to_string(),
+            steps,
+        })
+    }
+
+    /// Whether the query selects at most one value: a JSON Pointer, or a
+    /// JSONPath without wildcards or descendant steps.
+    pub fn is_singular(&self) -> bool {
+        self.steps
+            .iter()
+            .all(|step| !matches!(step, Step::Wildcard | Step::Descendant(_)))
+    }
+
+    /// The values selected in `value`, in document order.
+    pub fn select<'a>(&self, value: &'a Value) -> Vec<&'a Value> {
+        select_nodes(&self.steps, value)
+    }
+
+    /// The values selected in a compact document, in document order.
+    pub fn select_view<'a>(&self, view: CompactView<'a>) -> Vec<CompactView<'a>> {
+        select_nodes(&self.steps, view)
+    }
+}
+
+impl FromStr for Query {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Query::parse(s)
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// Run `query` on a TOON document in any format. Compact documents are
+/// queried in place and only the selected values are decoded, so, as with
+/// [`CompactView`], they are not checked against an embedded schema; other
+/// documents are decoded first.
+pub fn select_document(bytes: &[u8], query: &Query, opt: &DecodeOptions) -> Result<Vec<Value>> {
+    if bytes.starts_with(MAGIC) {
+        let view = CompactView::new(bytes).context("Failed to read compact document")?;
+        return Ok(query
+            .select_view(view)
+            .iter()
+            .map(CompactView::to_value)
+            .collect());
+    }
+    let value = decoder::decode(bytes, opt)?;
+    Ok(query.select(&value).into_iter().cloned().collect())
+}
+
+/// What a query needs from a node.
+trait Node: Copy {
+    fn member(self, key: &str) -> Option<Self>;
+    fn element(self, index: usize) -> Option<Self>;
+    /// Number of elements, if this is an array.
+    fn array_len(self) -> Option<usize>;
+    /// Members or elements in order.
+    fn children(self) -> Vec<Self>;
+}
+
+impl Node for &Value {
+    fn member(self, key: &str) -> Option<Self> {
+        self.as_object()?.get(key)
+    }
+
+    fn element(self, index: usize) -> Option<Self> {
+        self.as_array()?.get(index)
+    }
+
+    fn array_len(self) -> Option<usize> {
+        self.as_array().map(Vec::len)
+    }
+
+    fn children(self) -> Vec<Self> {
+        match self {
+            Value::Array(arr) => arr.iter().collect(),
+            Value::Object(obj) => obj.values().collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl Node for CompactView<'_> {
+    fn member(self, key: &str) -> Option<Self> {
+        self.get(key)
+    }
+
+    fn element(self, index: usize) -> Option<Self> {
+        self.index(index)
+    }
+
+    fn array_len(self) -> Option<usize> {
+        if self.is_array() {
+            self.len()
+        } else {
+            None
+        }
+    }
+
+    fn children(self) -> Vec<Self> {
+        if let Some(elements) = self.elements() {
+            return elements.collect();
+        }
+        self.entries()
+            .map(|entries| entries.map(|(_, value)| value).collect())
+            .unwrap_or_default()
+    }
+}
+
+fn select_nodes<N: Node>(steps: &[Step], root: N) -> Vec<N> {
+    let mut nodes = vec![root];
+    for step in steps {
+        let mut next = Vec::new();
+        for node in nodes {
+            match step {
+                Step::Descendant(inner) => {
+                    for node in descendants(node) {
+                        apply(inner, node, &mut next);
+                    }
+                }
+                step => apply(step, node, &mut next),
+            }
+        }
+        nodes = next;
+    }
+    nodes
+}
+
+/// The node and all nodes below it in document order. The walk keeps its
+/// own stack, so deep documents cannot overflow the call stack.
+fn descendants<N: Node>(node: N) -> Vec<N> {
+    let mut out = Vec::new();
+    let mut stack = vec![node];
+    while let Some(node) = stack.pop() {
+        out.push(node);
+        stack.extend(node.children().into_iter().rev());
+    }
+    out
+}
+
+fn apply<N: Node>(step: &Step, node: N, out: &mut Vec<N>) {
+    let selected = match step {
+        Step::Token(token) => match node.array_len() {
+            Some(_) => array_index(token).and_then(|i| node.element(i)),
+            None => node.member(token),
+        },
+        Step::Key(key) => node.member(key),
+        Step::Index(index) => node.array_len().and_then(|len| {
+            let index = if *index < 0 {
+                len.checked_sub(index.unsigned_abs() as usize)?
+            } else {
+                *index as usize
+            };
+            node.element(index)
+        }),
+        Step::Wildcard => {
+            out.extend(node.children());
+            return;
+        }
+        Step::Descendant(_) => unreachable!("descendant steps are expanded by the caller"),
+    };
+    out.extend(selected);
+}
+
+/// A JSON Pointer array index: digits without a leading zero.
+pub(crate) fn array_index(token: &str) -> Option<usize> {
+    let digits = !token.is_empty() && token.bytes().all(|b| b.is_ascii_digit());
+    if !digits || (token.len() > 1 && token.starts_with('0')) {
+        return None;
+    }
+    token.parse().ok()
+}
+
+fn parse_pointer(source: &str) -> Result<Vec<Step>> {
+    Ok(pointer_tokens(source)?
+        .into_iter()
+        .map(Step::Token)
+        .collect())
+}
+
+/// Escape a reference token for a JSON Pointer.
+pub(crate) fn escape_token(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+/// The unescaped reference tokens of a JSON Pointer.
+pub(crate) fn pointer_tokens(source: &str) -> Result<Vec<String>> {
+    if source.is_empty() {
+        return Ok(Vec::new());
+    }
+    let Some(rest) = source.strip_prefix('/') else {
+        anyhow::bail!(
+            "Invalid JSON Pointer {:?}: must be empty or start with '/'",
+            source
+        );
+    };
+    rest.split('/')
+        .map(|token| {
+            let mut out = String::with_capacity(token.len());
+            let mut chars = token.chars();
+            while let Some(c) = chars.next() {
+                if c != '~' {
+                    out.push(c);
+                    continue;
+                }
+                match chars.next() {
+                    Some('0') => out.push('~'),
+                    Some('1') => out.push('/'),
+                    _ => anyhow::bail!("Invalid escape in JSON Pointer {:?}", source),
+                }
+            }
+            Ok(out)
+        })
+        .collect()
+}
+
+/// Parser for the JSONPath subset.
+struct JsonPath<'a> {
+    source: &'a str,
+    pos: usize,
+}
+
+impl<'a> JsonPath<'a> {
+    fn new(source: &'a str) -> Self {
+        JsonPath { source, pos: 1 }
+    }
+
+    fn error(&self, msg: &str) -> anyhow::Error {
+        anyhow::anyhow!(
+            "Invalid JSONPath {:?} at position {}: {}",
+            self.source,
+            self.pos,
+            msg
+        )
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.source[self.pos..]
+    }
+
+    fn eat(&mut self, prefix: &str) -> bool {
+        let found = self.rest().starts_with(prefix);
+        if found {
+            self.pos += prefix.len();
+        }
+        found
+    }
+
+    fn parse(mut self) -> Result<Vec<Step>> {
+        let mut steps = Vec::new();
+        while !self.rest().is_empty() {
+            let step = if self.eat("..") {
+                let inner = if self.rest().starts_with('[') {
+                    self.pos += 1;
+                    self.bracket()?
+                } else {
+                    self.dotted()?
+                };
+                Step::Descendant(Box::new(inner))
+            } else if self.eat(".") {
+                self.dotted()?
+            } else if self.eat("[") {
+                self.bracket()?
+            } else {
+                return Err(self.error("expected '.' or '['"));
+            };
+            steps.push(step);
+        }
+        Ok(steps)
+    }
+
+    /// A member name or `*` after a dot.
+    fn dotted(&mut self) -> Result<Step> {
+        if self.eat("*") {
+            return Ok(Step::Wildcard);
+        }
+        let len = self.rest().find(['.', '[']).unwrap_or(self.rest().len());
+        if len == 0 {
+            return Err(self.error("expected a member name"));
+        }
+        let name = &self.rest()[..len];
+        self.pos += len;
+        Ok(Step::Key(name.to_string()))
+    }
+
+    /// The inside of a bracket, up to and including the closing `]`.
+    fn bracket(&mut self) -> Result<Step> {
+        let step = if self.eat("*") {
+            Step::Wildcard
+        } else if self.rest().starts_with(['\'', '"']) {
+            Step::Key(self.quoted()?)
+        } else {
+            let len = self.rest().find(']').unwrap_or(self.rest().len());
+            let index = self.rest()[..len]
+                .trim()
+                .parse()
+                .map_err(|_| self.error("expected an index, a quoted name or '*'"))?;
+            self.pos += len;
+            Step::Index(index)
+        };
+        if !self.eat("]") {
+            return Err(self.error("expected ']'"));
+        }
+        Ok(step)
+    }
+
+    /// A quoted member name, with backslash escapes.
+    fn quoted(&mut self) -> Result<String> {
+        let quote = self.rest().chars().next().unwrap_or('\'');
+        self.pos += 1;
+        let mut out = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some((_, escaped)) => out.push(escaped),
+                    None => break,
+                },
+                c if c == quote => {
+                    self.pos += i + 1;
+                    return Ok(out);
+                }
+                c => out.push(c),
+            }
+        }
+        self.pos = self.source.len();
+        Err(self.error("unterminated quoted name"))
+    }
+}
@@ -0,0 +1,248 @@
+//! Schema inference from sample documents.
+//!
+//! [`SchemaInferrer`] observes decoded documents one at a time and describes
+//! every value it saw: its types, the keys every object had (required) or
+//! only some had (optional), numeric ranges, and strings that repeat from a
+//! small set of values (enum candidates). Arrays that qualify as tabular
+//! blocks, by [`is_uniform_object_array`], have rows with a fixed set of
+//! columns, so their items are inferred as closed objects.
+
+use super::DRAFT_2020_12;
+use crate::codec::tabular::is_uniform_object_array;
+use serde_json::{json, Map, Number, Value};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Options for schema inference.
+#[derive(Clone, Debug)]
+pub struct InferOptions {
+    /// Most distinct values of a string that can become an `enum`
+    pub enum_limit: usize,
+}
+
+impl Default for InferOptions {
+    fn default() -> Self {
+        InferOptions { enum_limit: 10 }
+    }
+}
+
+/// Builds a JSON Schema that every observed document satisfies.
+#[derive(Default)]
+pub struct SchemaInferrer {
+    opt: InferOptions,
+    root: Shape,
+}
+
+/// Everything observed at one position in the documents.
+#[derive(Default)]
+struct Shape {
+    null: bool,
+    boolean: bool,
+    number: Option<NumberShape>,
+    string: Option<StringShape>,
+    /// The items of arrays
+    array: Option<Box<Shape>>,
+    object: Option<Box<ObjectShape>>,
+}
+
+struct NumberShape {
+    min: Number,
+    max: Number,
+    integers: bool,
+}
+
+struct StringShape {
+    count: usize,
+    /// Distinct values, until there are more than the enum limit
+    values: Option<BTreeSet<String>>,
+}
+
+#[derive(Default)]
+struct ObjectShape {
+    count: usize,
+    /// Each key's values and the number of objects that had it
+    properties: BTreeMap<String, (Shape, usize)>,
+    /// Whether every object seen here was a row of a tabular block
+    closed: bool,
+}
+
+impl SchemaInferrer {
+    pub fn new(opt: InferOptions) -> Self {
+        SchemaInferrer {
+            opt,
+            root: Shape::default(),
+        }
+    }
+
+    /// Add a sample document.
+    pub fn observe(&mut self, value: &Value) {
+        self.root.observe(value, false, &self.opt);
+    }
+
+    /// The schema of all documents observed so far. With no documents, it
+    /// accepts nothing.
+    pub fn schema(&self) -> Value {
+        let mut schema = match self.root.schema() {
+            Some(Value::Object(schema)) => schema,
+            _ => return json!({"$schema": DRAFT_2020_12, "not": {}}),
+        };
+        schema.insert("$schema".into(), DRAFT_2020_12.into());
+        Value::Object(schema)
+    }
+}
+
+/// Infer the schema of a set of sample documents.
+pub fn infer_schema<'a>(docs: impl IntoIterator<Item = &'a Value>, opt: &InferOptions) -> Value {
+    let mut inferrer = SchemaInferrer::new(opt.clone());
+    for doc in docs {
+        inferrer.observe(doc);
+    }
+    inferrer.schema()
+}
+
+impl Shape {
+    fn is_empty(&self) -> bool {
+        !self.null
+            && !self.boolean
+            && self.number.is_none()
+            && self.string.is_none()
+            && self.array.is_none()
+            && self.object.is_none()
+    }
+
+    /// Record `value`; `row` is set for the rows of a tabular block.
+    fn observe(&mut self, value: &Value, row: bool, opt: &InferOptions) {
+        match value {
+            Value::Null => self.null = true,
+            Value::Bool(_) => self.boolean = true,
+            Value::Number(n) => match &mut self.number {
+                None => {
+                    self.number = Some(NumberShape {
+                        min: n.clone(),
+                        max: n.clone(),
+                        integers: !n.is_f64(),
+                    })
+                }
+                Some(shape) => {
+                    if as_f64(n) < as_f64(&shape.min) {
+                        shape.min = n.clone();
+                    }
+                    if as_f64(n) > as_f64(&shape.max) {
+                        shape.max = n.clone();
+                    }
+                    shape.integers &= !n.is_f64();
+                }
+            },
+            Value::String(s) => {
+                let shape = self.string.get_or_insert_with(|| StringShape {
+                    count: 0,
+                    values: Some(BTreeSet::new()),
+                });
+                shape.count += 1;
+                if let Some(values) = &mut shape.values {
+                    values.insert(s.clone());
+                    if values.len() > opt.enum_limit {
+                        shape.values = None;
+                    }
+                }
+            }
+            Value::Array(arr) => {
+                let items = self.array.get_or_insert_with(Default::default);
+                let rows = is_uniform_object_array(arr);
+                for item in arr {
+                    items.observe(item, rows, opt);
+                }
+            }
+            Value::Object(obj) => {
+                let shape = self.object.get_or_insert_with(|| {
+                    Box::new(ObjectShape {
+                        closed: true,
+                        ..Default::default()
+                    })
+                });
+                shape.count += 1;
+                shape.closed &= row;
+                for (key, value) in obj {
+                    let (property, seen) = shape.properties.entry(key.clone()).or_default();
+                    property.observe(value, false, opt);
+                    *seen += 1;
+                }
+            }
+        }
+    }
+
+    /// The schema of this shape, or `None` if nothing was observed here.
+    fn schema(&self) -> Option<Value> {
+        if self.is_empty() {
+            return None;
+        }
+        let mut schema = Map::new();
+        let mut types = Vec::new();
+
+        if self.null {
+            types.push("null");
+        }
+        if self.boolean {
+            types.push("boolean");
+        }
+        if let Some(number) = &self.number {
+            types.push(if number.integers { "integer" } else { "number" });
+            schema.insert("minimum".into(), Value::Number(number.min.clone()));
+            schema.insert("maximum".into(), Value::Number(number.max.clone()));
+        }
+        if let Some(string) = &self.string {
+            types.push("string");
+            // Only values that repeat suggest a fixed set. An enum applies to
+            // every type, so it can only admit strings and null.
+            let only_strings = !self.boolean
+                && self.number.is_none()
+                && self.array.is_none()
+                && self.object.is_none();
+            let values = string.values.as_ref().filter(|v| string.count > v.len());
+            if let Some(values) = values.filter(|_| only_strings) {
+                let mut values: Vec<Value> = values.iter().cloned().map(Value::String).collect();
+                if self.null {
+                    values.push(Value::Null);
+                }
+                schema.insert("enum".into(), Value::Array(values));
+            }
+        }
+        if let Some(items) = &self.array {
+            types.push("array");
+            if let Some(items) = items.schema() {
+                schema.insert("items".into(), items);
+            }
+        }
+        if let Some(object) = &self.object {
+            types.push("object");
+            let mut properties = Map::new();
+            let mut required = Vec::new();
+            for (key, (shape, seen)) in &object.properties {
+                properties.insert(key.clone(), shape.schema().unwrap_or(json!({})));
+                if *seen == object.count {
+                    required.push(Value::String(key.clone()));
+                }
+            }
+            schema.insert("properties".into(), Value::Object(properties));
+            if !required.is_empty() {
+                schema.insert("required".into(), Value::Array(required));
+            }
+            if object.closed {
+                schema.insert("additionalProperties".into(), Value::Bool(false));
+            }
+        }
+
+        let types: Vec<Value> = types.into_iter().map(Value::from).collect();
+        let ty = match <[Value; 1]>::try_from(types) {
+            Ok([ty]) => ty,
+            Err(types) => Value::Array(types),
+        };
+        let mut out = Map::new();
+        out.insert("type".into(), ty);
+        out.extend(schema);
+        Some(Value::Object(out))
+    }
+}
+
+fn as_f64(n: &Number) -> f64 {
+    n.as_f64().unwrap_or(f64::NAN)
+}
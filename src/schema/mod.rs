@@ -0,0 +1,15 @@
+//! JSON Schema inference and validation.
+//!
+//! Schemas use the JSON Schema draft 2020-12 vocabulary. [`SchemaInferrer`]
+//! writes one from sample documents, and [`Schema`] validates documents
+//! against one, reporting each violation with the JSON Pointer of the value
+//! at fault.
+
+mod infer;
+mod validate;
+
+pub use infer::{infer_schema, InferOptions, SchemaInferrer};
+pub use validate::{Schema, Violation};
+
+/// The JSON Schema dialect of inferred schemas.
+pub const DRAFT_2020_12: &str = "https://json-schema.org/draft/2020-12/schema";
@@ -0,0 +1,538 @@
+//! Validation against a JSON Schema.
+//!
+//! The supported subset of draft 2020-12 covers the core applicators and
+//! the validation vocabulary:
+//!
+//! - `type`, `enum`, `const`
+//! - `minimum`, `maximum`, `exclusiveMinimum`, `exclusiveMaximum`, `multipleOf`
+//! - `minLength`, `maxLength`
+//! - `prefixItems`, `items`, `contains`, `minContains`, `maxContains`,
+//!   `minItems`, `maxItems`, `uniqueItems`
+//! - `properties`, `additionalProperties`, `required`, `propertyNames`,
+//!   `minProperties`, `maxProperties`, `dependentRequired`, `dependentSchemas`
+//! - `allOf`, `anyOf`, `oneOf`, `not`, `if`, `then`, `else`
+//! - `$ref` to `#` and JSON Pointers within the schema, such as `#/$defs/id`
+//!
+//! Annotations such as `title` and `format` are ignored. Keywords that
+//! would need a regex engine or dynamic scopes are rejected when the schema
+//! is loaded, rather than silently ignored.
+
+use crate::query::escape_token;
+use anyhow::{Context, Result};
+use serde_json::{Map, Value};
+use std::fmt;
+use std::sync::Arc;
+
+/// Keywords outside the supported subset.
+const UNSUPPORTED: &[&str] = &[
+    "pattern",
+    "patternProperties",
+    "unevaluatedItems",
+    "unevaluatedProperties",
+    "$dynamicRef",
+    "$recursiveRef",
+];
+
+/// Deepest nesting of subschemas during one validation, which bounds
+/// recursive `$ref`s.
+const MAX_DEPTH: usize = 256;
+
+/// A JSON Schema, checked to use only the supported keywords.
+#[derive(Clone, Debug)]
+pub struct Schema {
+    root: Arc<Value>,
+}
+
+/// A value that does not satisfy the schema.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Violation {
+    /// JSON Pointer to the value
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = if self.path.is_empty() {
+            "/"
+        } else {
+            &self.path
+        };
+        write!(f, "{}: {}", path, self.message)
+    }
+}
+
+impl Schema {
+    /// Load a schema, failing on keywords or `$ref`s it cannot evaluate.
+    pub fn new(schema: Value) -> Result<Self> {
+        check_schema(&schema, &schema, "#")?;
+        Ok(Schema {
+            root: Arc::new(schema),
+        })
+    }
+
+    pub fn as_value(&self) -> &Value {
+        &self.root
+    }
+
+    /// Every violation in `value`, in document order. An empty list means
+    /// the value is valid.
+    pub fn validate(&self, value: &Value) -> Vec<Violation> {
+        let mut out = Vec::new();
+        Validator { root: &self.root }.validate(&self.root, value, "", 0, &mut out);
+        out
+    }
+
+    pub fn is_valid(&self, value: &Value) -> bool {
+        self.validate(value).is_empty()
+    }
+
+    /// Fail with every violation if `value` does not satisfy the schema.
+    pub fn check(&self, value: &Value) -> Result<()> {
+        let violations = self.validate(value);
+        if violations.is_empty() {
+            return Ok(());
+        }
+        let mut msg = String::from("Document does not match the schema:");
+        for violation in &violations {
+            msg.push_str("\n  ");
+            msg.push_str(&violation.to_string());
+        }
+        Err(anyhow::anyhow!(msg))
+    }
+}
+
+/// Check a subschema and all the subschemas inside it.
+fn check_schema(root: &Value, schema: &Value, at: &str) -> Result<()> {
+    let obj = match schema {
+        Value::Bool(_) => return Ok(()),
+        Value::Object(obj) => obj,
+        _ => anyhow::bail!("Schema at {} must be an object or a boolean", at),
+    };
+
+    for (keyword, value) in obj {
+        let at = format!("{}/{}", at, escape_token(keyword));
+        if UNSUPPORTED.contains(&keyword.as_str()) {
+            anyhow::bail!("Unsupported schema keyword at {}", at);
+        }
+        match keyword.as_str() {
+            "items"
+            | "additionalProperties"
+            | "propertyNames"
+            | "contains"
+            | "not"
+            | "if"
+            | "then"
+            | "else" => check_schema(root, value, &at)?,
+            "properties" | "$defs" | "definitions" | "dependentSchemas" => {
+                let map = value
+                    .as_object()
+                    .with_context(|| format!("Schema keyword at {} must be an object", at))?;
+                for (key, schema) in map {
+                    check_schema(root, schema, &format!("{}/{}", at, escape_token(key)))?;
+                }
+            }
+            "allOf" | "anyOf" | "oneOf" | "prefixItems" => {
+                let list = value
+                    .as_array()
+                    .with_context(|| format!("Schema keyword at {} must be an array", at))?;
+                for (i, schema) in list.iter().enumerate() {
+                    check_schema(root, schema, &format!("{}/{}", at, i))?;
+                }
+            }
+            "$ref" => {
+                let reference = value.as_str().unwrap_or_default();
+                resolve(root, reference)
+                    .with_context(|| format!("Unresolvable $ref {:?} at {}", reference, at))?;
+            }
+            "type" => {
+                let names: Vec<&Value> = match value {
+                    Value::Array(names) => names.iter().collect(),
+                    name => vec![name],
+                };
+                for name in names {
+                    if !name.as_str().is_some_and(|name| TYPES.contains(&name)) {
+                        anyhow::bail!("Unknown type {} at {}", name, at);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+const TYPES: &[&str] = &[
+    "null", "boolean", "integer", "number", "string", "array", "object",
+];
+
+/// The subschema a `$ref` within the schema points to.
+fn resolve<'a>(root: &'a Value, reference: &str) -> Option<&'a Value> {
+    let pointer = reference.strip_prefix('#')?;
+    root.pointer(pointer)
+}
+
+struct Validator<'s> {
+    root: &'s Value,
+}
+
+impl<'s> Validator<'s> {
+    fn validate(
+        &self,
+        schema: &'s Value,
+        value: &Value,
+        path: &str,
+        depth: usize,
+        out: &mut Vec<Violation>,
+    ) {
+        let mut fail = |message: String| {
+            out.push(Violation {
+                path: path.to_string(),
+                message,
+            })
+        };
+        let schema = match schema {
+            Value::Bool(true) => return,
+            Value::Bool(false) => return fail("no value is allowed here".into()),
+            Value::Object(schema) => schema,
+            _ => return,
+        };
+        if depth > MAX_DEPTH {
+            return fail("schema nesting is too deep; is a $ref recursive?".into());
+        }
+
+        if let Some(expected) = schema.get("type") {
+            let matches = |name: &Value| name.as_str().is_some_and(|name| has_type(value, name));
+            let ok = match expected {
+                Value::Array(names) => names.iter().any(matches),
+                name => matches(name),
+            };
+            if !ok {
+                fail(format!(
+                    "expected {}, found {}",
+                    type_list(expected),
+                    type_name(value)
+                ));
+            }
+        }
+        if let Some(Value::Array(values)) = schema.get("enum") {
+            if !values.iter().any(|v| equal(v, value)) {
+                fail(format!(
+                    "{} is not one of {}",
+                    value,
+                    Value::Array(values.clone())
+                ));
+            }
+        }
+        if let Some(expected) = schema.get("const") {
+            if !equal(expected, value) {
+                fail(format!("expected {}, found {}", expected, value));
+            }
+        }
+
+        match value {
+            Value::Number(n) => {
+                let x = n.as_f64().unwrap_or(f64::NAN);
+                let bound = |keyword| schema.get(keyword).and_then(Value::as_f64);
+                if let Some(min) = bound("minimum").filter(|&min| x < min) {
+                    fail(format!("{} is less than the minimum of {}", n, min));
+                }
+                if let Some(max) = bound("maximum").filter(|&max| x > max) {
+                    fail(format!("{} is greater than the maximum of {}", n, max));
+                }
+                if let Some(min) = bound("exclusiveMinimum").filter(|&min| x <= min) {
+                    fail(format!("{} is not greater than {}", n, min));
+                }
+                if let Some(max) = bound("exclusiveMaximum").filter(|&max| x >= max) {
+                    fail(format!("{} is not less than {}", n, max));
+                }
+                if let Some(m) = bound("multipleOf").filter(|&m| m > 0.0) {
+                    let q = x / m;
+                    if (q - q.round()).abs() > 1e-9 * q.abs().max(1.0) {
+                        fail(format!("{} is not a multiple of {}", n, m));
+                    }
+                }
+            }
+            Value::String(s) => {
+                let len = s.chars().count() as u64;
+                let limit = |keyword| schema.get(keyword).and_then(Value::as_u64);
+                if let Some(min) = limit("minLength").filter(|&min| len < min) {
+                    fail(format!("string is shorter than {} characters", min));
+                }
+                if let Some(max) = limit("maxLength").filter(|&max| len > max) {
+                    fail(format!("string is longer than {} characters", max));
+                }
+            }
+            Value::Array(items) => self.validate_array(schema, items, path, depth, out),
+            Value::Object(obj) => self.validate_object(schema, obj, path, depth, out),
+            _ => {}
+        }
+
+        self.validate_applicators(schema, value, path, depth, out);
+    }
+
+    fn validate_array(
+        &self,
+        schema: &'s Map<String, Value>,
+        items: &[Value],
+        path: &str,
+        depth: usize,
+        out: &mut Vec<Violation>,
+    ) {
+        let len = items.len() as u64;
+        let limit = |keyword| schema.get(keyword).and_then(Value::as_u64);
+        if let Some(min) = limit("minItems").filter(|&min| len < min) {
+            push(out, path, format!("array has fewer than {} items", min));
+        }
+        if let Some(max) = limit("maxItems").filter(|&max| len > max) {
+            push(out, path, format!("array has more than {} items", max));
+        }
+        if schema.get("uniqueItems") == Some(&Value::Bool(true)) {
+            let duplicate =
+                (1..items.len()).find(|&i| items[..i].iter().any(|v| equal(v, &items[i])));
+            if let Some(i) = duplicate {
+                push(out, path, format!("item {} is a duplicate", i));
+            }
+        }
+
+        let prefix = match schema.get("prefixItems") {
+            Some(Value::Array(prefix)) => prefix.as_slice(),
+            _ => &[],
+        };
+        for (i, item) in items.iter().enumerate() {
+            let item_schema = match prefix.get(i) {
+                Some(schema) => schema,
+                None => match schema.get("items") {
+                    Some(schema) => schema,
+                    None => break,
+                },
+            };
+            self.validate(
+                item_schema,
+                item,
+                &child(path, &i.to_string()),
+                depth + 1,
+                out,
+            );
+        }
+
+        if let Some(contains) = schema.get("contains") {
+            let matched = items
+                .iter()
+                .filter(|item| self.is_valid(contains, item, depth))
+                .count() as u64;
+            let min = limit("minContains").unwrap_or(1);
+            if matched < min {
+                push(
+                    out,
+                    path,
+                    format!("array has fewer than {} matching items", min),
+                );
+            }
+            if let Some(max) = limit("maxContains").filter(|&max| matched > max) {
+                push(
+                    out,
+                    path,
+                    format!("array has more than {} matching items", max),
+                );
+            }
+        }
+    }
+
+    fn validate_object(
+        &self,
+        schema: &'s Map<String, Value>,
+        obj: &Map<String, Value>,
+        path: &str,
+        depth: usize,
+        out: &mut Vec<Violation>,
+    ) {
+        let len = obj.len() as u64;
+        let limit = |keyword| schema.get(keyword).and_then(Value::as_u64);
+        if let Some(min) = limit("minProperties").filter(|&min| len < min) {
+            push(
+                out,
+                path,
+                format!("object has fewer than {} properties", min),
+            );
+        }
+        if let Some(max) = limit("maxProperties").filter(|&max| len > max) {
+            push(
+                out,
+                path,
+                format!("object has more than {} properties", max),
+            );
+        }
+        if let Some(Value::Array(required)) = schema.get("required") {
+            for key in required.iter().filter_map(Value::as_str) {
+                if !obj.contains_key(key) {
+                    push(out, path, format!("missing required property {:?}", key));
+                }
+            }
+        }
+        if let Some(Value::Object(dependencies)) = schema.get("dependentRequired") {
+            for (key, required) in dependencies
+                .iter()
+                .filter(|(key, _)| obj.contains_key(*key))
+            {
+                let required = required.as_array().map(Vec::as_slice).unwrap_or_default();
+                for name in required.iter().filter_map(Value::as_str) {
+                    if !obj.contains_key(name) {
+                        let msg = format!("property {:?} requires property {:?}", key, name);
+                        push(out, path, msg);
+                    }
+                }
+            }
+        }
+        if let Some(Value::Object(dependencies)) = schema.get("dependentSchemas") {
+            for (_, dependent) in dependencies
+                .iter()
+                .filter(|(key, _)| obj.contains_key(*key))
+            {
+                self.validate(dependent, &Value::Object(obj.clone()), path, depth + 1, out);
+            }
+        }
+
+        let properties = schema.get("properties").and_then(Value::as_object);
+        for (key, value) in obj {
+            let value_path = child(path, key);
+            if let Some(names) = schema.get("propertyNames") {
+                let name = Value::String(key.clone());
+                self.validate(names, &name, &value_path, depth + 1, out);
+            }
+            match properties.and_then(|properties| properties.get(key)) {
+                Some(property) => self.validate(property, value, &value_path, depth + 1, out),
+                None => {
+                    if let Some(additional) = schema.get("additionalProperties") {
+                        if additional == &Value::Bool(false) {
+                            push(out, &value_path, "property is not allowed".into());
+                        } else {
+                            self.validate(additional, value, &value_path, depth + 1, out);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn validate_applicators(
+        &self,
+        schema: &'s Map<String, Value>,
+        value: &Value,
+        path: &str,
+        depth: usize,
+        out: &mut Vec<Violation>,
+    ) {
+        if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+            if let Some(target) = resolve(self.root, reference) {
+                self.validate(target, value, path, depth + 1, out);
+            }
+        }
+        let list = |keyword| match schema.get(keyword) {
+            Some(Value::Array(list)) => list.as_slice(),
+            _ => &[],
+        };
+        for sub in list("allOf") {
+            self.validate(sub, value, path, depth + 1, out);
+        }
+        let any_of = list("anyOf");
+        if !any_of.is_empty() && !any_of.iter().any(|sub| self.is_valid(sub, value, depth)) {
+            push(out, path, "value does not match any schema in anyOf".into());
+        }
+        let one_of = list("oneOf");
+        if !one_of.is_empty() {
+            let matched = one_of
+                .iter()
+                .filter(|sub| self.is_valid(sub, value, depth))
+                .count();
+            if matched != 1 {
+                let msg = format!("value matches {} schemas in oneOf instead of one", matched);
+                push(out, path, msg);
+            }
+        }
+        if let Some(not) = schema.get("not") {
+            if self.is_valid(not, value, depth) {
+                push(out, path, "value matches the schema in not".into());
+            }
+        }
+        if let Some(condition) = schema.get("if") {
+            let branch = if self.is_valid(condition, value, depth) {
+                schema.get("then")
+            } else {
+                schema.get("else")
+            };
+            if let Some(branch) = branch {
+                self.validate(branch, value, path, depth + 1, out);
+            }
+        }
+    }
+
+    fn is_valid(&self, schema: &'s Value, value: &Value, depth: usize) -> bool {
+        let mut out = Vec::new();
+        self.validate(schema, value, "", depth + 1, &mut out);
+        out.is_empty()
+    }
+}
+
+fn push(out: &mut Vec<Violation>, path: &str, message: String) {
+    out.push(Violation {
+        path: path.to_string(),
+        message,
+    });
+}
+
+fn child(path: &str, token: &str) -> String {
+    format!("{}/{}", path, escape_token(token))
+}
+
+fn has_type(value: &Value, name: &str) -> bool {
+    match (name, value) {
+        ("null", Value::Null)
+        | ("boolean", Value::Bool(_))
+        | ("number", Value::Number(_))
+        | ("string", Value::String(_))
+        | ("array", Value::Array(_))
+        | ("object", Value::Object(_)) => true,
+        ("integer", Value::Number(n)) => {
+            !n.is_f64() || n.as_f64().is_some_and(|x| x.fract() == 0.0)
+        }
+        _ => false,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) if has_type(value, "integer") => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn type_list(expected: &Value) -> String {
+    match expected {
+        Value::Array(names) => names
+            .iter()
+            .filter_map(Value::as_str)
+            .collect::<Vec<_>>()
+            .join(" or "),
+        name => name.as_str().unwrap_or_default().to_string(),
+    }
+}
+
+/// JSON equality, with numbers compared by value, so `1` equals `1.0`.
+fn equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x == y || x.as_f64() == y.as_f64(),
+        (Value::Array(x), Value::Array(y)) => {
+            x.len() == y.len() && x.iter().zip(y).all(|(a, b)| equal(a, b))
+        }
+        (Value::Object(x), Value::Object(y)) => {
+            x.len() == y.len() && x.iter().all(|(k, a)| y.get(k).is_some_and(|b| equal(a, b)))
+        }
+        _ => a == b,
+    }
+}
@@ -0,0 +1,969 @@
+//! Serialize Rust values straight to TOON-Text with serde.
+//!
+//! The output is identical to building a `serde_json::Value` and passing it
+//! to [`encode_json_to_toon`](crate::encode_json_to_toon), without the
+//! intermediate tree. Each value is rendered into text once its children are
+//! known, so object keys can still be sorted and, with a maximum width, each
+//! container can choose between its single-line and broken layouts.
+//!
+//! A container keeps the text of its children as pieces instead of copying
+//! it, so rendering takes time in proportion to the output. The output is
+//! still held in memory until the outermost value is done: [`to_writer`]
+//! then writes it piece by piece, without joining it into one string.
+
+use crate::codec::quoting::{self, QuotePolicy};
+use crate::codec::tabular::{self, CellKind, ColumnKind, ColumnType};
+use crate::EncodeOptions;
+use anyhow::Result;
+use serde::ser::{self, Serialize};
+use std::fmt::{self, Display};
+use std::io;
+
+/// Serialize a value as TOON-Text.
+pub fn to_string<T: ?Sized + Serialize>(value: &T, opt: &EncodeOptions) -> Result<String> {
+    let mut out = String::new();
+    render(value, opt)?.push_to(&mut out);
+    Ok(out)
+}
+
+/// Serialize a value as TOON-Text into a writer. The text is rendered in
+/// memory first, as for [`to_string`], and then written in pieces, so a
+/// buffered writer is best.
+pub fn to_writer<W: io::Write, T: ?Sized + Serialize>(
+    mut writer: W,
+    value: &T,
+    opt: &EncodeOptions,
+) -> Result<()> {
+    render(value, opt)?.write_to(&mut writer)?;
+    Ok(())
+}
+
+fn render<T: ?Sized + Serialize>(value: &T, opt: &EncodeOptions) -> Result<Text> {
+    if opt.compact {
+        anyhow::bail!("to_string produces TOON-Text; compact output is binary");
+    }
+    if let Some(schema) = &opt.schema {
+        schema.check(&serde_json::to_value(value)?)?;
+    }
+
+    let root = value.serialize(Serializer::root(opt))?;
+    if opt.tabular_arrays && opt.strict && root.kind == Kind::Scalar {
+        anyhow::bail!("Tabular mode only applies to arrays of objects");
+    }
+
+    let text = match (opt.max_width, root.flat) {
+        (Some(max_width), Some(flat)) if width(&flat) <= max_width => Text::Str(flat),
+        _ => root.broken,
+    };
+    Ok(text)
+}
+
+#[derive(Debug)]
+pub(crate) struct Error(String);
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Scalar,
+    Array,
+    Object,
+}
+
+/// Rendered text, kept in pieces so that a container can take over the text
+/// of its children without copying it.
+enum Text {
+    Str(String),
+    Concat(Vec<Text>),
+}
+
+impl Text {
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Text::Str(s) => Some(s),
+            Text::Concat(_) => None,
+        }
+    }
+
+    fn push_to(&self, out: &mut String) {
+        match self {
+            Text::Str(s) => out.push_str(s),
+            Text::Concat(pieces) => pieces.iter().for_each(|piece| piece.push_to(out)),
+        }
+    }
+
+    fn write_to<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        match self {
+            Text::Str(s) => out.write_all(s.as_bytes()),
+            Text::Concat(pieces) => pieces.iter().try_for_each(|piece| piece.write_to(out)),
+        }
+    }
+}
+
+/// Builds a [`Text`], joining short strings and keeping longer texts as
+/// pieces.
+#[derive(Default)]
+struct TextBuilder {
+    pieces: Vec<Text>,
+    current: String,
+}
+
+impl TextBuilder {
+    fn push_str(&mut self, s: &str) {
+        self.current.push_str(s);
+    }
+
+    fn push_char(&mut self, c: char) {
+        self.current.push(c);
+    }
+
+    fn push(&mut self, text: Text) {
+        if !self.current.is_empty() {
+            let current = std::mem::take(&mut self.current);
+            self.pieces.push(Text::Str(current));
+        }
+        self.pieces.push(text);
+    }
+
+    fn finish(mut self) -> Text {
+        if self.pieces.is_empty() {
+            return Text::Str(self.current);
+        }
+        if !self.current.is_empty() {
+            self.pieces.push(Text::Str(self.current));
+        }
+        Text::Concat(self.pieces)
+    }
+}
+
+/// A value rendered as TOON-Text.
+struct Rendered {
+    kind: Kind,
+    /// Layout used when the value does not sit on a single line; the only
+    /// layout for scalars
+    broken: Text,
+    /// Single-line layout of a container, when wanted and short enough
+    flat: Option<String>,
+    /// Sorted keys of an object, when tracking tabular uniformity
+    keys: Option<Vec<String>>,
+    /// Single-line values of an object in key order, with what they hold,
+    /// for tabular rows
+    cells: Option<Vec<(String, CellKind)>>,
+    /// Whether an array is a uniform array of objects
+    uniform: bool,
+    /// What the value holds, for the column types of tabular rows
+    cell: CellKind,
+}
+
+impl Rendered {
+    fn scalar(text: String, cell: CellKind) -> Rendered {
+        Rendered {
+            kind: Kind::Scalar,
+            broken: Text::Str(text),
+            flat: None,
+            keys: None,
+            cells: None,
+            uniform: false,
+            cell,
+        }
+    }
+
+    fn flat_form(&self) -> Option<&str> {
+        match self.kind {
+            Kind::Scalar => self.broken.as_str(),
+            _ => self.flat.as_deref(),
+        }
+    }
+}
+
+fn width(s: &str) -> usize {
+    s.chars().count()
+}
+
+#[derive(Clone, Copy)]
+struct Serializer<'a> {
+    opt: &'a EncodeOptions,
+    depth: usize,
+    /// Longest single-line container layout worth keeping (0 for none)
+    flat_limit: usize,
+    /// Record object keys for tabular uniformity checks
+    keys: bool,
+    /// Record object cells for tabular rows
+    cells: bool,
+    /// Record whether an array is a uniform array of objects
+    uniform: bool,
+}
+
+impl<'a> Serializer<'a> {
+    fn root(opt: &'a EncodeOptions) -> Self {
+        Serializer {
+            opt,
+            depth: 0,
+            flat_limit: opt.max_width.unwrap_or(0),
+            keys: false,
+            cells: false,
+            uniform: opt.tabular_arrays,
+        }
+    }
+
+    /// Serializer for the elements of an array.
+    fn element(&self) -> Self {
+        let tabular_rows = self.depth == 0 && self.opt.tabular_arrays;
+        Serializer {
+            depth: self.depth + 1,
+            keys: self.uniform,
+            cells: tabular_rows,
+            uniform: false,
+            ..*self
+        }
+    }
+
+    /// Serializer for the values of an object.
+    fn field(&self) -> Self {
+        Serializer {
+            depth: self.depth + 1,
+            flat_limit: if self.cells { usize::MAX } else { self.flat_limit },
+            keys: false,
+            cells: false,
+            uniform: self.depth == 0 && self.opt.tabular_arrays && self.opt.strict,
+            ..*self
+        }
+    }
+
+    fn quoting(&self) -> QuotePolicy {
+        self.opt.quoting
+    }
+
+    fn string(&self, s: &str) -> Rendered {
+        let mut out = String::new();
+        quoting::write_string(&mut out, s, self.quoting());
+        Rendered::scalar(out, CellKind::Scalar(ColumnKind::Str))
+    }
+
+    /// The single-line layout of `item` if it has one that fits at `column`
+    /// followed by `trailing` columns.
+    fn choose_flat<'r>(
+        &self,
+        item: &'r Rendered,
+        column: usize,
+        trailing: usize,
+    ) -> Option<&'r str> {
+        let (max_width, flat) = (self.opt.max_width?, item.flat.as_deref()?);
+        (column + width(flat) + trailing <= max_width).then_some(flat)
+    }
+
+    /// Lay out an array or object from its rendered items, each preceded by
+    /// a prefix (`key: ` for objects).
+    fn container(&self, kind: Kind, items: Vec<(String, Rendered)>) -> Rendered {
+        let (open, close) = match kind {
+            Kind::Object => ('{', '}'),
+            _ => ('[', ']'),
+        };
+        let indent = self.opt.indent.unwrap_or_default();
+
+        let flat = if self.flat_limit > 0 {
+            self.flat_container(open, close, &items)
+        } else {
+            None
+        };
+
+        let mut broken = TextBuilder::default();
+        broken.push_char(open);
+        if !items.is_empty() {
+            let child_indent = indent.repeat(self.depth + 1);
+            let child_column = indent.columns(self.depth + 1);
+            let count = items.len();
+            for (i, (prefix, item)) in items.into_iter().enumerate() {
+                let last = i == count - 1;
+                broken.push_char('\n');
+                broken.push_str(&child_indent);
+                broken.push_str(&prefix);
+                let column = child_column + width(&prefix);
+                // Scalars are short enough to copy; containers are kept whole
+                match self.choose_flat(&item, column, usize::from(!last)) {
+                    Some(flat) => broken.push_str(flat),
+                    None => match item.flat_form().filter(|_| item.kind == Kind::Scalar) {
+                        Some(text) => broken.push_str(text),
+                        None => broken.push(item.broken),
+                    },
+                }
+                if !last {
+                    broken.push_char(',');
+                }
+            }
+            broken.push_char('\n');
+            broken.push_str(&indent.repeat(self.depth));
+        }
+        broken.push_char(close);
+
+        Rendered {
+            kind,
+            broken: broken.finish(),
+            flat,
+            keys: None,
+            cells: None,
+            uniform: false,
+            cell: CellKind::Container,
+        }
+    }
+
+    fn flat_container(&self, open: char, close: char, items: &[(String, Rendered)]) -> Option<String> {
+        let mut flat = String::new();
+        flat.push(open);
+        for (i, (prefix, item)) in items.iter().enumerate() {
+            if i > 0 {
+                flat.push_str(", ");
+            }
+            flat.push_str(prefix);
+            flat.push_str(item.flat_form()?);
+            if flat.len() > self.flat_limit && width(&flat) > self.flat_limit {
+                return None;
+            }
+        }
+        flat.push(close);
+        (width(&flat) <= self.flat_limit).then_some(flat)
+    }
+
+    fn end_array(&self, items: Vec<Rendered>) -> Result<Rendered, Error> {
+        let uniform = self.uniform
+            && items.first().is_some_and(|first| {
+                first.keys.as_ref().is_some_and(|keys| !keys.is_empty())
+                    && items.iter().all(|item| item.keys == first.keys)
+            });
+
+        if self.depth == 0 && self.opt.tabular_arrays {
+            if uniform {
+                return Ok(Rendered {
+                    kind: Kind::Array,
+                    ..Rendered::scalar(self.tabular(&items), CellKind::Container)
+                });
+            }
+            if self.opt.strict {
+                return Err(ser::Error::custom(
+                    "Tabular mode requires uniform array of objects, but array has mixed types",
+                ));
+            }
+        }
+
+        let items: Vec<_> = items.into_iter().map(|item| (String::new(), item)).collect();
+        let mut rendered = self.container(Kind::Array, items);
+        rendered.uniform = uniform;
+        Ok(rendered)
+    }
+
+    fn tabular(&self, rows: &[Rendered]) -> String {
+        let indent = self.opt.indent.unwrap_or_default();
+        let keys = rows[0].keys.as_deref().unwrap_or_default();
+        let types = if self.opt.typed_columns {
+            self.column_types(rows, keys.len())
+        } else {
+            Vec::new()
+        };
+
+        let mut out = String::new();
+        tabular::write_text_header(&mut out, keys, &types, indent, self.quoting());
+        let indent_str = indent.repeat(1);
+        for (i, row) in rows.iter().enumerate() {
+            out.push_str(&indent_str);
+            for (j, (cell, _)) in row.cells.iter().flatten().enumerate() {
+                if j > 0 {
+                    out.push_str(", ");
+                }
+                out.push_str(cell);
+            }
+            if i < rows.len() - 1 {
+                out.push(',');
+            }
+            out.push('\n');
+        }
+        out.push(']');
+        out
+    }
+
+    /// Column types of rendered rows, from what each cell holds.
+    fn column_types(&self, rows: &[Rendered], columns: usize) -> Vec<Option<ColumnType>> {
+        (0..columns)
+            .map(|i| {
+                let cells = rows.iter().filter_map(|row| row.cells.as_ref()?.get(i));
+                ColumnType::from_kinds(cells.map(|&(_, cell)| cell))
+            })
+            .collect()
+    }
+
+    fn end_object(&self, mut entries: Vec<(String, Rendered)>) -> Result<Rendered, Error> {
+        entries.sort_by(|a, b| a.0.cmp(&b.0)); // Deterministic output
+
+        if self.depth == 0 && self.opt.tabular_arrays && self.opt.strict {
+            let mixed = entries
+                .iter()
+                .any(|(_, value)| value.kind == Kind::Array && !value.uniform);
+            if mixed {
+                return Err(ser::Error::custom("Tabular mode requires uniform arrays of objects"));
+            }
+        }
+
+        let keys = self
+            .keys
+            .then(|| entries.iter().map(|(key, _)| key.clone()).collect());
+        let cells = self.cells.then(|| {
+            entries
+                .iter()
+                .map(|(_, value)| {
+                    let text = value.flat_form().unwrap_or_default().to_string();
+                    (text, value.cell)
+                })
+                .collect()
+        });
+
+        let items: Vec<_> = entries
+            .into_iter()
+            .map(|(key, value)| {
+                let mut prefix = String::new();
+                quoting::write_string(&mut prefix, &key, self.quoting());
+                prefix.push_str(": ");
+                (prefix, value)
+            })
+            .collect();
+        let mut rendered = self.container(Kind::Object, items);
+        rendered.keys = keys;
+        rendered.cells = cells;
+        Ok(rendered)
+    }
+
+    /// Wrap an enum variant's content as `{variant: content}`.
+    fn variant(&self, variant: &'static str, content: Rendered) -> Result<Rendered, Error> {
+        self.end_object(vec![(variant.to_string(), content)])
+    }
+}
+
+impl<'a> ser::Serializer for Serializer<'a> {
+    type Ok = Rendered;
+    type Error = Error;
+    type SerializeSeq = SeqSerializer<'a>;
+    type SerializeTuple = SeqSerializer<'a>;
+    type SerializeTupleStruct = SeqSerializer<'a>;
+    type SerializeTupleVariant = SeqSerializer<'a>;
+    type SerializeMap = MapSerializer<'a>;
+    type SerializeStruct = MapSerializer<'a>;
+    type SerializeStructVariant = MapSerializer<'a>;
+
+    fn serialize_bool(self, v: bool) -> Result<Rendered, Error> {
+        let text = if v { "true" } else { "false" };
+        Ok(Rendered::scalar(text.to_string(), CellKind::Scalar(ColumnKind::Bool)))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Rendered, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Rendered, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Rendered, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Rendered, Error> {
+        Ok(Rendered::scalar(v.to_string(), CellKind::Scalar(ColumnKind::Int)))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Rendered, Error> {
+        Ok(Rendered::scalar(v.to_string(), CellKind::Scalar(ColumnKind::Int)))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Rendered, Error> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Rendered, Error> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Rendered, Error> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Rendered, Error> {
+        Ok(Rendered::scalar(v.to_string(), CellKind::Scalar(ColumnKind::Int)))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Rendered, Error> {
+        Ok(Rendered::scalar(v.to_string(), CellKind::Scalar(ColumnKind::Int)))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Rendered, Error> {
+        // Widened like serde_json::Value does, so 0.1f32 is 0.10000000149011612
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Rendered, Error> {
+        // Same formatting as serde_json::Number; non-finite values become null
+        match serde_json::Number::from_f64(v) {
+            Some(n) => Ok(Rendered::scalar(n.to_string(), CellKind::Scalar(ColumnKind::Float))),
+            None => self.serialize_unit(),
+        }
+    }
+
+    fn serialize_char(self, v: char) -> Result<Rendered, Error> {
+        Ok(self.string(v.encode_utf8(&mut [0; 4])))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Rendered, Error> {
+        Ok(self.string(v))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Rendered, Error> {
+        let items = v
+            .iter()
+            .map(|b| Rendered::scalar(b.to_string(), CellKind::Scalar(ColumnKind::Int)))
+            .collect();
+        self.end_array(items)
+    }
+
+    fn serialize_none(self) -> Result<Rendered, Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Rendered, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Rendered, Error> {
+        Ok(Rendered::scalar("null".to_string(), CellKind::Null))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Rendered, Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Rendered, Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Rendered, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Rendered, Error> {
+        let content = value.serialize(self.field())?;
+        self.variant(variant, content)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer<'a>, Error> {
+        Ok(SeqSerializer {
+            ser: self,
+            items: Vec::with_capacity(len.unwrap_or(0)),
+            variant: None,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer<'a>, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer<'a>, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer<'a>, Error> {
+        Ok(SeqSerializer {
+            ser: self.field(),
+            items: Vec::with_capacity(len),
+            variant: Some((self, variant)),
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<MapSerializer<'a>, Error> {
+        Ok(MapSerializer {
+            ser: self,
+            entries: Vec::with_capacity(len.unwrap_or(0)),
+            key: None,
+            variant: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<MapSerializer<'a>, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<MapSerializer<'a>, Error> {
+        Ok(MapSerializer {
+            ser: self.field(),
+            entries: Vec::with_capacity(len),
+            key: None,
+            variant: Some((self, variant)),
+        })
+    }
+}
+
+struct SeqSerializer<'a> {
+    ser: Serializer<'a>,
+    items: Vec<Rendered>,
+    /// Enclosing serializer and name of a tuple variant
+    variant: Option<(Serializer<'a>, &'static str)>,
+}
+
+impl SeqSerializer<'_> {
+    fn push<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.items.push(value.serialize(self.ser.element())?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Rendered, Error> {
+        let content = self.ser.end_array(self.items)?;
+        match self.variant {
+            Some((outer, variant)) => outer.variant(variant, content),
+            None => Ok(content),
+        }
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer<'_> {
+    type Ok = Rendered;
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Rendered, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer<'_> {
+    type Ok = Rendered;
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Rendered, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer<'_> {
+    type Ok = Rendered;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Rendered, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SeqSerializer<'_> {
+    type Ok = Rendered;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Rendered, Error> {
+        self.finish()
+    }
+}
+
+struct MapSerializer<'a> {
+    ser: Serializer<'a>,
+    entries: Vec<(String, Rendered)>,
+    key: Option<String>,
+    /// Enclosing serializer and name of a struct variant
+    variant: Option<(Serializer<'a>, &'static str)>,
+}
+
+impl MapSerializer<'_> {
+    fn entry<T: ?Sized + Serialize>(&mut self, key: String, value: &T) -> Result<(), Error> {
+        let value = value.serialize(self.ser.field())?;
+        self.entries.push((key, value));
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Rendered, Error> {
+        let content = self.ser.end_object(self.entries)?;
+        match self.variant {
+            Some((outer, variant)) => outer.variant(variant, content),
+            None => Ok(content),
+        }
+    }
+}
+
+impl ser::SerializeMap for MapSerializer<'_> {
+    type Ok = Rendered;
+    type Error = Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Error> {
+        self.key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| ser::Error::custom("serialize_value called before serialize_key"))?;
+        self.entry(key, value)
+    }
+
+    fn end(self) -> Result<Rendered, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for MapSerializer<'_> {
+    type Ok = Rendered;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.entry(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<Rendered, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for MapSerializer<'_> {
+    type Ok = Rendered;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.entry(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<Rendered, Error> {
+        self.finish()
+    }
+}
+
+/// Serializes map keys, which must be strings, characters or integers.
+pub(crate) struct KeySerializer;
+
+fn key_must_be_a_string() -> Error {
+    ser::Error::custom("map key must be a string")
+}
+
+impl ser::Serializer for KeySerializer {
+    type Ok = String;
+    type Error = Error;
+    type SerializeSeq = ser::Impossible<String, Error>;
+    type SerializeTuple = ser::Impossible<String, Error>;
+    type SerializeTupleStruct = ser::Impossible<String, Error>;
+    type SerializeTupleVariant = ser::Impossible<String, Error>;
+    type SerializeMap = ser::Impossible<String, Error>;
+    type SerializeStruct = ser::Impossible<String, Error>;
+    type SerializeStructVariant = ser::Impossible<String, Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<String, Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<String, Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_char(self, v: char) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<String, Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_none(self) -> Result<String, Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, _value: &T) -> Result<String, Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit(self) -> Result<String, Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String, Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<String, Error> {
+        Ok(variant.to_string())
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<String, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<String, Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(key_must_be_a_string())
+    }
+}
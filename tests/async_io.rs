@@ -0,0 +1,95 @@
+#![cfg(feature = "tokio")]
+
+use bytes::BytesMut;
+use serde_json::{json, Value};
+use tokio_util::codec::{Decoder, Encoder};
+use toon::async_io::{decode_from_async_reader, encode_to_async_writer, CompactCodec};
+use toon::{DecodeOptions, EncodeOptions};
+
+fn sample() -> Value {
+    json!({"users": [{"id": 1, "name": "Ada"}, {"id": 2, "name": "Bob"}], "ok": true})
+}
+
+#[tokio::test]
+async fn test_async_roundtrip_text_and_compact() {
+    for compact in [false, true] {
+        let opt = EncodeOptions {
+            compact,
+            ..Default::default()
+        };
+        let (client, server) = tokio::io::duplex(64);
+        let writer = tokio::spawn(async move {
+            encode_to_async_writer(client, &sample(), &opt)
+                .await
+                .unwrap();
+        });
+        let decoded = decode_from_async_reader(server, &DecodeOptions::default())
+            .await
+            .unwrap();
+        writer.await.unwrap();
+        assert_eq!(decoded, sample());
+    }
+}
+
+#[tokio::test]
+async fn test_async_decode_reports_errors() {
+    let opt = DecodeOptions {
+        strict: true,
+        ..Default::default()
+    };
+    let err = decode_from_async_reader(&b"{a: [1, 2"[..], &opt)
+        .await
+        .unwrap_err();
+    assert!(format!("{:#}", err).contains("Failed to decode text TOON"));
+
+    let decoded = decode_from_async_reader(&b"{a: [1, 2]}"[..], &opt)
+        .await
+        .unwrap();
+    assert_eq!(decoded, json!({"a": [1, 2]}));
+}
+
+#[test]
+fn test_codec_handles_partial_and_multiple_frames() {
+    let mut codec = CompactCodec::new();
+    let mut encoded = BytesMut::new();
+    codec.encode(&sample(), &mut encoded).unwrap();
+    codec.encode(json!([1, "two"]), &mut encoded).unwrap();
+
+    // Feed the frames a few bytes at a time
+    let mut src = BytesMut::new();
+    let mut decoded = Vec::new();
+    for chunk in encoded.chunks(3) {
+        src.extend_from_slice(chunk);
+        while let Some(value) = codec.decode(&mut src).unwrap() {
+            decoded.push(value);
+        }
+    }
+    assert_eq!(decoded, [sample(), json!([1, "two"])]);
+    assert!(src.is_empty());
+}
+
+#[test]
+fn test_codec_matches_document_writer() {
+    let opt = EncodeOptions {
+        compact: true,
+        ..Default::default()
+    };
+    let mut writer = toon::DocumentWriter::new(Vec::new(), opt);
+    writer.write(&sample()).unwrap();
+    let framed = writer.into_inner().unwrap();
+
+    let mut encoded = BytesMut::new();
+    CompactCodec::new().encode(&sample(), &mut encoded).unwrap();
+    assert_eq!(&encoded[..], &framed[..]);
+}
+
+#[test]
+fn test_codec_rejects_oversized_frames() {
+    let mut codec = CompactCodec::new().with_max_frame_length(16);
+    let mut dst = BytesMut::new();
+    assert!(codec.encode(&sample(), &mut dst).is_err());
+
+    let mut src = BytesMut::from(&u32::MAX.to_le_bytes()[..]);
+    let err = codec.decode(&mut src).unwrap_err();
+    assert!(err.to_string().contains("exceeds the maximum"), "{}", err);
+}
@@ -0,0 +1,59 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// A fresh directory for one test's files.
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("toon-cli-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn toon(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_toon"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_failed_encode_keeps_existing_output() {
+    let dir = scratch_dir("keep");
+    let bad = dir.join("bad.json");
+    let good = dir.join("good.json");
+    let schema = dir.join("schema.json");
+    let out = dir.join("keep.toon");
+    fs::write(&bad, r#"{"id": 1, "name": "#).unwrap();
+    fs::write(&good, r#"{"id": "one"}"#).unwrap();
+    fs::write(&schema, r#"{"properties": {"id": {"type": "integer"}}}"#).unwrap();
+    fs::write(&out, "previous").unwrap();
+
+    let result = toon(&["encode", bad.to_str().unwrap(), "-o", out.to_str().unwrap()]);
+    assert!(!result.status.success());
+    assert_eq!(fs::read_to_string(&out).unwrap(), "previous");
+
+    let result = toon(&[
+        "encode",
+        good.to_str().unwrap(),
+        "--schema",
+        schema.to_str().unwrap(),
+        "-o",
+        out.to_str().unwrap(),
+    ]);
+    assert!(!result.status.success());
+    assert_eq!(fs::read_to_string(&out).unwrap(), "previous");
+
+    let result = toon(&["encode", good.to_str().unwrap(), "-o", out.to_str().unwrap()]);
+    assert!(result.status.success());
+    assert_eq!(fs::read_to_string(&out).unwrap(), "{\n  id: one\n}");
+
+    // No temporary files are left behind
+    let mut names: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["bad.json", "good.json", "keep.toon", "schema.json"]);
+    fs::remove_dir_all(&dir).unwrap();
+}
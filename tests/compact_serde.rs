@@ -0,0 +1,145 @@
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use toon::compact::{from_slice, to_vec};
+use toon::{decode_toon_to_json, encode_json_to_toon, DecodeOptions, EncodeOptions};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct User {
+    name: String,
+    id: u32,
+    email: Option<String>,
+    tags: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+enum Shape {
+    Point,
+    Circle(f64),
+    Rect { w: u32, h: u32 },
+    Line(i32, i32),
+}
+
+/// Serializes as a byte string, like `serde_bytes::Bytes`.
+struct Bytes<'a>(&'a [u8]);
+
+impl Serialize for Bytes<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+fn user() -> User {
+    User {
+        name: "Alice".to_string(),
+        id: 7,
+        email: None,
+        tags: vec!["admin".to_string(), "ops".to_string()],
+    }
+}
+
+fn compact_opts() -> EncodeOptions {
+    EncodeOptions {
+        compact: true,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_to_vec_matches_value_encoder() {
+    let expected =
+        encode_json_to_toon(&serde_json::to_value(user()).unwrap(), &compact_opts()).unwrap();
+    assert_eq!(to_vec(&user()).unwrap(), expected);
+
+    // f32 is widened to f64, as serde_json::Value does
+    let floats = (0.1f32, f32::NAN, 1.5f32);
+    let expected =
+        encode_json_to_toon(&serde_json::to_value(floats).unwrap(), &compact_opts()).unwrap();
+    assert_eq!(to_vec(&floats).unwrap(), expected);
+    assert_eq!(
+        from_slice::<Value>(&expected).unwrap(),
+        json!([0.10000000149011612, null, 1.5])
+    );
+}
+
+#[test]
+fn test_struct_roundtrip() {
+    let bytes = to_vec(&vec![user(), user()]).unwrap();
+    let decoded: Vec<User> = from_slice(&bytes).unwrap();
+    assert_eq!(decoded, vec![user(), user()]);
+}
+
+#[test]
+fn test_enum_roundtrip() {
+    let shapes = vec![
+        Shape::Point,
+        Shape::Circle(1.5),
+        Shape::Rect { w: 2, h: 3 },
+        Shape::Line(-1, 1),
+    ];
+    let bytes = to_vec(&shapes).unwrap();
+    assert_eq!(from_slice::<Vec<Shape>>(&bytes).unwrap(), shapes);
+
+    let decoded = decode_toon_to_json(&bytes, &DecodeOptions::default()).unwrap();
+    assert_eq!(decoded, serde_json::to_value(&shapes).unwrap());
+}
+
+#[test]
+fn test_borrows_str_from_input() {
+    #[derive(Deserialize)]
+    struct Borrowed<'a> {
+        name: &'a str,
+    }
+
+    let bytes = to_vec(&json!({"name": "Ada"})).unwrap();
+    let value: Borrowed = from_slice(&bytes).unwrap();
+    assert_eq!(value.name, "Ada");
+    assert!(bytes.as_ptr_range().contains(&value.name.as_ptr()));
+}
+
+#[test]
+fn test_byte_strings_borrowed() {
+    let mut map = BTreeMap::new();
+    map.insert("blob", Bytes(&[0, 159, 255]));
+    let bytes = to_vec(&map).unwrap();
+
+    let decoded: BTreeMap<&str, &[u8]> = from_slice(&bytes).unwrap();
+    assert_eq!(decoded["blob"], &[0, 159, 255]);
+    assert!(bytes.as_ptr_range().contains(&decoded["blob"].as_ptr()));
+
+    // Everywhere else, byte strings read back as arrays of numbers
+    let value = decode_toon_to_json(&bytes, &DecodeOptions::default()).unwrap();
+    assert_eq!(value, json!({"blob": [0, 159, 255]}));
+    assert_eq!(from_slice::<Value>(&bytes).unwrap(), value);
+    let owned: BTreeMap<String, Vec<u8>> = from_slice(&bytes).unwrap();
+    assert_eq!(owned["blob"], [0, 159, 255]);
+}
+
+#[test]
+fn test_wide_integers_roundtrip() {
+    let values = (i128::MIN, u128::MAX);
+    let bytes = to_vec(&values).unwrap();
+    assert_eq!(from_slice::<(i128, u128)>(&bytes).unwrap(), values);
+}
+
+#[test]
+fn test_reads_value_encoder_output() {
+    let value = json!({"email": "a@example.com", "id": 1, "name": "A", "tags": []});
+    let bytes = encode_json_to_toon(&value, &compact_opts()).unwrap();
+    let decoded: User = from_slice(&bytes).unwrap();
+    assert_eq!(decoded.email.as_deref(), Some("a@example.com"));
+}
+
+#[test]
+fn test_malformed_input_rejected() {
+    let bytes = to_vec(&user()).unwrap();
+
+    let err = from_slice::<User>(&bytes[..bytes.len() - 2]).unwrap_err();
+    assert!(err.to_string().contains("at byte"), "{}", err);
+
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert!(from_slice::<User>(&trailing).is_err());
+
+    assert!(from_slice::<User>(b"{name: A}").is_err());
+}
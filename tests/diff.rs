@@ -0,0 +1,109 @@
+use serde_json::{json, Value};
+use toon::diff::{diff, to_patch, Change, DiffOptions};
+use toon::patch::apply_patch;
+
+fn keyed(key: &str) -> DiffOptions {
+    DiffOptions {
+        key: Some(key.to_string()),
+    }
+}
+
+/// Applying the diff as a JSON Patch must give the new value.
+fn check_roundtrip(old: &Value, new: &Value, opt: &DiffOptions) -> Vec<Change> {
+    let changes = diff(old, new, opt);
+    let mut patched = old.clone();
+    apply_patch(&mut patched, &to_patch(&changes)).unwrap();
+    assert_eq!(&patched, new, "{:?}", changes);
+    changes
+}
+
+#[test]
+fn test_object_and_positional_array_changes() {
+    let old = json!({"name": "app", "port": 80, "tags": ["a", "b", "c"], "a/b": 1});
+    let new = json!({"name": "app", "port": 8080, "tags": ["a", "x"], "debug": true});
+    let changes = check_roundtrip(&old, &new, &DiffOptions::default());
+    let lines: Vec<String> = changes.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        lines,
+        [
+            "- /a~1b: 1",
+            "~ /port: 80 -> 8080",
+            "~ /tags/1: b -> x",
+            "- /tags/2: c",
+            "+ /debug: true",
+        ]
+    );
+    assert_eq!(changes[1].path(), "/port");
+
+    assert!(diff(&old, &old, &DiffOptions::default()).is_empty());
+    assert_eq!(
+        check_roundtrip(&json!(1), &json!("1"), &DiffOptions::default())
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>(),
+        ["~ /: 1 -> \"1\""]
+    );
+}
+
+#[test]
+fn test_keyed_rows() {
+    let old = json!([
+        {"id": 1, "name": "Ada"},
+        {"id": 2, "name": "Bob"},
+        {"id": 3, "name": "Cy"},
+        {"id": 4, "name": "Di"}
+    ]);
+    let new = json!([
+        {"id": 3, "name": "Cy"},
+        {"id": 1, "name": "Ada Lovelace"},
+        {"id": 5, "name": "Ed"},
+        {"id": 4, "name": "Di"}
+    ]);
+
+    let changes = check_roundtrip(&old, &new, &keyed("id"));
+    let lines: Vec<String> = changes.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        lines,
+        [
+            "- /1: {id: 2, name: Bob}",
+            "> /1 -> /0",
+            "~ /1/name: Ada -> \"Ada Lovelace\"",
+            "+ /2: {id: 5, name: Ed}",
+        ]
+    );
+
+    // By position, both fields of the first three rows differ
+    let positional = check_roundtrip(&old, &new, &DiffOptions::default());
+    assert_eq!(positional.len(), 6);
+}
+
+#[test]
+fn test_key_falls_back_to_positions() {
+    let opt = keyed("id");
+    // Duplicate keys, a missing key and non-object elements
+    for (old, new) in [
+        (json!([{"id": 1}, {"id": 1}]), json!([{"id": 1}])),
+        (json!([{"id": 1}, {"x": 2}]), json!([{"x": 2}])),
+        (json!([1, 2]), json!([2])),
+    ] {
+        let changes = check_roundtrip(&old, &new, &opt);
+        assert!(
+            changes.iter().all(|c| !matches!(c, Change::Moved { .. })),
+            "{:?}",
+            changes
+        );
+    }
+}
+
+#[test]
+fn test_nested_keyed_tables() {
+    let old = json!({"teams": [
+        {"id": "a", "members": [{"id": 1}, {"id": 2}]},
+        {"id": "b", "members": []}
+    ]});
+    let new = json!({"teams": [
+        {"id": "b", "members": [{"id": 3}]},
+        {"id": "a", "members": [{"id": 2}, {"id": 1}]}
+    ]});
+    check_roundtrip(&old, &new, &keyed("id"));
+}
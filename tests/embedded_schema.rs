@@ -0,0 +1,316 @@
+use serde::Deserialize;
+use serde_json::{json, Value};
+use toon::codec::compact;
+use toon::codec::events::Event;
+use toon::patch::{patch_document, Patch};
+use toon::schema::Schema;
+use toon::{decode_from_reader, decode_toon_to_json, CompactView, DecodeOptions, EncodeOptions};
+
+fn user_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "id": {"type": "integer", "minimum": 1},
+            "name": {"type": "string"},
+            "admin": {"type": "boolean"},
+            "email": {"type": ["string", "null"]},
+            "tags": {"type": "array", "items": {"type": "string"}}
+        },
+        "required": ["id", "name"]
+    })
+}
+
+fn sample() -> Value {
+    let users: Vec<Value> = (1..=20)
+        .map(|i| {
+            json!({
+                "id": i,
+                "name": format!("user{}", i),
+                "admin": i % 5 == 0,
+                "email": if i % 2 == 0 { Value::Null } else { json!(format!("u{}@x.org", i)) },
+                "tags": ["a", "b"]
+            })
+        })
+        .collect();
+    json!({"users": users, "version": 3})
+}
+
+fn sample_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "users": {"type": "array", "items": {"$ref": "#/$defs/user"}},
+            "version": {"type": "integer"}
+        },
+        "$defs": {"user": user_schema()}
+    })
+}
+
+fn embed_options(compact_index: bool) -> EncodeOptions {
+    EncodeOptions {
+        compact: true,
+        compact_index,
+        schema: Some(Schema::new(sample_schema()).unwrap()),
+        embed_schema: true,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_embedded_schema_roundtrip_in_every_decoder() {
+    let plain = compact::encode(&sample()).unwrap();
+    for compact_index in [false, true] {
+        let bytes = toon::encode_json_to_toon(&sample(), &embed_options(compact_index)).unwrap();
+
+        assert_eq!(
+            decode_toon_to_json(&bytes, &DecodeOptions::default()).unwrap(),
+            sample()
+        );
+        assert_eq!(
+            decode_from_reader(&bytes[..], &DecodeOptions::default()).unwrap(),
+            sample()
+        );
+        assert_eq!(compact::decode(&bytes).unwrap(), sample());
+        assert_eq!(CompactView::new(&bytes).unwrap().to_value(), sample());
+
+        let mut lines = Vec::new();
+        toon::ndjson::decode(&bytes[..], &mut lines, &DecodeOptions::default()).unwrap();
+        assert_eq!(String::from_utf8(lines).unwrap(), format!("{}\n", sample()));
+
+        let events: Vec<Event> = compact::events(&bytes[..])
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(events[0], Event::StartObject);
+        assert_eq!(events[1], Event::Key("users".into()));
+
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct User {
+            id: u32,
+            name: String,
+            email: Option<String>,
+        }
+        #[derive(Deserialize)]
+        struct Doc {
+            users: Vec<User>,
+        }
+        let doc: Doc = toon::compact::from_slice(&bytes).unwrap();
+        assert_eq!(
+            doc.users[2],
+            User {
+                id: 3,
+                name: "user3".into(),
+                email: Some("u3@x.org".into())
+            }
+        );
+
+        let schema = compact::embedded_schema(&bytes).unwrap().unwrap();
+        assert_eq!(schema.as_value(), &sample_schema());
+    }
+
+    let bytes = toon::encode_json_to_toon(&sample(), &embed_options(false)).unwrap();
+    let schema_size = compact::encode(&sample_schema()).unwrap().len() - 5;
+    assert!(bytes.len() - schema_size < plain.len());
+    assert_eq!(compact::embedded_schema(&plain).unwrap().map(|_| ()), None);
+}
+
+#[test]
+fn test_values_the_schema_fixes_have_no_tag() {
+    let schema = json!({
+        "type": "object",
+        "properties": {"id": {"type": "integer"}, "gone": {"type": "null"}},
+        "additionalProperties": {"type": "string"}
+    });
+    let bytes =
+        compact::encode_with_schema(&json!({"id": 7, "gone": null, "x": "y"}), &schema).unwrap();
+
+    let section = compact::encode(&schema).unwrap()[5..].to_vec();
+    let mut expected = b"TOON\x01\x0a".to_vec();
+    expected.extend((section.len() as u32).to_le_bytes());
+    expected.extend(&section);
+    expected.extend(3u32.to_le_bytes());
+    expected.extend(b"\x04\0\0\0gone");
+    expected.extend(b"\x02\0\0\0id\x01\0\0\x007");
+    expected.extend(b"\x01\0\0\0x\x01\0\0\0y");
+    assert_eq!(bytes, expected);
+
+    let doc = CompactView::new(&bytes).unwrap();
+    assert_eq!(doc.get("id").and_then(|v| v.as_i64()), Some(7));
+    assert!(doc.get("gone").is_some_and(|v| v.is_null()));
+    assert_eq!(doc.get("x").and_then(|v| v.as_str()), Some("y"));
+    assert_eq!(doc.len(), Some(3));
+
+    let bytes = compact::encode_with_schema(
+        &json!([1, null]),
+        &json!({"items": {"type": "null"}, "prefixItems": [true]}),
+    )
+    .unwrap();
+    let doc = CompactView::new(&bytes).unwrap();
+    assert!(doc.index(1).is_some_and(|v| v.is_null()));
+    assert_eq!(doc.to_value(), json!([1, null]));
+}
+
+#[test]
+fn test_view_reads_through_the_schema() {
+    let bytes = toon::encode_json_to_toon(&sample(), &embed_options(true)).unwrap();
+    let doc = CompactView::new(&bytes).unwrap();
+
+    let users = doc.get("users").unwrap();
+    assert!(!users.is_indexed());
+    assert_eq!(users.len(), Some(20));
+    let user = users.index(9).unwrap();
+    assert!(user.is_object());
+    assert_eq!(user.get("id").and_then(|v| v.as_u64()), Some(10));
+    assert_eq!(user.get("admin").and_then(|v| v.as_bool()), Some(true));
+    assert!(user.get("email").is_some_and(|v| v.is_null()));
+    assert_eq!(
+        user.get("tags")
+            .and_then(|t| t.elements())
+            .map(|t| t.filter_map(|v| v.as_str()).collect::<Vec<_>>()),
+        Some(vec!["a", "b"])
+    );
+    assert_eq!(user.to_value(), sample()["users"][9]);
+    assert_eq!(doc.get("version").and_then(|v| v.as_i64()), Some(3));
+
+    let first: Vec<_> = doc.entries().unwrap().map(|(key, _)| key).collect();
+    assert_eq!(first, ["users", "version"]);
+
+    let select = |source| {
+        let query = toon::Query::parse(source).unwrap();
+        toon::query::select_document(&bytes, &query, &DecodeOptions::default()).unwrap()
+    };
+    assert_eq!(select("$.users[-1].name"), [json!("user20")]);
+    assert_eq!(select("$..version"), [json!(3)]);
+    assert_eq!(select("$.users[*].id").len(), 20);
+}
+
+#[test]
+fn test_refs_tuples_and_untyped_subschemas() {
+    let schema = json!({
+        "$ref": "#/$defs/node",
+        "$defs": {
+            "node": {
+                "type": "object",
+                "properties": {
+                    "label": {"type": "string"},
+                    "point": {"prefixItems": [{"type": "number"}, {"type": "number"}], "type": "array"},
+                    "extra": {"anyOf": [{"type": "string"}, {"type": "integer"}]},
+                    "children": {"type": "array", "items": {"$ref": "#/$defs/node"}}
+                }
+            }
+        }
+    });
+    let value = json!({
+        "label": "root",
+        "point": [1.5, -2],
+        "extra": 4,
+        "children": [
+            {"label": "a", "extra": "x", "children": []},
+            {"label": "b", "point": [0, 0], "children": [{"label": "c", "children": []}]}
+        ]
+    });
+    let opt = EncodeOptions {
+        compact: true,
+        schema: Some(Schema::new(schema).unwrap()),
+        embed_schema: true,
+        ..Default::default()
+    };
+    let bytes = toon::encode_json_to_toon(&value, &opt).unwrap();
+    assert_eq!(
+        decode_toon_to_json(&bytes, &DecodeOptions::default()).unwrap(),
+        value
+    );
+    let doc = CompactView::new(&bytes).unwrap();
+    let c = doc
+        .get("children")
+        .and_then(|c| c.index(1))
+        .and_then(|b| b.get("children"));
+    assert_eq!(
+        c.and_then(|c| c.index(0)).map(|c| c.to_value()),
+        Some(value["children"][1]["children"][0].clone())
+    );
+
+    // A schema that refers to itself forever types nothing, and no value
+    // satisfies it
+    let endless = json!({"$ref": "#"});
+    let bytes = compact::encode_with_schema(&json!([1, "two"]), &endless).unwrap();
+    let view = CompactView::new(&bytes).unwrap();
+    assert_eq!(view.to_value(), json!([1, "two"]));
+    assert!(compact::decode(&bytes).is_err());
+}
+
+#[test]
+fn test_decoding_checks_the_embedded_schema() {
+    // The layout only depends on types, so a value that breaks another
+    // keyword is written, and caught when the document is decoded.
+    let bad = json!({"users": [{"id": 0, "name": "root"}], "version": 1});
+    let bytes = compact::encode_with_schema(&bad, &sample_schema()).unwrap();
+    assert!(CompactView::new(&bytes).is_ok());
+    let err = decode_toon_to_json(&bytes, &DecodeOptions::default()).unwrap_err();
+    assert!(format!("{:#}", err).contains("Document does not match its embedded schema"));
+    assert!(format!("{:#}", err).contains("/users/0/id: 0 is less than the minimum of 1"));
+    assert!(compact::decode(&bytes).is_err());
+
+    let mut lines = Vec::new();
+    let err = toon::ndjson::decode(&bytes[..], &mut lines, &DecodeOptions::default()).unwrap_err();
+    assert!(format!("{:#}", err).contains("/users/0/id: 0 is less than the minimum of 1"));
+    assert!(lines.is_empty());
+
+    // Views, and queries on compact documents, read in place and leave the
+    // schema to decoding
+    let view = CompactView::new(&bytes).unwrap();
+    let id = view
+        .get("users")
+        .and_then(|users| users.index(0))
+        .and_then(|user| user.get("id"));
+    assert_eq!(id.and_then(|id| id.as_i64()), Some(0));
+    let query = toon::Query::parse("/users/0/id").unwrap();
+    assert_eq!(
+        toon::query::select_document(&bytes, &query, &DecodeOptions::default()).unwrap(),
+        [json!(0)]
+    );
+
+    let err = toon::encode_json_to_toon(&bad, &embed_options(false)).unwrap_err();
+    assert!(format!("{:#}", err).contains("/users/0/id"));
+
+    let wrong_type = json!({"users": "none", "version": 1});
+    let err = compact::encode_with_schema(&wrong_type, &sample_schema()).unwrap_err();
+    assert!(err.to_string().contains("does not have the type"));
+
+    let opt = EncodeOptions {
+        compact: true,
+        embed_schema: true,
+        ..Default::default()
+    };
+    assert!(toon::encode_json_to_toon(&sample(), &opt).is_err());
+
+    let mut truncated = toon::encode_json_to_toon(&sample(), &embed_options(false)).unwrap();
+    truncated.truncate(truncated.len() - 3);
+    assert!(CompactView::new(&truncated).is_err());
+    assert!(decode_toon_to_json(&truncated, &DecodeOptions::default()).is_err());
+
+    // NDJSON writes the elements of a checked array one per line
+    let bytes = compact::encode_with_schema(&json!([1, 2]), &json!({"items": {"type": "integer"}}))
+        .unwrap();
+    let mut lines = Vec::new();
+    toon::ndjson::decode(&bytes[..], &mut lines, &DecodeOptions::default()).unwrap();
+    assert_eq!(lines, b"1\n2\n");
+}
+
+#[test]
+fn test_patch_keeps_the_embedded_schema() {
+    let bytes = toon::encode_json_to_toon(&sample(), &embed_options(false)).unwrap();
+
+    let patch = Patch::Json(json!([{"op": "replace", "path": "/version", "value": 4}]));
+    let patched = patch_document(&bytes, &patch, &DecodeOptions::default()).unwrap();
+    assert!(compact::embedded_schema(&patched).unwrap().is_some());
+    let mut expected = sample();
+    expected["version"] = json!(4);
+    assert_eq!(
+        decode_toon_to_json(&patched, &DecodeOptions::default()).unwrap(),
+        expected
+    );
+
+    let patch = Patch::Json(json!([{"op": "replace", "path": "/version", "value": "four"}]));
+    assert!(patch_document(&bytes, &patch, &DecodeOptions::default()).is_err());
+}
@@ -0,0 +1,122 @@
+use serde_json::{json, Value};
+use toon::codec::{compact, tabular, text};
+use toon::{decode_toon_to_json, encode_json_to_toon, events, DecodeOptions, EncodeOptions, Event};
+
+fn text_events(input: &str) -> Vec<Event> {
+    text::events(input.as_bytes())
+        .collect::<anyhow::Result<_>>()
+        .unwrap()
+}
+
+#[test]
+fn test_text_events() {
+    assert_eq!(
+        text_events("{a: [1, x], b: {}}"),
+        vec![
+            Event::StartObject,
+            Event::Key("a".into()),
+            Event::StartArray,
+            Event::Scalar(json!(1)),
+            Event::Scalar(json!("x")),
+            Event::EndArray,
+            Event::Key("b".into()),
+            Event::StartObject,
+            Event::EndObject,
+            Event::EndObject,
+        ]
+    );
+}
+
+#[test]
+fn test_tabular_events_match_across_formats() {
+    let value = json!([{"id": 1, "name": "Ada"}, {"id": 2, "name": "Bob"}]);
+    let expected = vec![
+        Event::StartArray,
+        Event::TabularHeader(vec!["id".into(), "name".into()]),
+        Event::Row(vec![json!(1), json!("Ada")]),
+        Event::Row(vec![json!(2), json!("Bob")]),
+        Event::EndArray,
+    ];
+
+    let text_opts = EncodeOptions {
+        tabular_arrays: true,
+        ..Default::default()
+    };
+    let encoded = encode_json_to_toon(&value, &text_opts).unwrap();
+    assert_eq!(
+        text_events(std::str::from_utf8(&encoded).unwrap()),
+        expected
+    );
+
+    let encoded = tabular::encode_tabular_compact(value.as_array().unwrap()).unwrap();
+    let parsed: Vec<Event> = compact::events(&encoded[..])
+        .unwrap()
+        .collect::<anyhow::Result<_>>()
+        .unwrap();
+    assert_eq!(parsed, expected);
+}
+
+#[test]
+fn test_compact_events() {
+    let encoded = compact::encode(&json!({"k": [true, null]})).unwrap();
+    let parsed: Vec<Event> = compact::events(&encoded[..])
+        .unwrap()
+        .collect::<anyhow::Result<_>>()
+        .unwrap();
+    assert_eq!(
+        parsed,
+        vec![
+            Event::StartObject,
+            Event::Key("k".into()),
+            Event::StartArray,
+            Event::Scalar(json!(true)),
+            Event::Scalar(Value::Null),
+            Event::EndArray,
+            Event::EndObject,
+        ]
+    );
+
+    assert!(compact::events(&b"{}"[..]).is_err());
+}
+
+#[test]
+fn test_next_value_skips_subtrees() {
+    let mut parser = events(
+        &b"{big: {a: [1, 2, 3]}, small: 1}"[..],
+        &DecodeOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(parser.next_event().unwrap(), Some(Event::StartObject));
+    assert_eq!(parser.next_event().unwrap(), Some(Event::Key("big".into())));
+    assert_eq!(parser.next_value().unwrap(), Some(json!({"a": [1, 2, 3]})));
+    assert_eq!(
+        parser.next_event().unwrap(),
+        Some(Event::Key("small".into()))
+    );
+    assert_eq!(parser.next_value().unwrap(), Some(json!(1)));
+    assert_eq!(parser.next_event().unwrap(), Some(Event::EndObject));
+    assert_eq!(parser.next_event().unwrap(), None);
+}
+
+#[test]
+fn test_iterator_stops_after_error() {
+    let mut parser = text::events(&b"[1, 2 3]"[..]);
+    assert_eq!(parser.next().unwrap().unwrap(), Event::StartArray);
+    assert_eq!(parser.next().unwrap().unwrap(), Event::Scalar(json!(1)));
+    assert_eq!(parser.next().unwrap().unwrap(), Event::Scalar(json!(2)));
+    let err = parser.next().unwrap().unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Expected ',' or ']' in array at line 1"),
+        "{}",
+        err
+    );
+    assert!(parser.next().is_none());
+}
+
+#[test]
+fn test_decoders_report_lines() {
+    let err =
+        decode_toon_to_json(b"{\n  a: [1,\n  b: 2\n}", &DecodeOptions::default()).unwrap_err();
+    assert!(format!("{:#}", err).contains("at line 3"), "{:#}", err);
+}
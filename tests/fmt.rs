@@ -0,0 +1,75 @@
+use serde_json::json;
+use toon::codec::text;
+use toon::{encode_json_to_toon, format_toon, EncodeOptions, Indent};
+
+#[test]
+fn test_encoder_output_is_canonical() {
+    let value = json!({
+        "name": "Alice",
+        "tags": ["a", "b"],
+        "nested": {"x": 1, "y": [true, null]}
+    });
+
+    let mut encoded = encode_json_to_toon(&value, &EncodeOptions::default()).unwrap();
+    encoded.push(b'\n');
+
+    assert!(text::is_canonical(&encoded, Indent::default()).unwrap());
+}
+
+#[test]
+fn test_reorders_keys_and_reindents() {
+    let input = b"{zeta: 1,\n      alpha: [1,2]}";
+    let formatted = format_toon(input, Indent::default()).unwrap();
+
+    assert_eq!(
+        String::from_utf8(formatted).unwrap(),
+        "{\n  alpha: [\n    1,\n    2\n  ],\n  zeta: 1\n}\n"
+    );
+}
+
+#[test]
+fn test_removes_unneeded_quotes() {
+    let input = b"{\"name\": \"Alice\", \"city\": \"New York\"}";
+    let formatted = format_toon(input, Indent::default()).unwrap();
+
+    assert_eq!(
+        String::from_utf8(formatted).unwrap(),
+        "{\n  city: \"New York\",\n  name: Alice\n}\n"
+    );
+}
+
+#[test]
+fn test_normalizes_numbers() {
+    let formatted = format_toon(b"[1.50, 1e3, -0.0]", Indent::default()).unwrap();
+
+    assert_eq!(
+        String::from_utf8(formatted).unwrap(),
+        "[\n  1.5,\n  1000.0,\n  -0.0\n]\n"
+    );
+}
+
+#[test]
+fn test_keeps_tabular_layout() {
+    let input = b"[\n# name, id\nAlice, 1,\nBob, 2\n]";
+    let formatted = format_toon(input, Indent::default()).unwrap();
+
+    assert_eq!(
+        String::from_utf8(formatted).unwrap(),
+        "[\n  # id, name\n  1, Alice,\n  2, Bob\n]\n"
+    );
+}
+
+#[test]
+fn test_format_is_idempotent() {
+    let input = b"{ b: [ {y: 2, x: 1} ], a: \"text\" }";
+    let once = format_toon(input, Indent::default()).unwrap();
+    let twice = format_toon(&once, Indent::default()).unwrap();
+
+    assert_eq!(once, twice);
+}
+
+#[test]
+fn test_short_tabular_row_fails() {
+    let input = b"[\n  # id, name\n  1,\n  2, Bob\n]";
+    assert!(format_toon(input, Indent::default()).is_err());
+}
@@ -0,0 +1,107 @@
+use serde_json::{json, Value};
+use toon::{DecodeOptions, DocumentReader, DocumentWriter, EncodeOptions};
+
+fn documents() -> Vec<Value> {
+    vec![
+        json!({"id": 1, "tags": ["a", "b"]}),
+        json!("---"),
+        json!([{"x": 1}, {"x": 2}]),
+        json!(null),
+    ]
+}
+
+fn read_all(bytes: &[u8], compact: bool) -> anyhow::Result<Vec<Value>> {
+    let opt = DecodeOptions {
+        compact,
+        ..Default::default()
+    };
+    DocumentReader::new(bytes, opt).collect()
+}
+
+#[test]
+fn test_roundtrip_documents() {
+    for compact in [false, true] {
+        for tabular_arrays in [false, true] {
+            let opt = EncodeOptions {
+                compact,
+                tabular_arrays,
+                ..Default::default()
+            };
+            let mut writer = DocumentWriter::new(Vec::new(), opt);
+            for doc in documents() {
+                writer.write(&doc).unwrap();
+            }
+            assert_eq!(writer.documents_written(), 4);
+            let bytes = writer.into_inner().unwrap();
+
+            assert_eq!(read_all(&bytes, compact).unwrap(), documents());
+        }
+    }
+}
+
+#[test]
+fn test_text_separator_layout() {
+    let mut writer = DocumentWriter::new(Vec::new(), EncodeOptions::default());
+    writer.write(&json!({"a": 1})).unwrap();
+    writer.serialize(&[1, 2]).unwrap();
+    assert_eq!(
+        String::from_utf8(writer.into_inner().unwrap()).unwrap(),
+        "{\n  a: 1\n}\n---\n[\n  1,\n  2\n]\n"
+    );
+}
+
+#[test]
+fn test_text_reader_skips_empty_documents() {
+    let input = b"---\n{a: 1}\n---  \r\n\n---\n[1]\n---\n";
+    assert_eq!(
+        read_all(input, false).unwrap(),
+        [json!({"a": 1}), json!([1])]
+    );
+    assert!(read_all(b"", false).unwrap().is_empty());
+}
+
+#[test]
+fn test_text_errors_name_the_document() {
+    let mut reader =
+        DocumentReader::new(&b"{a: 1}\n---\n{\n  b 2\n}\n"[..], DecodeOptions::default());
+    assert_eq!(reader.next().unwrap().unwrap(), json!({"a": 1}));
+    let err = reader.next().unwrap().unwrap_err();
+    assert!(
+        err.to_string().contains("document 2 starting at line 3"),
+        "{}",
+        err
+    );
+    assert!(reader.next().is_none());
+}
+
+#[test]
+fn test_compact_frame_errors() {
+    let opt = EncodeOptions {
+        compact: true,
+        ..Default::default()
+    };
+    let mut writer = DocumentWriter::new(Vec::new(), opt);
+    writer.write(&json!({"a": 1})).unwrap();
+    writer.write(&json!([1, 2, 3])).unwrap();
+    let bytes = writer.into_inner().unwrap();
+
+    let err = read_all(&bytes[..bytes.len() - 1], true).unwrap_err();
+    assert!(
+        err.to_string().contains("Truncated frame for document 2"),
+        "{}",
+        err
+    );
+
+    let mut bad = bytes.clone();
+    bad.extend_from_slice(&[1, 0]);
+    let err = read_all(&bad, true).unwrap_err();
+    assert!(
+        err.to_string().contains("Truncated frame header"),
+        "{}",
+        err
+    );
+
+    let mut bad = 4u32.to_le_bytes().to_vec();
+    bad.extend_from_slice(b"{a:1");
+    assert!(read_all(&bad, true).is_err());
+}
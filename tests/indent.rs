@@ -0,0 +1,95 @@
+use serde_json::json;
+use toon::{decode_toon_to_json, encode_json_to_toon, format_toon, DecodeOptions, EncodeOptions, Indent};
+
+fn strict() -> DecodeOptions {
+    DecodeOptions {
+        strict: true,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_tab_indentation() {
+    let value = json!({"a": [1, {"b": null}]});
+    let opts = EncodeOptions {
+        indent: Some(Indent::Tab),
+        ..Default::default()
+    };
+
+    let encoded = encode_json_to_toon(&value, &opts).unwrap();
+    assert_eq!(
+        String::from_utf8(encoded.clone()).unwrap(),
+        "{\n\ta: [\n\t\t1,\n\t\t{\n\t\t\tb: null\n\t\t}\n\t]\n}"
+    );
+    assert_eq!(decode_toon_to_json(&encoded, &strict()).unwrap(), value);
+}
+
+#[test]
+fn test_tab_indentation_tabular() {
+    let value = json!([{"id": 1}, {"id": 2}]);
+    let opts = EncodeOptions {
+        tabular_arrays: true,
+        indent: Some(Indent::Tab),
+        ..Default::default()
+    };
+
+    let encoded = encode_json_to_toon(&value, &opts).unwrap();
+    assert_eq!(
+        String::from_utf8(encoded.clone()).unwrap(),
+        "[\n\t# id\n\t1,\n\t2\n]"
+    );
+    assert_eq!(decode_toon_to_json(&encoded, &strict()).unwrap(), value);
+}
+
+#[test]
+fn test_tab_indentation_with_max_width() {
+    let value = json!({"items": ["alpha", "beta"], "n": 1});
+    let opts = EncodeOptions {
+        indent: Some(Indent::Tab),
+        max_width: Some(26),
+        ..Default::default()
+    };
+
+    let encoded = encode_json_to_toon(&value, &opts).unwrap();
+    assert_eq!(
+        String::from_utf8(encoded).unwrap(),
+        "{\n\titems: [alpha, beta],\n\tn: 1\n}"
+    );
+}
+
+#[test]
+fn test_mixed_indentation_rejected_in_strict_mode() {
+    let input = b"{\n  a: 1,\n\tb: 2\n}";
+
+    let err = decode_toon_to_json(input, &strict()).unwrap_err();
+    assert!(err.to_string().contains("line 3"), "{}", err);
+    assert_eq!(
+        decode_toon_to_json(input, &DecodeOptions::default()).unwrap(),
+        json!({"a": 1, "b": 2})
+    );
+}
+
+#[test]
+fn test_whitespace_inside_strings_is_not_indentation() {
+    let input = b"{\n  a: \"x\n\ty\"\n}";
+    assert_eq!(
+        decode_toon_to_json(input, &strict()).unwrap(),
+        json!({"a": "x\n\ty"})
+    );
+}
+
+#[test]
+fn test_fmt_with_tabs() {
+    let formatted = format_toon(b"{b: [1], a: 2}", Indent::Tab).unwrap();
+    assert_eq!(
+        String::from_utf8(formatted).unwrap(),
+        "{\n\ta: 2,\n\tb: [\n\t\t1\n\t]\n}\n"
+    );
+}
+
+#[test]
+fn test_indent_from_str() {
+    assert_eq!("tab".parse::<Indent>().unwrap(), Indent::Tab);
+    assert_eq!("4".parse::<Indent>().unwrap(), Indent::Spaces(4));
+    assert!("wide".parse::<Indent>().is_err());
+}
@@ -0,0 +1,132 @@
+use serde::Deserialize;
+use serde_json::{json, Value};
+use toon::codec::compact;
+use toon::{decode_from_reader, decode_toon_to_json, CompactView, DecodeOptions, EncodeOptions};
+
+fn sample() -> Value {
+    let users: Vec<Value> = (0..20)
+        .map(|i| json!({"id": i, "name": format!("user{}", i), "tags": ["a", "b"]}))
+        .collect();
+    let fields: serde_json::Map<String, Value> = (0..12)
+        .map(|i| (format!("field{:02}", i), json!(i * 10)))
+        .collect();
+    json!({"users": users, "fields": fields, "small": [1, 2, 3]})
+}
+
+fn indexed_options() -> EncodeOptions {
+    EncodeOptions {
+        compact: true,
+        compact_index: true,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_indexed_roundtrip_in_every_decoder() {
+    let bytes = toon::encode_json_to_toon(&sample(), &indexed_options()).unwrap();
+    assert_eq!(bytes, compact::encode_indexed(&sample()).unwrap());
+    assert_ne!(bytes, compact::encode(&sample()).unwrap());
+
+    assert_eq!(
+        decode_toon_to_json(&bytes, &DecodeOptions::default()).unwrap(),
+        sample()
+    );
+    assert_eq!(
+        decode_from_reader(&bytes[..], &DecodeOptions::default()).unwrap(),
+        sample()
+    );
+    assert_eq!(CompactView::new(&bytes).unwrap().to_value(), sample());
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct User {
+        id: u32,
+        name: String,
+    }
+    #[derive(Deserialize)]
+    struct Doc {
+        users: Vec<User>,
+    }
+    let doc: Doc = toon::compact::from_slice(&bytes).unwrap();
+    assert_eq!(doc.users.len(), 20);
+    assert_eq!(
+        doc.users[7],
+        User {
+            id: 7,
+            name: "user7".into()
+        }
+    );
+}
+
+#[test]
+fn test_view_jumps_through_offset_tables() {
+    let bytes = compact::encode_indexed(&sample()).unwrap();
+    let doc = CompactView::new(&bytes).unwrap();
+
+    let users = doc.get("users").unwrap();
+    assert!(users.is_array());
+    assert_eq!(users.len(), Some(20));
+    for i in [0, 7, 19] {
+        let user = users.index(i).unwrap();
+        assert_eq!(user.get("id").and_then(|v| v.as_u64()), Some(i as u64));
+    }
+    assert!(users.index(20).is_none());
+    assert_eq!(users.elements().unwrap().count(), 20);
+
+    let fields = doc.get("fields").unwrap();
+    assert!(fields.is_object());
+    for i in 0..12 {
+        let key = format!("field{:02}", i);
+        assert_eq!(fields.get(&key).and_then(|v| v.as_i64()), Some(i * 10));
+    }
+    assert!(fields.get("field").is_none());
+    assert!(fields.get("zzz").is_none());
+    assert_eq!(fields.entries().unwrap().count(), 12);
+
+    // Containers below the threshold are written as before
+    let small = doc.get("small").unwrap();
+    assert_eq!(
+        small.raw(),
+        &compact::encode(&json!([1, 2, 3])).unwrap()[5..]
+    );
+    assert_eq!(small.index(2).and_then(|v| v.as_i64()), Some(3));
+}
+
+#[test]
+fn test_view_rejects_bad_offset_tables() {
+    let value = json!([1, 2, 3, 4, 5, 6, 7, 8]);
+    let bytes = compact::encode_indexed(&value).unwrap();
+    // Magic, tag, count and size come before the first offset
+    let table = 5 + 1 + 4 + 4;
+
+    let mut bad = bytes.clone();
+    bad[table + 4] += 1;
+    let err = CompactView::new(&bad).unwrap_err();
+    assert!(err.to_string().contains("Offset of entry 1"), "{}", err);
+
+    let mut bad = bytes.clone();
+    bad[table - 4] += 1;
+    let err = CompactView::new(&bad).unwrap_err();
+    assert!(err.to_string().contains("Size"), "{}", err);
+
+    let err = CompactView::new(&bytes[..table + 8]).unwrap_err();
+    assert!(
+        err.to_string().contains("Truncated offset table"),
+        "{}",
+        err
+    );
+    assert!(compact::decode(&bytes[..table + 8]).is_err());
+}
+
+#[test]
+fn test_view_rejects_unsorted_indexed_keys() {
+    let fields: serde_json::Map<String, Value> =
+        (0..8).map(|i| (format!("k{}", i), json!(i))).collect();
+    let mut bytes = compact::encode_indexed(&Value::Object(fields)).unwrap();
+    // Swap the digits of the first two keys, "k0" and "k1"
+    let first = 5 + 1 + 4 + 4 + 8 * 4;
+    let second = first + 4 + 2 + 1 + 4 + 1;
+    bytes.swap(first + 5, second + 5);
+
+    let err = CompactView::new(&bytes).unwrap_err();
+    assert!(err.to_string().contains("not sorted"), "{}", err);
+}
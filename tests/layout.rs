@@ -0,0 +1,62 @@
+use serde_json::json;
+use toon::{decode_toon_to_json, encode_json_to_toon, DecodeOptions, EncodeOptions};
+
+fn encode_with_width(value: &serde_json::Value, max_width: usize) -> String {
+    let opts = EncodeOptions {
+        max_width: Some(max_width),
+        ..Default::default()
+    };
+    String::from_utf8(encode_json_to_toon(value, &opts).unwrap()).unwrap()
+}
+
+#[test]
+fn test_small_containers_fit_on_one_line() {
+    let value = json!({"a": [1, 2, 3], "b": {"x": true}});
+    assert_eq!(encode_with_width(&value, 80), "{a: [1, 2, 3], b: {x: true}}");
+}
+
+#[test]
+fn test_outer_breaks_inner_stays_flat() {
+    let value = json!({"name": "Alice", "tags": ["admin", "staff"]});
+    assert_eq!(
+        encode_with_width(&value, 24),
+        "{\n  name: Alice,\n  tags: [admin, staff]\n}"
+    );
+}
+
+#[test]
+fn test_narrow_width_matches_default_layout() {
+    let value = json!({"a": [1, 2], "b": {"c": null}});
+    let default = encode_json_to_toon(&value, &EncodeOptions::default()).unwrap();
+    assert_eq!(encode_with_width(&value, 1).into_bytes(), default);
+}
+
+#[test]
+fn test_width_includes_trailing_comma() {
+    // `[1, 2],` is 7 columns wide, so the inner array only fits at width 9
+    let value = json!([[1, 2], 3]);
+    assert_eq!(encode_with_width(&value, 8), "[\n  [\n    1,\n    2\n  ],\n  3\n]");
+    assert_eq!(encode_with_width(&value, 9), "[\n  [1, 2],\n  3\n]");
+}
+
+#[test]
+fn test_empty_containers() {
+    assert_eq!(encode_with_width(&json!({"a": [], "b": {}}), 80), "{a: [], b: {}}");
+}
+
+#[test]
+fn test_flat_output_roundtrips() {
+    let value = json!({
+        "users": [
+            {"id": 1, "name": "Alice Smith", "roles": ["admin"]},
+            {"id": 2, "name": "Bob", "roles": []}
+        ],
+        "count": 2
+    });
+
+    for max_width in [1, 20, 40, 200] {
+        let encoded = encode_with_width(&value, max_width);
+        let decoded = decode_toon_to_json(encoded.as_bytes(), &DecodeOptions::default()).unwrap();
+        assert_eq!(value, decoded);
+    }
+}
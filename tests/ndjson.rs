@@ -0,0 +1,179 @@
+use serde_json::{json, Value};
+use toon::{
+    decode_toon_to_json, encode_json_to_toon, ndjson, DecodeOptions, EncodeOptions, Indent,
+};
+
+const LOG: &str = r#"{"level":"info","msg":"started","ts":1}
+
+{"level":"warn","msg":"slow, retrying","ts":2}
+{"level":"info","msg":"done","ts":3}
+"#;
+
+fn records() -> Value {
+    json!([
+        {"level": "info", "msg": "started", "ts": 1},
+        {"level": "warn", "msg": "slow, retrying", "ts": 2},
+        {"level": "info", "msg": "done", "ts": 3}
+    ])
+}
+
+fn encode(input: &str, opt: &EncodeOptions) -> anyhow::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    ndjson::encode(input.as_bytes(), &mut out, opt)?;
+    Ok(out)
+}
+
+fn decode(input: &[u8]) -> String {
+    let mut out = Vec::new();
+    ndjson::decode(input, &mut out, &DecodeOptions::default()).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+fn all_options() -> Vec<EncodeOptions> {
+    let mut options = Vec::new();
+    for tabular_arrays in [false, true] {
+        for compact in [false, true] {
+            options.push(EncodeOptions {
+                tabular_arrays,
+                compact,
+                ..Default::default()
+            });
+        }
+    }
+    options
+}
+
+#[test]
+fn test_encode_one_record_per_line() {
+    let encoded = encode(LOG, &EncodeOptions::default()).unwrap();
+    assert_eq!(
+        String::from_utf8(encoded).unwrap(),
+        "[\n  {level: info, msg: started, ts: 1},\n  \
+         {level: warn, msg: \"slow, retrying\", ts: 2},\n  \
+         {level: info, msg: done, ts: 3}\n]"
+    );
+}
+
+#[test]
+fn test_encode_tabular_block() {
+    let opts = EncodeOptions {
+        tabular_arrays: true,
+        ..Default::default()
+    };
+    let encoded = encode(LOG, &opts).unwrap();
+    assert_eq!(
+        String::from_utf8(encoded).unwrap(),
+        "[\n  # level, msg, ts\n  info, started, 1,\n  \
+         warn, \"slow, retrying\", 2,\n  info, done, 3\n]"
+    );
+}
+
+#[test]
+fn test_tabular_block_matches_value_encoder() {
+    for compact in [false, true] {
+        for indent in [None, Some(Indent::Tab)] {
+            let opts = EncodeOptions {
+                tabular_arrays: true,
+                typed_columns: true,
+                compact,
+                indent,
+                ..Default::default()
+            };
+            let encoded = encode(LOG, &opts).unwrap();
+            assert_eq!(encoded, encode_json_to_toon(&records(), &opts).unwrap());
+        }
+    }
+
+    let opts = EncodeOptions {
+        tabular_arrays: true,
+        typed_columns: true,
+        ..Default::default()
+    };
+    let encoded = String::from_utf8(encode(LOG, &opts).unwrap()).unwrap();
+    assert_eq!(
+        encoded.lines().nth(1).unwrap(),
+        "  # level:str, msg:str, ts:int"
+    );
+}
+
+#[test]
+fn test_tabular_falls_back_for_mixed_records() {
+    let mixed = "{\"a\": 1}\n{\"a\": 2}\n{\"b\": 3}\n";
+    for compact in [false, true] {
+        let opts = EncodeOptions {
+            tabular_arrays: true,
+            compact,
+            ..Default::default()
+        };
+        let encoded = encode(mixed, &opts).unwrap();
+        assert_eq!(
+            decode_toon_to_json(&encoded, &DecodeOptions::default()).unwrap(),
+            json!([{"a": 1}, {"a": 2}, {"b": 3}])
+        );
+
+        let strict = EncodeOptions {
+            strict: true,
+            ..opts
+        };
+        let mut out = Vec::new();
+        let err = ndjson::encode(mixed.as_bytes(), &mut out, &strict).unwrap_err();
+        assert!(format!("{:#}", err).contains("line 3"), "{:#}", err);
+        assert!(out.is_empty());
+    }
+    assert_eq!(
+        encode(
+            mixed,
+            &EncodeOptions {
+                tabular_arrays: true,
+                ..Default::default()
+            }
+        )
+        .unwrap(),
+        encode(mixed, &EncodeOptions::default()).unwrap()
+    );
+}
+
+#[test]
+fn test_roundtrip_in_every_mode() {
+    let expected: String = records()
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|record| format!("{}\n", record))
+        .collect();
+
+    for opts in all_options() {
+        let encoded = encode(LOG, &opts).unwrap();
+        assert_eq!(
+            decode_toon_to_json(&encoded, &DecodeOptions::default()).unwrap(),
+            records(),
+            "{:?}",
+            opts
+        );
+        assert_eq!(decode(&encoded), expected, "{:?}", opts);
+    }
+}
+
+#[test]
+fn test_empty_input() {
+    for opts in all_options() {
+        let encoded = encode("\n", &opts).unwrap();
+        assert_eq!(decode(&encoded), "", "{:?}", opts);
+    }
+}
+
+#[test]
+fn test_decode_non_array_is_one_line() {
+    assert_eq!(decode(b"{a: [1, 2]}"), "{\"a\":[1,2]}\n");
+    assert_eq!(decode(b"[[1], {b: 2}]"), "[1]\n{\"b\":2}\n");
+}
+
+#[test]
+fn test_invalid_json_line() {
+    let err = encode("{\"a\": 1}\n{oops}\n", &EncodeOptions::default()).unwrap_err();
+    assert!(
+        format!("{:#}", err).contains("Invalid JSON on line 2"),
+        "{:#}",
+        err
+    );
+}
@@ -0,0 +1,230 @@
+use serde_json::{json, Value};
+use toon::patch::{apply_merge_patch, apply_patch, patch_document};
+use toon::{DecodeOptions, EncodeOptions, Patch};
+
+fn patched(doc: Value, patch: Value) -> anyhow::Result<Value> {
+    let mut doc = doc;
+    apply_patch(&mut doc, &patch)?;
+    Ok(doc)
+}
+
+#[test]
+fn test_json_patch_operations() {
+    let doc = json!({"foo": ["bar", "baz"], "a~b": {"c/d": 1}});
+    let patch = json!([
+        {"op": "add", "path": "/foo/1", "value": "qux"},
+        {"op": "add", "path": "/foo/-", "value": "end"},
+        {"op": "remove", "path": "/foo/0"},
+        {"op": "replace", "path": "/a~0b/c~1d", "value": 2},
+        {"op": "copy", "from": "/a~0b", "path": "/copied"},
+        {"op": "move", "from": "/foo", "path": "/moved"},
+        {"op": "test", "path": "/moved", "value": ["qux", "baz", "end"]}
+    ]);
+    assert_eq!(
+        patched(doc, patch).unwrap(),
+        json!({"a~b": {"c/d": 2}, "copied": {"c/d": 2}, "moved": ["qux", "baz", "end"]})
+    );
+
+    assert_eq!(
+        patched(
+            json!({"a": 1}),
+            json!([{"op": "replace", "path": "", "value": [1]}])
+        )
+        .unwrap(),
+        json!([1])
+    );
+}
+
+#[test]
+fn test_json_patch_is_atomic() {
+    let mut doc = json!({"a": 1, "list": [1, 2]});
+    let patch = json!([
+        {"op": "add", "path": "/b", "value": 2},
+        {"op": "test", "path": "/a", "value": 5}
+    ]);
+    let err = apply_patch(&mut doc, &patch).unwrap_err();
+    assert!(format!("{:#}", err).contains("operation 2"), "{:#}", err);
+    assert!(
+        format!("{:#}", err).contains("Test failed at /a"),
+        "{:#}",
+        err
+    );
+    assert_eq!(doc, json!({"a": 1, "list": [1, 2]}));
+
+    for (patch, message) in [
+        (
+            json!([{"op": "remove", "path": "/missing"}]),
+            "No value at /missing",
+        ),
+        (
+            json!([{"op": "add", "path": "/list/5", "value": 0}]),
+            "Invalid array index",
+        ),
+        (
+            json!([{"op": "add", "path": "/a/b", "value": 0}]),
+            "/a is not an array or object",
+        ),
+        (
+            json!([{"op": "move", "from": "/list", "path": "/list/0"}]),
+            "into one of its children",
+        ),
+        (
+            json!([{"op": "frobnicate", "path": "/a"}]),
+            "Unknown patch operation",
+        ),
+        (
+            json!([{"op": "add", "path": "a", "value": 0}]),
+            "Invalid JSON Pointer",
+        ),
+        (json!([{"op": "add", "path": "/a"}]), "Missing \"value\""),
+        (json!({"op": "add"}), "must be an array"),
+    ] {
+        let err = apply_patch(&mut doc, &patch).unwrap_err();
+        assert!(format!("{:#}", err).contains(message), "{:#}", err);
+    }
+}
+
+#[test]
+fn test_merge_patch() {
+    // The example from RFC 7396, section 3
+    let mut doc = json!({
+        "title": "Goodbye!",
+        "author": {"givenName": "John", "familyName": "Doe"},
+        "tags": ["example", "sample"],
+        "content": "This will be unchanged"
+    });
+    let patch = json!({
+        "title": "Hello!",
+        "phoneNumber": "+01-123-456-7890",
+        "author": {"familyName": null},
+        "tags": ["example"]
+    });
+    apply_merge_patch(&mut doc, &patch);
+    assert_eq!(
+        doc,
+        json!({
+            "title": "Hello!",
+            "author": {"givenName": "John"},
+            "tags": ["example"],
+            "content": "This will be unchanged",
+            "phoneNumber": "+01-123-456-7890"
+        })
+    );
+
+    let mut doc = json!([1]);
+    Patch::Merge(json!({"a": {"b": null, "c": 1}}))
+        .apply(&mut doc)
+        .unwrap();
+    assert_eq!(doc, json!({"a": {"c": 1}}));
+}
+
+#[test]
+fn test_patch_document_keeps_text_layout() {
+    let input = b"{\n\tname: app,\n\tport: 80\n}\n";
+    let patch = Patch::Json(json!([{"op": "replace", "path": "/port", "value": 8080}]));
+    let out = patch_document(input, &patch, &DecodeOptions::default()).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "{\n\tname: app,\n\tport: 8080\n}\n"
+    );
+
+    let input = b"[\n    # id, name\n    1, a,\n    2, b\n]";
+    let patch = Patch::Json(json!([{"op": "add", "path": "/-", "value": {"id": 3, "name": "c"}}]));
+    let out = patch_document(input, &patch, &DecodeOptions::default()).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "[\n    # id, name\n    1, a,\n    2, b,\n    3, c\n]"
+    );
+}
+
+#[test]
+fn test_patch_document_keeps_compact_format() {
+    let rows: Vec<Value> = (0..10).map(|i| json!({"id": i})).collect();
+    let patch = Patch::Json(json!([{"op": "remove", "path": "/0"}]));
+    let expected = Value::Array(rows[1..].to_vec());
+
+    for (tabular_arrays, compact_index) in [(false, false), (false, true), (true, false)] {
+        let opts = EncodeOptions {
+            compact: true,
+            tabular_arrays,
+            compact_index,
+            ..Default::default()
+        };
+        let input = toon::encode_json_to_toon(&Value::Array(rows.clone()), &opts).unwrap();
+        let out = patch_document(&input, &patch, &DecodeOptions::default()).unwrap();
+        assert_eq!(out, toon::encode_json_to_toon(&expected, &opts).unwrap());
+    }
+}
+
+#[test]
+fn test_patch_document_edits_text_in_place() {
+    let edit = |input: &str, ops: Value| {
+        let out = patch_document(
+            input.as_bytes(),
+            &Patch::Json(ops),
+            &DecodeOptions::default(),
+        )
+        .unwrap();
+        String::from_utf8(out).unwrap()
+    };
+
+    // Hand-written key order and one-line containers are kept
+    let input = "{\n  server: {port: 80, host: local},\n  name: app,\n  tags: [a, b]\n}\n";
+    assert_eq!(
+        edit(
+            input,
+            json!([{"op": "replace", "path": "/server/port", "value": 8080}])
+        ),
+        "{\n  server: {port: 8080, host: local},\n  name: app,\n  tags: [a, b]\n}\n"
+    );
+    assert_eq!(
+        edit(
+            input,
+            json!([
+                {"op": "add", "path": "/tags/-", "value": "c"},
+                {"op": "remove", "path": "/server/host"},
+                {"op": "add", "path": "/debug", "value": true},
+                {"op": "add", "path": "/zone", "value": {"id": 1}}
+            ])
+        ),
+        "{\n  debug: true,\n  server: {port: 80},\n  name: app,\n  tags: [a, b, c],\n  zone: {\n    id: 1\n  }\n}\n"
+    );
+
+    // Removing the first entries keeps the rest as they were
+    assert_eq!(
+        edit(
+            "{a: 1, b: 2, c: 3}",
+            json!([{"op": "remove", "path": "/a"}, {"op": "remove", "path": "/b"}])
+        ),
+        "{c: 3}"
+    );
+    assert_eq!(
+        edit("{a: 1}", json!([{"op": "remove", "path": "/a"}])),
+        "{}"
+    );
+
+    // A document written with a maximum width only changes where patched
+    let value = json!({"users": [{"id": 1, "name": "a"}, {"id": 2, "name": "b"}], "version": 1});
+    let opts = EncodeOptions {
+        max_width: Some(40),
+        ..Default::default()
+    };
+    let input = String::from_utf8(toon::encode_json_to_toon(&value, &opts).unwrap()).unwrap();
+    let out = edit(
+        &input,
+        json!([{"op": "replace", "path": "/users/1/name", "value": "bee"}]),
+    );
+    assert_eq!(out, input.replace("name: b}", "name: bee}"));
+    let out = edit(
+        &input,
+        json!([{"op": "replace", "path": "/version", "value": 2}]),
+    );
+    assert_eq!(out, input.replace("version: 1", "version: 2"));
+
+    // Replacing a tabular block with a non-uniform array rewrites it plainly
+    let out = edit(
+        "[\n  # id\n  1,\n  2\n]",
+        json!([{"op": "add", "path": "/-", "value": 3}]),
+    );
+    assert_eq!(out, "[\n  {\n    id: 1\n  },\n  {\n    id: 2\n  },\n  3\n]");
+}
@@ -1,6 +1,25 @@
 use proptest::prelude::*;
 use serde_json::Value;
-use toon::{decode_toon_to_json, encode_json_to_toon, DecodeOptions, EncodeOptions};
+use toon::{
+    decode_from_reader, decode_toon_to_json, encode_json_to_toon, from_str, to_string, DecodeOptions, EncodeOptions,
+    Indent, QuotePolicy,
+};
+
+/// Hands out at most one byte per read, to exercise buffer refills.
+struct TrickleReader<'a>(&'a [u8]);
+
+impl std::io::Read for TrickleReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match (self.0.split_first(), buf.first_mut()) {
+            (Some((&b, rest)), Some(slot)) => {
+                *slot = b;
+                self.0 = rest;
+                Ok(1)
+            }
+            _ => Ok(0),
+        }
+    }
+}
 
 fn json_value_strategy() -> impl Strategy<Value = Value> {
     let leaf = prop_oneof![
@@ -31,6 +50,72 @@ fn json_value_strategy() -> impl Strategy<Value = Value> {
     )
 }
 
+fn quote_policy_strategy() -> impl Strategy<Value = QuotePolicy> {
+    prop_oneof![
+        Just(QuotePolicy::Minimal),
+        Just(QuotePolicy::AlwaysStrings),
+        Just(QuotePolicy::NonAscii),
+        Just(QuotePolicy::Ascii),
+    ]
+}
+
+/// Strings that stress quoting: keywords, number look-alikes, punctuation,
+/// whitespace, control characters and arbitrary unicode.
+fn tricky_string_strategy() -> impl Strategy<Value = String> {
+    prop_oneof![
+        any::<String>(),
+        "(true|false|null|#|-|\\+|[0-9])[a-z0-9.:,]{0,5}",
+        "[ a-z\"\\\\:,{}\\[\\]#\t\n]{0,10}",
+    ]
+}
+
+fn tricky_value_strategy() -> impl Strategy<Value = Value> {
+    let leaf = prop_oneof![
+        Just(Value::Null),
+        any::<bool>().prop_map(Value::Bool),
+        any::<i32>().prop_map(|n| Value::Number(n.into())),
+        tricky_string_strategy().prop_map(Value::String),
+    ];
+
+    leaf.prop_recursive(3, 64, 5, |inner| {
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 0..5).prop_map(Value::Array),
+            prop::collection::hash_map(tricky_string_strategy(), inner, 0..5)
+                .prop_map(|m| Value::Object(m.into_iter().collect())),
+        ]
+    })
+}
+
+fn encode_options_strategy() -> impl Strategy<Value = EncodeOptions> {
+    (
+        any::<bool>(),
+        prop_oneof![Just(Indent::Tab), (0..5_u8).prop_map(Indent::Spaces)],
+        prop::option::of(1..80_usize),
+        quote_policy_strategy(),
+        any::<bool>(),
+        any::<bool>(),
+    )
+        .prop_map(|(tabular_arrays, indent, max_width, quoting, strict, typed_columns)| EncodeOptions {
+            tabular_arrays,
+            typed_columns,
+            indent: Some(indent),
+            max_width,
+            quoting,
+            strict,
+            ..Default::default()
+        })
+}
+
+fn uniform_rows_strategy() -> impl Strategy<Value = Value> {
+    prop::collection::hash_set(tricky_string_strategy(), 1..4).prop_flat_map(|keys| {
+        let keys: Vec<String> = keys.into_iter().collect();
+        let row = prop::collection::vec(tricky_value_strategy(), keys.len()).prop_map(
+            move |values| Value::Object(keys.iter().cloned().zip(values).collect()),
+        );
+        prop::collection::vec(row, 1..5).prop_map(Value::Array)
+    })
+}
+
 proptest! {
     #[test]
     fn test_roundtrip_text_mode(value in json_value_strategy()) {
@@ -58,4 +143,204 @@ proptest! {
         .map_err(|e| TestCaseError::fail(e.to_string()))?;
         prop_assert_eq!(value, decoded);
     }
+
+    #[test]
+    fn test_roundtrip_indexed_compact(value in json_value_strategy()) {
+        let encoded = toon::codec::compact::encode_indexed(&value)
+        .map_err(|e| TestCaseError::fail(e.to_string()))?;
+        let decoded = decode_toon_to_json(&encoded, &DecodeOptions::default())
+        .map_err(|e| TestCaseError::fail(e.to_string()))?;
+        prop_assert_eq!(&value, &decoded);
+        let view = toon::CompactView::new(&encoded)
+        .map_err(|e| TestCaseError::fail(e.to_string()))?;
+        if let Value::Object(obj) = &value {
+            for (key, field) in obj {
+                prop_assert_eq!(view.get(key).map(|v| v.to_value()), Some(field.clone()));
+            }
+        }
+        prop_assert_eq!(view.to_value(), value);
+    }
+
+    #[test]
+    fn test_diff_as_patch_gives_new_value(old in json_value_strategy(), new in json_value_strategy()) {
+        let changes = toon::diff::diff(&old, &new, &Default::default());
+        let mut patched = old;
+        toon::patch::apply_patch(&mut patched, &toon::diff::to_patch(&changes))
+        .map_err(|e| TestCaseError::fail(e.to_string()))?;
+        prop_assert_eq!(patched, new);
+    }
+
+    #[test]
+    fn test_patch_text_document_in_place(
+        old in json_value_strategy(),
+        merge in json_value_strategy(),
+        max_width in prop_oneof![Just(None), (1..120_usize).prop_map(Some)],
+    ) {
+        let opts = EncodeOptions {
+            max_width,
+            ..Default::default()
+        };
+        let encoded = encode_json_to_toon(&old, &opts)
+        .map_err(|e| TestCaseError::fail(e.to_string()))?;
+        let mut new = old.clone();
+        toon::patch::apply_merge_patch(&mut new, &merge);
+        let patch = toon::diff::to_patch(&toon::diff::diff(&old, &new, &Default::default()));
+        let patched = toon::patch::patch_document(&encoded, &toon::Patch::Json(patch), &DecodeOptions::default())
+        .map_err(|e| TestCaseError::fail(e.to_string()))?;
+        let decoded = decode_toon_to_json(&patched, &DecodeOptions::default())
+        .map_err(|e| TestCaseError::fail(e.to_string()))?;
+        prop_assert_eq!(decoded, new);
+    }
+
+    #[test]
+    fn test_roundtrip_width_aware_layout(value in json_value_strategy(), max_width in 1..120_usize) {
+        let opts = EncodeOptions {
+            max_width: Some(max_width),
+            ..Default::default()
+        };
+        let encoded = encode_json_to_toon(&value, &opts)
+        .map_err(|e| TestCaseError::fail(e.to_string()))?;
+        let decoded = decode_toon_to_json(&encoded, &DecodeOptions::default())
+        .map_err(|e| TestCaseError::fail(e.to_string()))?;
+        prop_assert_eq!(value, decoded);
+    }
+
+    #[test]
+    fn test_roundtrip_every_quote_policy(
+        value in tricky_value_strategy(),
+        quoting in quote_policy_strategy(),
+        max_width in prop::option::of(1..80_usize),
+    ) {
+        let opts = EncodeOptions {
+            quoting,
+            max_width,
+            ..Default::default()
+        };
+        let encoded = encode_json_to_toon(&value, &opts)
+        .map_err(|e| TestCaseError::fail(e.to_string()))?;
+        let decoded = decode_toon_to_json(&encoded, &DecodeOptions::default())
+        .map_err(|e| TestCaseError::fail(e.to_string()))?;
+        prop_assert_eq!(value, decoded);
+    }
+
+    #[test]
+    fn test_roundtrip_tabular_every_quote_policy(
+        value in uniform_rows_strategy(),
+        quoting in quote_policy_strategy(),
+        typed_columns in any::<bool>(),
+    ) {
+        let opts = EncodeOptions {
+            tabular_arrays: true,
+            typed_columns,
+            quoting,
+            ..Default::default()
+        };
+        let encoded = encode_json_to_toon(&value, &opts)
+        .map_err(|e| TestCaseError::fail(e.to_string()))?;
+        let strict = DecodeOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let decoded = decode_toon_to_json(&encoded, &strict)
+        .map_err(|e| TestCaseError::fail(e.to_string()))?;
+        prop_assert_eq!(value, decoded);
+    }
+
+    #[test]
+    fn test_serializer_matches_value_encoder(
+        value in prop_oneof![tricky_value_strategy(), uniform_rows_strategy()],
+        opts in encode_options_strategy(),
+    ) {
+        let expected = encode_json_to_toon(&value, &opts).map(String::from_utf8);
+        match (to_string(&value, &opts), expected) {
+            (Ok(actual), Ok(expected)) => prop_assert_eq!(actual, expected.unwrap()),
+            (Err(_), Err(_)) => {}
+            (actual, expected) => prop_assert!(
+                false,
+                "serializer gave {:?}, encoder gave {:?}",
+                actual.map_err(|e| e.to_string()),
+                expected.map_err(|e| e.to_string())
+            ),
+        }
+    }
+
+    #[test]
+    fn test_deserializer_matches_value_decoder(
+        value in prop_oneof![tricky_value_strategy(), uniform_rows_strategy()],
+        opts in encode_options_strategy(),
+    ) {
+        let Ok(encoded) = encode_json_to_toon(&value, &opts) else {
+            return Ok(());
+        };
+        let text = String::from_utf8(encoded).unwrap();
+        let decoded = decode_toon_to_json(text.as_bytes(), &DecodeOptions::default())
+            .map_err(|e| TestCaseError::fail(e.to_string()))?;
+        let deserialized: Value = from_str(&text)
+            .map_err(|e| TestCaseError::fail(e.to_string()))?;
+        prop_assert_eq!(deserialized, decoded);
+    }
+
+    #[test]
+    fn test_compact_serde_matches_value_codec(value in json_value_strategy()) {
+        let opts = EncodeOptions {
+            compact: true,
+            ..Default::default()
+        };
+        let expected = encode_json_to_toon(&value, &opts)
+            .map_err(|e| TestCaseError::fail(e.to_string()))?;
+        let encoded = toon::compact::to_vec(&value)
+            .map_err(|e| TestCaseError::fail(e.to_string()))?;
+        prop_assert_eq!(&encoded, &expected);
+
+        let decoded: Value = toon::compact::from_slice(&encoded)
+            .map_err(|e| TestCaseError::fail(e.to_string()))?;
+        prop_assert_eq!(decoded, value);
+    }
+
+    #[test]
+    fn test_reader_decoder_matches_slice_decoder(
+        value in prop_oneof![tricky_value_strategy(), uniform_rows_strategy()],
+        opts in encode_options_strategy(),
+        compact in any::<bool>(),
+    ) {
+        let opts = EncodeOptions { compact, ..opts };
+        let Ok(encoded) = encode_json_to_toon(&value, &opts) else {
+            return Ok(());
+        };
+        let decode_opts = DecodeOptions::default();
+        let expected = decode_toon_to_json(&encoded, &decode_opts)
+            .map_err(|e| TestCaseError::fail(e.to_string()))?;
+
+        let decoded = decode_from_reader(encoded.as_slice(), &decode_opts)
+            .map_err(|e| TestCaseError::fail(format!("{:#}", e)))?;
+        prop_assert_eq!(&decoded, &expected);
+
+        let trickled = decode_from_reader(TrickleReader(&encoded), &decode_opts)
+            .map_err(|e| TestCaseError::fail(format!("{:#}", e)))?;
+        prop_assert_eq!(&trickled, &expected);
+    }
+
+    #[test]
+    fn test_embedded_schema_roundtrip(
+        value in prop_oneof![tricky_value_strategy(), uniform_rows_strategy()],
+        compact_index in any::<bool>(),
+    ) {
+        let schema = toon::schema::infer_schema([&value], &Default::default());
+        let opts = EncodeOptions {
+            compact: true,
+            compact_index,
+            schema: Some(toon::schema::Schema::new(schema).unwrap()),
+            embed_schema: true,
+            ..Default::default()
+        };
+        let encoded = encode_json_to_toon(&value, &opts)
+            .map_err(|e| TestCaseError::fail(format!("{:#}", e)))?;
+
+        let decoded = decode_from_reader(TrickleReader(&encoded), &DecodeOptions::default())
+            .map_err(|e| TestCaseError::fail(format!("{:#}", e)))?;
+        prop_assert_eq!(&decoded, &value);
+        let view = toon::CompactView::new(&encoded)
+            .map_err(|e| TestCaseError::fail(format!("{:#}", e)))?;
+        prop_assert_eq!(view.to_value(), value);
+    }
 }
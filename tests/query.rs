@@ -0,0 +1,108 @@
+use serde_json::{json, Value};
+use toon::codec::compact;
+use toon::query::select_document;
+use toon::{CompactView, DecodeOptions, EncodeOptions, Query};
+
+fn sample() -> Value {
+    json!({
+        "users": [
+            {"id": 1, "name": "Ada", "tags": ["admin"]},
+            {"id": 2, "name": "Bob", "tags": []}
+        ],
+        "meta": {"a/b": 1, "m~n": 2, "0": "zero", "id": 99}
+    })
+}
+
+fn select(query: &str) -> Vec<Value> {
+    Query::parse(query)
+        .unwrap()
+        .select(&sample())
+        .into_iter()
+        .cloned()
+        .collect()
+}
+
+#[test]
+fn test_json_pointer() {
+    assert_eq!(select(""), [sample()]);
+    assert_eq!(select("/users/1/name"), [json!("Bob")]);
+    assert_eq!(select("/meta/a~1b"), [json!(1)]);
+    assert_eq!(select("/meta/m~0n"), [json!(2)]);
+    // Numeric tokens index arrays and name members of objects
+    assert_eq!(select("/meta/0"), [json!("zero")]);
+    assert!(select("/users/01").is_empty());
+    assert!(select("/users/-").is_empty());
+    assert!(select("/users/2").is_empty());
+    assert!(select("/missing/x").is_empty());
+    assert!(Query::parse("/a~2").is_err());
+    assert!(Query::parse("/a").unwrap().is_singular());
+}
+
+#[test]
+fn test_jsonpath_subset() {
+    assert_eq!(select("$"), [sample()]);
+    assert_eq!(select("$.users[0].name"), [json!("Ada")]);
+    assert_eq!(select("$.users[-1].id"), [json!(2)]);
+    assert_eq!(select("$['meta'][\"a/b\"]"), [json!(1)]);
+    assert_eq!(select("$.users[*].name"), [json!("Ada"), json!("Bob")]);
+    assert_eq!(select("$.users.*.id"), [json!(1), json!(2)]);
+    assert_eq!(select("$..id"), [json!(99), json!(1), json!(2)]);
+    assert_eq!(select("$..tags[0]"), [json!("admin")]);
+    assert!(select("$.users[-3]").is_empty());
+    assert!(select("$.users.name").is_empty());
+
+    assert!(Query::parse("$.users[0]").unwrap().is_singular());
+    assert!(!Query::parse("$..id").unwrap().is_singular());
+    for bad in ["$.", "$[", "$[x]", "$['open", "users", "$users"] {
+        assert!(Query::parse(bad).is_err(), "{}", bad);
+    }
+}
+
+#[test]
+fn test_compact_view_matches_decoded_value() {
+    let queries = [
+        "/users/1/tags",
+        "/meta/0",
+        "$..id",
+        "$.users[*].name",
+        "$..*",
+        "$.users[-1]",
+    ];
+    let value = sample();
+    for bytes in [
+        compact::encode(&value).unwrap(),
+        compact::encode_indexed(&value).unwrap(),
+    ] {
+        let view = CompactView::new(&bytes).unwrap();
+        for query in queries {
+            let query = Query::parse(query).unwrap();
+            let from_view: Vec<Value> = query
+                .select_view(view)
+                .iter()
+                .map(CompactView::to_value)
+                .collect();
+            let from_value: Vec<Value> = query.select(&value).into_iter().cloned().collect();
+            assert_eq!(from_view, from_value, "{}", query);
+        }
+    }
+}
+
+#[test]
+fn test_select_document_in_every_format() {
+    let query: Query = "$.users[*].id".parse().unwrap();
+    for (compact, tabular_arrays) in [(false, false), (true, false), (true, true)] {
+        let opts = EncodeOptions {
+            compact,
+            tabular_arrays,
+            ..Default::default()
+        };
+        let bytes = toon::encode_json_to_toon(&sample(), &opts).unwrap();
+        assert_eq!(
+            select_document(&bytes, &query, &DecodeOptions::default()).unwrap(),
+            [json!(1), json!(2)]
+        );
+    }
+
+    let err = select_document(b"TOON\x01\x05", &query, &DecodeOptions::default()).unwrap_err();
+    assert!(format!("{:#}", err).contains("compact"), "{:#}", err);
+}
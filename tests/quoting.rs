@@ -0,0 +1,57 @@
+use serde_json::json;
+use toon::codec::quoting::{needs_quotes, QuotePolicy};
+use toon::{decode_toon_to_json, encode_json_to_toon, DecodeOptions, EncodeOptions};
+
+fn encode_with(value: &serde_json::Value, quoting: QuotePolicy, tabular_arrays: bool) -> String {
+    let opts = EncodeOptions {
+        quoting,
+        tabular_arrays,
+        max_width: Some(80),
+        ..Default::default()
+    };
+    String::from_utf8(encode_json_to_toon(value, &opts).unwrap()).unwrap()
+}
+
+#[test]
+fn test_minimal_quotes_literal_look_alikes() {
+    for s in ["true", "null", "nullable", "007", "-x", "#tag", "a: b", ""] {
+        assert!(needs_quotes(s, QuotePolicy::Minimal), "{:?} should be quoted", s);
+    }
+    for s in ["hello", "snake_case", "café", "a.b"] {
+        assert!(!needs_quotes(s, QuotePolicy::Minimal), "{:?} should be bare", s);
+    }
+}
+
+#[test]
+fn test_tabular_quotes_keyword_strings() {
+    let value = json!([{"flag": "true", "code": "007"}, {"flag": "no", "code": "x"}]);
+    let encoded = encode_with(&value, QuotePolicy::Minimal, true);
+
+    assert!(encoded.contains("\"007\", \"true\""));
+    let decoded = decode_toon_to_json(encoded.as_bytes(), &DecodeOptions::default()).unwrap();
+    assert_eq!(value, decoded);
+}
+
+#[test]
+fn test_always_quotes_keys_and_values() {
+    let value = json!({"name": "Alice", "n": 1});
+    assert_eq!(
+        encode_with(&value, QuotePolicy::AlwaysStrings, false),
+        "{\"n\": 1, \"name\": \"Alice\"}"
+    );
+}
+
+#[test]
+fn test_non_ascii_quotes_only_non_ascii() {
+    let value = json!(["plain", "café"]);
+    assert_eq!(
+        encode_with(&value, QuotePolicy::NonAscii, false),
+        "[plain, \"café\"]"
+    );
+}
+
+#[test]
+fn test_policy_from_str() {
+    assert_eq!("always".parse::<QuotePolicy>().unwrap(), QuotePolicy::AlwaysStrings);
+    assert!("sometimes".parse::<QuotePolicy>().is_err());
+}
@@ -1,3 +1,5 @@
+#![allow(clippy::approx_constant)]
+
 use serde_json::{json, Value};
 use toon::{decode_toon_to_json, encode_json_to_toon, DecodeOptions, EncodeOptions};
 
@@ -6,6 +8,7 @@ fn roundtrip_test(value: &Value, opts: &EncodeOptions) {
     let decode_opts = DecodeOptions {
         compact: opts.compact,
         strict: opts.strict,
+        ..Default::default()
     };
     let decoded = decode_toon_to_json(&encoded, &decode_opts).expect("Decode failed");
     assert_eq!(*value, decoded, "Roundtrip failed");
@@ -0,0 +1,217 @@
+use serde_json::json;
+use toon::schema::{infer_schema, InferOptions, Schema, SchemaInferrer, DRAFT_2020_12};
+use toon::{decode_toon_to_json, encode_json_to_toon, DecodeOptions, EncodeOptions};
+
+#[test]
+fn test_types_ranges_and_required_keys() {
+    let docs = [
+        json!({"name": "a", "port": 80, "ratio": 0.5, "debug": true}),
+        json!({"name": "b", "port": 8080, "ratio": 2, "tags": ["x"]}),
+    ];
+    let schema = infer_schema(&docs, &InferOptions::default());
+    assert_eq!(
+        schema,
+        json!({
+            "$schema": DRAFT_2020_12,
+            "type": "object",
+            "properties": {
+                "debug": {"type": "boolean"},
+                "name": {"type": "string"},
+                "port": {"type": "integer", "minimum": 80, "maximum": 8080},
+                "ratio": {"type": "number", "minimum": 0.5, "maximum": 2},
+                "tags": {"type": "array", "items": {"type": "string"}}
+            },
+            "required": ["name", "port", "ratio"]
+        })
+    );
+}
+
+#[test]
+fn test_enum_candidates() {
+    let docs = [json!({"level": "info", "msg": "started", "code": null})];
+    let mut inferrer = SchemaInferrer::new(InferOptions { enum_limit: 2 });
+    inferrer.observe(&docs[0]);
+    for (level, msg, code) in [("warn", "slow", "E1"), ("info", "done", "E1")] {
+        inferrer.observe(&json!({"level": level, "msg": msg, "code": code}));
+    }
+    let schema = inferrer.schema();
+
+    // Repeated values from a small set become an enum, with null if seen
+    assert_eq!(
+        schema["properties"]["level"],
+        json!({"type": "string", "enum": ["info", "warn"]})
+    );
+    assert_eq!(
+        schema["properties"]["code"],
+        json!({"type": ["null", "string"], "enum": ["E1", null]})
+    );
+    // Three distinct messages exceed the limit
+    assert_eq!(schema["properties"]["msg"], json!({"type": "string"}));
+
+    // Unique values and mixed types give no enum
+    let schema = infer_schema(&[json!(["a", "b", 1, "a"])], &InferOptions::default());
+    assert_eq!(
+        schema["items"],
+        json!({"type": ["integer", "string"], "minimum": 1, "maximum": 1})
+    );
+}
+
+#[test]
+fn test_tabular_rows_are_closed_objects() {
+    let doc = json!({
+        "users": [{"id": 1, "name": "Ada"}, {"id": 2, "name": null}],
+        "events": [{"id": 1}, {"id": 2, "extra": true}]
+    });
+    let schema = infer_schema(&[doc], &InferOptions::default());
+    let users = &schema["properties"]["users"]["items"];
+    assert_eq!(users["additionalProperties"], json!(false));
+    assert_eq!(users["required"], json!(["id", "name"]));
+    assert_eq!(
+        users["properties"]["name"]["type"],
+        json!(["null", "string"])
+    );
+
+    let events = &schema["properties"]["events"]["items"];
+    assert!(events.get("additionalProperties").is_none());
+    assert_eq!(events["required"], json!(["id"]));
+}
+
+#[test]
+fn test_no_documents_and_empty_arrays() {
+    assert_eq!(
+        SchemaInferrer::new(InferOptions::default()).schema(),
+        json!({"$schema": DRAFT_2020_12, "not": {}})
+    );
+    let schema = infer_schema(&[json!([])], &InferOptions::default());
+    assert_eq!(schema, json!({"$schema": DRAFT_2020_12, "type": "array"}));
+}
+
+fn user_schema() -> Schema {
+    Schema::new(json!({
+        "$schema": DRAFT_2020_12,
+        "type": "object",
+        "properties": {
+            "users": {"type": "array", "items": {"$ref": "#/$defs/user"}, "minItems": 1}
+        },
+        "required": ["users"],
+        "$defs": {
+            "user": {
+                "type": "object",
+                "properties": {
+                    "id": {"type": "integer", "minimum": 1},
+                    "name": {"type": "string", "maxLength": 8},
+                    "role": {"enum": ["admin", "user"]}
+                },
+                "required": ["id", "name"],
+                "additionalProperties": false
+            }
+        }
+    }))
+    .unwrap()
+}
+
+#[test]
+fn test_violations_have_pointer_paths() {
+    let schema = user_schema();
+    assert!(schema.is_valid(&json!({"users": [{"id": 1, "name": "Ada", "role": "admin"}]})));
+
+    let doc = json!({"users": [
+        {"id": 1.0, "name": "Ada"},
+        {"id": 0, "name": "Grace Hopper", "role": "root", "a/b": 1}
+    ]});
+    let violations: Vec<String> = schema
+        .validate(&doc)
+        .iter()
+        .map(|v| v.to_string())
+        .collect();
+    assert_eq!(
+        violations,
+        [
+            "/users/1/a~1b: property is not allowed",
+            "/users/1/id: 0 is less than the minimum of 1",
+            "/users/1/name: string is longer than 8 characters",
+            "/users/1/role: \"root\" is not one of [\"admin\",\"user\"]",
+        ]
+    );
+    let violations: Vec<String> = schema
+        .validate(&json!([]))
+        .iter()
+        .map(|v| v.to_string())
+        .collect();
+    assert_eq!(violations, ["/: expected object, found array"]);
+}
+
+#[test]
+fn test_applicators() {
+    let schema = Schema::new(json!({
+        "oneOf": [{"type": "integer"}, {"type": "number", "maximum": 1}],
+        "not": {"const": 0},
+        "if": {"minimum": 10},
+        "then": {"multipleOf": 5}
+    }))
+    .unwrap();
+    assert!(schema.is_valid(&json!(2)));
+    assert!(schema.is_valid(&json!(0.5)));
+    assert!(schema.is_valid(&json!(15)));
+    assert_eq!(schema.validate(&json!(1)).len(), 1); // matches both oneOf branches
+    assert_eq!(schema.validate(&json!(0.0)).len(), 2); // and also the const
+    assert_eq!(
+        schema.validate(&json!(12))[0].message,
+        "12 is not a multiple of 5"
+    );
+
+    let tuple = Schema::new(json!({
+        "prefixItems": [{"type": "string"}],
+        "items": {"type": "integer"},
+        "contains": {"const": 3},
+        "maxContains": 1,
+        "uniqueItems": true
+    }))
+    .unwrap();
+    assert!(tuple.is_valid(&json!(["a", 1, 3])));
+    assert!(!tuple.is_valid(&json!(["a", 1, 2])));
+    assert!(!tuple.is_valid(&json!(["a", 3, 3])));
+    assert!(!tuple.is_valid(&json!([1, 3])));
+}
+
+#[test]
+fn test_unsupported_schemas_are_rejected() {
+    let err = Schema::new(json!({"properties": {"a": {"pattern": "^x"}}})).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Unsupported schema keyword at #/properties/a/pattern"
+    );
+    assert!(Schema::new(json!({"$ref": "#/$defs/missing"})).is_err());
+    assert!(Schema::new(json!({"$ref": "other.json"})).is_err());
+    assert!(Schema::new(json!({"type": "float"})).is_err());
+
+    // A recursive $ref that never consumes input stops at the depth limit
+    let schema = Schema::new(json!({"$ref": "#"})).unwrap();
+    assert!(!schema.is_valid(&json!(1)));
+}
+
+#[test]
+fn test_encode_and_decode_options_check_the_schema() {
+    let good = json!({"users": [{"id": 1, "name": "Ada"}]});
+    let bad = json!({"users": [{"id": 1}]});
+    let encode = EncodeOptions {
+        schema: Some(user_schema()),
+        ..Default::default()
+    };
+    let decode = DecodeOptions {
+        schema: Some(user_schema()),
+        ..Default::default()
+    };
+
+    let text = encode_json_to_toon(&good, &encode).unwrap();
+    assert_eq!(decode_toon_to_json(&text, &decode).unwrap(), good);
+
+    let err = encode_json_to_toon(&bad, &encode).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Document does not match the schema:\n  /users/0: missing required property \"name\""
+    );
+    let text = encode_json_to_toon(&bad, &EncodeOptions::default()).unwrap();
+    assert!(decode_toon_to_json(&text, &decode).is_err());
+    assert!(toon::to_string(&bad, &encode).is_err());
+}
@@ -0,0 +1,291 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use toon::{
+    decode_toon_to_json, from_slice, from_str, to_string, to_writer, DecodeOptions, EncodeOptions,
+};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct User {
+    id: u32,
+    name: String,
+    email: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+enum Shape {
+    Point,
+    Circle(f64),
+    Rect { w: u32, h: u32 },
+    Line(i32, i32),
+}
+
+fn users() -> Vec<User> {
+    vec![
+        User {
+            id: 1,
+            name: "Alice".to_string(),
+            email: Some("alice@example.com".to_string()),
+        },
+        User {
+            id: 2,
+            name: "Bob Smith".to_string(),
+            email: None,
+        },
+    ]
+}
+
+#[test]
+fn test_struct_fields_sorted() {
+    let text = to_string(&users()[0], &EncodeOptions::default()).unwrap();
+    assert_eq!(
+        text,
+        "{\n  email: alice@example.com,\n  id: 1,\n  name: Alice\n}"
+    );
+}
+
+#[test]
+fn test_vec_of_structs_tabular() {
+    let opts = EncodeOptions {
+        tabular_arrays: true,
+        ..Default::default()
+    };
+
+    let text = to_string(&users(), &opts).unwrap();
+    assert_eq!(
+        text,
+        "[\n  # email, id, name\n  alice@example.com, 1, Alice,\n  null, 2, \"Bob Smith\"\n]"
+    );
+
+    let decoded = decode_toon_to_json(text.as_bytes(), &DecodeOptions::default()).unwrap();
+    assert_eq!(decoded, serde_json::to_value(users()).unwrap());
+}
+
+#[test]
+fn test_enum_representation_matches_json() {
+    let shapes = vec![
+        Shape::Point,
+        Shape::Circle(1.5),
+        Shape::Rect { w: 2, h: 3 },
+        Shape::Line(-1, 1),
+    ];
+    let opts = EncodeOptions {
+        max_width: Some(80),
+        ..Default::default()
+    };
+
+    let text = to_string(&shapes, &opts).unwrap();
+    assert_eq!(
+        text,
+        "[Point, {Circle: 1.5}, {Rect: {h: 3, w: 2}}, {Line: [-1, 1]}]"
+    );
+
+    let decoded = decode_toon_to_json(text.as_bytes(), &DecodeOptions::default()).unwrap();
+    assert_eq!(decoded, serde_json::to_value(&shapes).unwrap());
+}
+
+#[test]
+fn test_integer_map_keys() {
+    let mut map = BTreeMap::new();
+    map.insert(10, "ten");
+    map.insert(2, "two");
+
+    let text = to_string(&map, &EncodeOptions::default()).unwrap();
+    let decoded = decode_toon_to_json(text.as_bytes(), &DecodeOptions::default()).unwrap();
+    assert_eq!(decoded, json!({"10": "ten", "2": "two"}));
+}
+
+#[test]
+fn test_non_finite_floats_become_null() {
+    let text = to_string(&[f64::NAN, 1.0], &EncodeOptions::default()).unwrap();
+    assert_eq!(text, "[\n  null,\n  1.0\n]");
+}
+
+#[test]
+fn test_strict_tabular_rejects_mixed_rows() {
+    #[derive(Serialize)]
+    #[serde(untagged)]
+    enum Row {
+        A { a: u8 },
+        B { b: u8 },
+    }
+
+    let opts = EncodeOptions {
+        tabular_arrays: true,
+        strict: true,
+        ..Default::default()
+    };
+    assert!(to_string(&vec![Row::A { a: 1 }, Row::B { b: 2 }], &opts).is_err());
+}
+
+#[test]
+fn test_to_writer() {
+    let mut out = Vec::new();
+    to_writer(&mut out, &json!({"a": [1, 2]}), &EncodeOptions::default()).unwrap();
+    assert_eq!(out, b"{\n  a: [\n    1,\n    2\n  ]\n}");
+
+    // Nested containers are written as the same text that to_string returns
+    let nested = json!({"a": [[1, {"b": [2, 3]}], {"c": {"d": "e"}}], "f": [[[]], {}]});
+    for max_width in [None, Some(12), Some(80)] {
+        let opts = EncodeOptions {
+            max_width,
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        to_writer(&mut out, &nested, &opts).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            to_string(&nested, &opts).unwrap()
+        );
+        assert_eq!(
+            to_string(&nested, &opts).unwrap().into_bytes(),
+            toon::encode_json_to_toon(&nested, &opts).unwrap()
+        );
+    }
+}
+
+#[test]
+fn test_typed_columns_match_value_route() {
+    #[derive(Serialize)]
+    struct Row {
+        id: u64,
+        score: f64,
+        mixed: f64,
+        code: &'static str,
+        note: Option<char>,
+        tags: Vec<u8>,
+    }
+    let rows = vec![
+        Row {
+            id: 1,
+            score: 1.0,
+            mixed: 2.5,
+            code: "007",
+            note: None,
+            tags: vec![1],
+        },
+        Row {
+            id: 2,
+            score: f64::NAN,
+            mixed: 3.0,
+            code: "true",
+            note: Some('x'),
+            tags: vec![],
+        },
+    ];
+    let opts = EncodeOptions {
+        tabular_arrays: true,
+        typed_columns: true,
+        ..Default::default()
+    };
+    let text = to_string(&rows, &opts).unwrap();
+    let value = serde_json::to_value(&rows).unwrap();
+    assert_eq!(
+        text.as_bytes(),
+        toon::encode_json_to_toon(&value, &opts).unwrap()
+    );
+    assert_eq!(
+        text.lines().nth(1).unwrap(),
+        "  # code:str, id:int, mixed:float, note:str?, score:float?, tags"
+    );
+}
+
+#[test]
+fn test_f32_matches_value_route() {
+    #[derive(Serialize)]
+    struct Point {
+        x: f32,
+    }
+    let point = Point { x: 0.1 };
+    let value = serde_json::to_value(&point).unwrap();
+    let expected = toon::encode_json_to_toon(&value, &EncodeOptions::default()).unwrap();
+    let text = to_string(&point, &EncodeOptions::default()).unwrap();
+    assert_eq!(text.as_bytes(), expected);
+    assert_eq!(text, "{\n  x: 0.10000000149011612\n}");
+}
+
+#[test]
+fn test_compact_option_rejected() {
+    let opts = EncodeOptions {
+        compact: true,
+        ..Default::default()
+    };
+    assert!(to_string(&users(), &opts).is_err());
+}
+
+#[test]
+fn test_from_str_roundtrip() {
+    let opts = EncodeOptions {
+        tabular_arrays: true,
+        ..Default::default()
+    };
+    let text = to_string(&users(), &opts).unwrap();
+    let decoded: Vec<User> = from_str(&text).unwrap();
+    assert_eq!(decoded, users());
+
+    let text = to_string(&users(), &EncodeOptions::default()).unwrap();
+    let decoded: Vec<User> = from_slice(text.as_bytes()).unwrap();
+    assert_eq!(decoded, users());
+}
+
+#[test]
+fn test_from_str_enums() {
+    let shapes: Vec<Shape> =
+        from_str("[Point, {Circle: 1.5}, {Rect: {h: 3, w: 2}}, {Line: [-1, 1]}]").unwrap();
+    assert_eq!(
+        shapes,
+        vec![
+            Shape::Point,
+            Shape::Circle(1.5),
+            Shape::Rect { w: 2, h: 3 },
+            Shape::Line(-1, 1),
+        ]
+    );
+}
+
+#[test]
+fn test_from_str_borrows_strings() {
+    #[derive(Deserialize)]
+    struct Borrowed<'a> {
+        name: &'a str,
+        zip: &'a str,
+    }
+
+    let value: Borrowed = from_str("{name: \"Ada Lovelace\", zip: 02134}").unwrap();
+    assert_eq!(value.name, "Ada Lovelace");
+    assert_eq!(value.zip, "02134");
+}
+
+#[test]
+fn test_from_str_integer_map_keys() {
+    let map: BTreeMap<u32, String> = from_str("{10: ten, 2: two}").unwrap();
+    assert_eq!(map[&10], "ten");
+    assert_eq!(map[&2], "two");
+}
+
+#[test]
+fn test_from_str_error_names_path_and_line() {
+    let text = "[\n  # email, id, name\n  null, 1, Alice,\n  null, x, Bob\n]";
+    let err = from_str::<Vec<User>>(text).unwrap_err().to_string();
+    assert!(err.contains("at `[1].id`"), "{}", err);
+    assert!(err.contains("(line 4)"), "{}", err);
+
+    let err = from_str::<User>("{id: 1,\n name: [1]}")
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("at `name`"), "{}", err);
+    assert!(err.contains("(line 2)"), "{}", err);
+}
+
+#[test]
+fn test_from_str_rejects_trailing_input() {
+    assert!(from_str::<u32>("1 2").is_err());
+    assert!(from_str::<Vec<u32>>("[1, 2] x").is_err());
+}
+
+#[test]
+fn test_from_str_tabular_row_width_checked() {
+    let text = "[\n  # id, name\n  1\n]";
+    let err = from_str::<Vec<BTreeMap<String, u32>>>(text).unwrap_err();
+    assert!(err.to_string().contains("Expected 2 cells"), "{}", err);
+}
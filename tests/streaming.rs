@@ -0,0 +1,156 @@
+use serde_json::{json, Value};
+use std::io::{self, Write};
+use toon::{
+    decode_from_reader, encode_json_to_toon, encode_to_writer, DecodeOptions, EncodeOptions,
+};
+
+/// Records the size of every write it receives.
+#[derive(Default)]
+struct RecordingWriter {
+    data: Vec<u8>,
+    largest_write: usize,
+}
+
+impl Write for RecordingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.largest_write = self.largest_write.max(buf.len());
+        self.data.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Fails every write.
+struct BrokenWriter;
+
+impl Write for BrokenWriter {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(io::ErrorKind::BrokenPipe, "pipe closed"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn large_rows() -> Value {
+    let rows: Vec<Value> = (0..20_000)
+        .map(|i| json!({"id": i, "name": format!("user {}", i), "active": i % 2 == 0}))
+        .collect();
+    Value::Array(rows)
+}
+
+fn all_options() -> Vec<EncodeOptions> {
+    let mut options = Vec::new();
+    for tabular_arrays in [false, true] {
+        for compact in [false, true] {
+            options.push(EncodeOptions {
+                tabular_arrays,
+                compact,
+                ..Default::default()
+            });
+        }
+    }
+    options.push(EncodeOptions {
+        max_width: Some(60),
+        ..Default::default()
+    });
+    options
+}
+
+#[test]
+fn test_writer_output_matches_encode() {
+    let value = json!({"users": [{"id": 1, "name": "Alice"}], "tags": ["a", "b c"], "n": null});
+    for opts in all_options() {
+        for input in [&value, &large_rows()] {
+            let mut out = Vec::new();
+            encode_to_writer(&mut out, input, &opts).unwrap();
+            assert_eq!(out, encode_json_to_toon(input, &opts).unwrap(), "{:?}", opts);
+        }
+    }
+}
+
+#[test]
+fn test_large_output_written_in_chunks() {
+    let value = large_rows();
+    for opts in all_options().into_iter().filter(|o| o.max_width.is_none()) {
+        let mut writer = RecordingWriter::default();
+        encode_to_writer(&mut writer, &value, &opts).unwrap();
+        assert!(writer.data.len() > 100_000, "{:?}", opts);
+        assert!(writer.largest_write <= 8 * 1024, "{:?}", opts);
+    }
+}
+
+#[test]
+fn test_write_errors_reported() {
+    let err = encode_to_writer(BrokenWriter, &large_rows(), &EncodeOptions::default()).unwrap_err();
+    assert!(format!("{:#}", err).contains("pipe closed"), "{:#}", err);
+}
+
+#[test]
+fn test_strict_error_before_any_output() {
+    let opts = EncodeOptions {
+        tabular_arrays: true,
+        strict: true,
+        ..Default::default()
+    };
+    let mut out = Vec::new();
+    assert!(encode_to_writer(&mut out, &json!([1, {"a": 1}]), &opts).is_err());
+    assert!(out.is_empty());
+}
+
+/// Yields `data`, then fails every read.
+struct FailAfter<'a>(&'a [u8]);
+
+impl io::Read for FailAfter<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.0.is_empty() {
+            return Err(io::Error::other("read past the end of the document"));
+        }
+        let n = buf.len().min(self.0.len());
+        buf[..n].copy_from_slice(&self.0[..n]);
+        self.0 = &self.0[n..];
+        Ok(n)
+    }
+}
+
+#[test]
+fn test_decode_from_reader_every_format() {
+    let value = large_rows();
+    for opts in all_options() {
+        let encoded = encode_json_to_toon(&value, &opts).unwrap();
+        let decoded = decode_from_reader(encoded.as_slice(), &DecodeOptions::default()).unwrap();
+        assert_eq!(decoded, value, "{:?}", opts);
+    }
+}
+
+#[test]
+fn test_reader_stops_at_end_of_document() {
+    let decoded = decode_from_reader(FailAfter(b"{a: [1, 2]}"), &DecodeOptions::default()).unwrap();
+    assert_eq!(decoded, json!({"a": [1, 2]}));
+}
+
+#[test]
+fn test_reader_errors_name_line() {
+    let err = decode_from_reader(&b"{\n  a: 1,\n  b 2\n}"[..], &DecodeOptions::default())
+        .unwrap_err();
+    assert!(format!("{:#}", err).contains("Expected ':' after object key at line 3"), "{:#}", err);
+
+    let strict = DecodeOptions {
+        strict: true,
+        ..Default::default()
+    };
+    let err = decode_from_reader(&b"{\n  a: 1,\n\tb: 2\n}"[..], &strict).unwrap_err();
+    assert!(format!("{:#}", err).contains("Mixed indentation at line 3"), "{:#}", err);
+}
+
+#[test]
+fn test_reader_rejects_truncated_compact() {
+    let encoded = encode_json_to_toon(&large_rows(), &all_options()[1]).unwrap();
+    let truncated = &encoded[..encoded.len() / 2];
+    assert!(decode_from_reader(truncated, &DecodeOptions::default()).is_err());
+    assert!(decode_from_reader(&b""[..], &DecodeOptions::default()).is_err());
+}
@@ -1,5 +1,10 @@
-use serde_json::json;
-use toon::{decode_toon_to_json, encode_json_to_toon, DecodeOptions, EncodeOptions};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use toon::codec::tabular::{encode_tabular_compact, encode_tabular_text};
+use toon::{
+    decode_toon_to_json, encode_json_to_toon, ColumnType, DecodeOptions, EncodeOptions, Indent,
+    QuotePolicy, TabularReader, TabularWriter,
+};
 
 #[test]
 fn test_uniform_array_tabular_text() {
@@ -134,3 +139,314 @@ fn test_nested_values_in_tabular() {
 
     assert_eq!(value, decoded);
 }
+
+fn people() -> Value {
+    json!([
+        {"id": 1, "name": "Alice", "score": 100.5},
+        {"id": 2, "name": "Bob", "score": null},
+        {"id": 3, "name": "Charlie", "score": 88}
+    ])
+}
+
+#[test]
+fn test_tabular_reader_rows_from_both_formats() {
+    let rows = people();
+    let arr = rows.as_array().unwrap();
+    let text = encode_tabular_text(arr, Indent::default(), QuotePolicy::Minimal, false).unwrap();
+    let compact = encode_tabular_compact(arr).unwrap();
+
+    for encoded in [text, compact] {
+        let mut reader = TabularReader::new(&encoded[..]).unwrap();
+        assert_eq!(reader.columns(), ["id", "name", "score"]);
+        let read: Vec<Value> = reader
+            .by_ref()
+            .map(|row| Value::Object(row.unwrap()))
+            .collect();
+        assert_eq!(Value::Array(read), rows);
+        assert!(reader.next_row().unwrap().is_none());
+    }
+}
+
+#[test]
+fn test_tabular_reader_typed_rows() {
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Person {
+        id: u32,
+        name: String,
+        score: Option<f64>,
+    }
+
+    let encoded = encode_tabular_compact(people().as_array().unwrap()).unwrap();
+    let people: Vec<Person> = TabularReader::new(&encoded[..])
+        .unwrap()
+        .deserialize()
+        .collect::<anyhow::Result<_>>()
+        .unwrap();
+    assert_eq!(people.len(), 3);
+    assert_eq!(
+        people[1],
+        Person {
+            id: 2,
+            name: "Bob".into(),
+            score: None
+        }
+    );
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Strict {
+        id: String,
+    }
+    let err = TabularReader::new(&encoded[..])
+        .unwrap()
+        .deserialize::<Strict>()
+        .next()
+        .unwrap()
+        .unwrap_err();
+    assert!(err.to_string().contains("Invalid tabular row 1"), "{}", err);
+}
+
+#[test]
+fn test_tabular_reader_rejects_other_documents() {
+    assert!(TabularReader::new(&b"{a: 1}"[..]).is_err());
+    assert!(TabularReader::new(&b"[1, 2]"[..]).is_err());
+
+    let empty = TabularReader::new(&b"[]"[..]).unwrap();
+    assert!(empty.columns().is_empty());
+    assert_eq!(empty.count(), 0);
+
+    let mut truncated = TabularReader::new(&b"[\n  # a, b\n  1, 2,\n  3"[..]).unwrap();
+    assert!(truncated.next_row().unwrap().is_some());
+    assert!(truncated.next_row().is_err());
+    assert!(truncated.next_row().unwrap().is_none());
+}
+
+#[test]
+fn test_tabular_writer_matches_batch_encoders() {
+    let rows = people();
+    let arr = rows.as_array().unwrap();
+
+    let mut writer = TabularWriter::text(Vec::new(), Indent::default(), QuotePolicy::Minimal);
+    for row in arr {
+        writer.write_row(row).unwrap();
+    }
+    assert_eq!(
+        writer.finish().unwrap(),
+        encode_tabular_text(arr, Indent::default(), QuotePolicy::Minimal, false).unwrap()
+    );
+
+    let mut writer = TabularWriter::compact(Vec::new());
+    for row in arr {
+        writer.write_row(row).unwrap();
+    }
+    let encoded = writer.finish().unwrap();
+    assert_eq!(
+        decode_toon_to_json(&encoded, &DecodeOptions::default()).unwrap(),
+        rows
+    );
+}
+
+#[test]
+fn test_tabular_writer_explicit_columns() {
+    #[derive(serde::Serialize)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    let columns = vec!["y".to_string(), "x".to_string()];
+    let writer = TabularWriter::compact(Vec::new())
+        .with_columns(columns.clone())
+        .unwrap();
+    let encoded = writer.finish().unwrap();
+    let reader = TabularReader::new(&encoded[..]).unwrap();
+    assert_eq!(reader.columns(), columns);
+    assert_eq!(reader.count(), 0);
+
+    let mut writer = TabularWriter::text(Vec::new(), Indent::default(), QuotePolicy::Minimal)
+        .with_columns(columns)
+        .unwrap();
+    writer.serialize(&Point { x: 1, y: 2 }).unwrap();
+    let err = writer.write_row(&json!({"x": 1})).unwrap_err();
+    assert!(err.to_string().contains("do not match"), "{}", err);
+    assert!(writer.write_row(&json!([1, 2])).is_err());
+    let encoded = writer.finish().unwrap();
+    assert_eq!(
+        String::from_utf8(encoded).unwrap(),
+        "[\n  # y, x\n  2, 1\n]"
+    );
+}
+
+#[test]
+fn test_tabular_writer_column_types() {
+    let rows = json!([
+        {"id": 1, "name": "a", "score": 1.5},
+        {"id": 2, "name": "b", "score": null}
+    ]);
+    let columns = vec!["id".to_string(), "name".to_string(), "score".to_string()];
+    let types = toon::codec::tabular::column_types(rows.as_array().unwrap(), &columns);
+    assert_eq!(types[2], Some("float?".parse::<ColumnType>().unwrap()));
+
+    let mut writer = TabularWriter::text(Vec::new(), Indent::default(), QuotePolicy::Minimal)
+        .with_columns(columns.clone())
+        .unwrap()
+        .with_column_types(types.clone())
+        .unwrap();
+    for row in rows.as_array().unwrap() {
+        writer.write_row(row).unwrap();
+    }
+    let err = writer
+        .write_row(&json!({"id": "3", "name": "c", "score": 2}))
+        .unwrap_err();
+    assert!(err.to_string().contains("not of type int"), "{}", err);
+    assert_eq!(writer.rows_written(), 2);
+    let encoded = writer.finish().unwrap();
+    assert_eq!(
+        encoded,
+        encode_json_to_toon(&rows, &typed_options()).unwrap()
+    );
+
+    let writer = TabularWriter::text(Vec::new(), Indent::default(), QuotePolicy::Minimal);
+    assert!(writer.with_column_types(types.clone()).is_err());
+    let writer = TabularWriter::compact(Vec::new())
+        .with_columns(columns)
+        .unwrap();
+    assert!(writer.with_column_types(types).is_err());
+}
+
+#[test]
+fn test_tabular_writer_appends_to_existing_document() {
+    let rows = people();
+    let arr = rows.as_array().unwrap();
+
+    // Start from the batch encoder's output, which has a fixed row count
+    let file = std::io::Cursor::new(encode_tabular_compact(&arr[..1]).unwrap());
+    let mut writer = TabularWriter::append(file).unwrap();
+    writer.write_row(&arr[1]).unwrap();
+    let file = writer.finish().unwrap();
+
+    let mut writer = TabularWriter::append(file).unwrap();
+    writer.write_row(&arr[2]).unwrap();
+    assert!(writer.write_row(&json!({"id": 4})).is_err());
+    assert_eq!(writer.rows_written(), 1);
+    let encoded = writer.finish().unwrap().into_inner();
+
+    assert_eq!(
+        decode_toon_to_json(&encoded, &DecodeOptions::default()).unwrap(),
+        rows
+    );
+
+    let text = std::io::Cursor::new(b"[\n  # id\n  1\n]".to_vec());
+    assert!(TabularWriter::append(text).is_err());
+}
+
+fn typed_options() -> EncodeOptions {
+    EncodeOptions {
+        tabular_arrays: true,
+        typed_columns: true,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_typed_header_from_homogeneous_columns() {
+    let rows = json!([
+        {"id": 1, "zip": "007", "score": 1.5, "ok": true, "tags": [1], "mixed": 1},
+        {"id": 2, "zip": "42", "score": null, "ok": false, "tags": [], "mixed": "a"}
+    ]);
+    let encoded = encode_json_to_toon(&rows, &typed_options()).unwrap();
+    let text = String::from_utf8(encoded.clone()).unwrap();
+    assert_eq!(
+        text.lines().nth(1).unwrap(),
+        "  # id:int, mixed, ok:bool, score:float?, tags, zip:str"
+    );
+    assert_eq!(toon::to_string(&rows, &typed_options()).unwrap(), text);
+
+    let strict = DecodeOptions {
+        strict: true,
+        ..Default::default()
+    };
+    assert_eq!(decode_toon_to_json(&encoded, &strict).unwrap(), rows);
+
+    // Serde reads typed headers too
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Row {
+        id: u32,
+        zip: String,
+    }
+    let typed: Vec<Row> = toon::from_str("[\n  # id:int, zip:str\n  1, \"007\"\n]").unwrap();
+    assert_eq!(typed[0].zip, "007");
+}
+
+#[test]
+fn test_strict_decoding_checks_column_types() {
+    let text = b"[\n  # id:int, name:str?\n  1, Ada,\n  2.5, null\n]";
+    let strict = DecodeOptions {
+        strict: true,
+        ..Default::default()
+    };
+    let err = decode_toon_to_json(text, &strict).unwrap_err();
+    assert_eq!(
+        format!("{:#}", err),
+        "Failed to decode text TOON: Column \"id\" is int, but the cell is 2.5 at line 4"
+    );
+    // Without strict mode the types are only read
+    let value = decode_toon_to_json(text, &DecodeOptions::default()).unwrap();
+    assert_eq!(
+        value,
+        json!([{"id": 1, "name": "Ada"}, {"id": 2.5, "name": null}])
+    );
+
+    let err = decode_toon_to_json(b"[\n  # \"id\":number\n  1\n]", &DecodeOptions::default());
+    assert!(err.is_err());
+}
+
+#[test]
+fn test_header_keys_with_colons() {
+    // Without a type name after it, a colon is part of the key, as in
+    // headers written before typed columns
+    let text = "[\n  # a:b, c, id:number\n  1, x, 2\n]";
+    let expected = json!([{"a:b": 1, "c": "x", "id:number": 2}]);
+    assert_eq!(
+        decode_toon_to_json(text.as_bytes(), &DecodeOptions::default()).unwrap(),
+        expected
+    );
+    assert_eq!(toon::from_str::<Value>(text).unwrap(), expected);
+    let reader = TabularReader::new(text.as_bytes()).unwrap();
+    assert_eq!(reader.columns(), ["a:b", "c", "id:number"]);
+    assert_eq!(reader.column_types(), [None, None, None]);
+
+    // The last colon before a type name starts the type
+    let text = "[\n  # a:b:int, \"c:d\":str\n  1, x\n]";
+    let reader = TabularReader::new(text.as_bytes()).unwrap();
+    assert_eq!(reader.columns(), ["a:b", "c:d"]);
+    assert_eq!(
+        decode_toon_to_json(text.as_bytes(), &DecodeOptions::default()).unwrap(),
+        json!([{"a:b": 1, "c:d": "x"}])
+    );
+    assert_eq!(
+        toon::from_str::<Value>(text).unwrap(),
+        json!([{"a:b": 1, "c:d": "x"}])
+    );
+}
+
+#[test]
+fn test_column_types_from_reader_and_fmt() {
+    let text = "[\n  # name:str?, id:float\n  Ada, 1\n]\n";
+    let reader = TabularReader::new(text.as_bytes()).unwrap();
+    assert_eq!(reader.columns(), ["name", "id"]);
+    let types: Vec<String> = reader
+        .column_types()
+        .iter()
+        .map(|ty| ty.unwrap().to_string())
+        .collect();
+    assert_eq!(types, ["str?", "float"]);
+
+    // Formatting sorts the columns but keeps the declared types
+    let formatted = toon::format_toon(text.as_bytes(), Indent::default()).unwrap();
+    assert_eq!(formatted, b"[\n  # id:float, name:str?\n  1, Ada\n]\n");
+
+    let compact = encode_tabular_compact(people().as_array().unwrap()).unwrap();
+    let reader = TabularReader::new(&compact[..]).unwrap();
+    assert_eq!(reader.column_types(), [None, None, None]);
+}
@@ -0,0 +1,68 @@
+use serde_json::json;
+use toon::{decode_toon_to_json, encode_json_to_toon, DecodeOptions, EncodeOptions, QuotePolicy};
+
+fn decode(input: &str) -> anyhow::Result<serde_json::Value> {
+    decode_toon_to_json(input.as_bytes(), &DecodeOptions::default())
+}
+
+#[test]
+fn test_surrogate_pair_escape() {
+    assert_eq!(decode(r#""\ud83d\ude00""#).unwrap(), json!("😀"));
+    assert_eq!(decode(r#""a\ud834\udd1eb""#).unwrap(), json!("a𝄞b"));
+}
+
+#[test]
+fn test_bmp_escape() {
+    assert_eq!(decode(r#""caf\u00e9""#).unwrap(), json!("café"));
+}
+
+#[test]
+fn test_lone_high_surrogate_rejected() {
+    for input in [r#""\ud83d""#, r#""\ud83dx""#, r#""\ud83dA""#] {
+        let err = decode(input).unwrap_err();
+        assert!(format!("{:#}", err).contains("Lone surrogate \\ud83d"), "{:#}", err);
+    }
+}
+
+#[test]
+fn test_lone_low_surrogate_rejected() {
+    let err = decode(r#""\ude00""#).unwrap_err();
+    assert!(format!("{:#}", err).contains("Lone surrogate \\ude00"), "{:#}", err);
+}
+
+#[test]
+fn test_short_escape_rejected() {
+    assert!(decode(r#""\u12""#).is_err());
+    assert!(decode(r#""\u12zz""#).is_err());
+}
+
+#[test]
+fn test_ascii_output() {
+    let value = json!({"greeting": "héllo 😀", "plain": "abc"});
+    let opts = EncodeOptions {
+        quoting: QuotePolicy::Ascii,
+        ..Default::default()
+    };
+
+    let encoded = encode_json_to_toon(&value, &opts).unwrap();
+    assert!(encoded.is_ascii());
+    assert_eq!(
+        String::from_utf8(encoded.clone()).unwrap(),
+        "{\n  greeting: \"h\\u00e9llo \\ud83d\\ude00\",\n  plain: abc\n}"
+    );
+    assert_eq!(decode_toon_to_json(&encoded, &DecodeOptions::default()).unwrap(), value);
+}
+
+#[test]
+fn test_ascii_output_escapes_keys_and_tabular_headers() {
+    let value = json!([{"名前": "Ωmega"}, {"名前": "x"}]);
+    let opts = EncodeOptions {
+        quoting: QuotePolicy::Ascii,
+        tabular_arrays: true,
+        ..Default::default()
+    };
+
+    let encoded = encode_json_to_toon(&value, &opts).unwrap();
+    assert!(encoded.is_ascii());
+    assert_eq!(decode_toon_to_json(&encoded, &DecodeOptions::default()).unwrap(), value);
+}
@@ -0,0 +1,130 @@
+use serde_json::{json, Value};
+use toon::codec::compact;
+use toon::CompactView;
+
+fn sample() -> Value {
+    json!({
+        "id": 42,
+        "name": "Ada",
+        "ratio": 0.5,
+        "active": true,
+        "parent": null,
+        "tags": ["x", "y", {"deep": [1, 2, 3]}],
+        "empty": {}
+    })
+}
+
+#[test]
+fn test_lookups_borrow_from_the_buffer() {
+    let bytes = compact::encode(&sample()).unwrap();
+    let doc = CompactView::new(&bytes).unwrap();
+
+    assert!(doc.is_object());
+    assert_eq!(doc.len(), Some(7));
+    assert_eq!(doc.get("id").and_then(|v| v.as_i64()), Some(42));
+    assert_eq!(doc.get("id").and_then(|v| v.as_number()), Some("42"));
+    assert_eq!(doc.get("ratio").and_then(|v| v.as_f64()), Some(0.5));
+    assert_eq!(doc.get("active").and_then(|v| v.as_bool()), Some(true));
+    assert!(doc.get("parent").unwrap().is_null());
+    assert!(doc.get("missing").is_none());
+
+    let name = doc.get("name").and_then(|v| v.as_str()).unwrap();
+    assert_eq!(name, "Ada");
+    let range = bytes.as_ptr_range();
+    assert!(range.contains(&name.as_ptr()));
+
+    let tags = doc.get("tags").unwrap();
+    assert!(tags.is_array());
+    assert_eq!(tags.index(1).and_then(|v| v.as_str()), Some("y"));
+    assert!(tags.index(3).is_none());
+    let deep = tags.index(2).and_then(|v| v.get("deep")).unwrap();
+    assert_eq!(deep.to_value(), json!([1, 2, 3]));
+    assert_eq!(doc.get("empty").and_then(|v| v.is_empty()), Some(true));
+
+    // Lookups of the wrong type return None
+    assert!(doc.index(0).is_none());
+    assert!(tags.get("x").is_none());
+    assert!(doc.get("name").unwrap().as_i64().is_none());
+}
+
+#[test]
+fn test_iteration_and_to_value() {
+    let bytes = compact::encode(&sample()).unwrap();
+    let doc = CompactView::new(&bytes).unwrap();
+
+    let keys: Vec<&str> = doc.entries().unwrap().map(|(k, _)| k).collect();
+    assert_eq!(
+        keys,
+        ["active", "empty", "id", "name", "parent", "ratio", "tags"]
+    );
+    let tags: Vec<Value> = doc
+        .get("tags")
+        .unwrap()
+        .elements()
+        .unwrap()
+        .map(|v| v.to_value())
+        .collect();
+    assert_eq!(tags, sample()["tags"].as_array().unwrap().clone());
+    assert_eq!(doc.to_value(), sample());
+    assert_eq!(
+        doc.get("tags").unwrap().raw(),
+        &compact::encode(&sample()["tags"]).unwrap()[5..]
+    );
+}
+
+#[test]
+fn test_bytes_from_serde() {
+    struct Bytes<'a>(&'a [u8]);
+    impl serde::Serialize for Bytes<'_> {
+        fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+            s.serialize_bytes(self.0)
+        }
+    }
+
+    let bytes = toon::compact::to_vec(&Bytes(&[1, 2, 255])).unwrap();
+    let doc = CompactView::new(&bytes).unwrap();
+    assert_eq!(doc.as_bytes(), Some(&[1u8, 2, 255][..]));
+    assert_eq!(doc.to_value(), json!([1, 2, 255]));
+}
+
+#[test]
+fn test_validation_rejects_malformed_documents() {
+    let bytes = compact::encode(&sample()).unwrap();
+    assert!(CompactView::new(&bytes[..bytes.len() - 1]).is_err());
+    assert!(CompactView::new(&bytes[1..]).is_err());
+
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    let err = CompactView::new(&trailing).unwrap_err();
+    assert!(err.to_string().contains("Trailing bytes"), "{}", err);
+
+    // A string with invalid UTF-8
+    let mut bad = b"TOON\x01\x04".to_vec();
+    bad.extend_from_slice(&2u32.to_le_bytes());
+    bad.extend_from_slice(&[0xff, 0xfe]);
+    assert!(CompactView::new(&bad).is_err());
+
+    // An unparsable number
+    let mut bad = b"TOON\x01\x03".to_vec();
+    bad.extend_from_slice(&3u32.to_le_bytes());
+    bad.extend_from_slice(b"abc");
+    assert!(CompactView::new(&bad).is_err());
+}
+
+#[test]
+fn test_deep_nesting_does_not_overflow() {
+    let depth = 200_000;
+    let mut bytes = b"TOON\x01".to_vec();
+    for _ in 0..depth {
+        bytes.push(5);
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+    }
+    bytes.push(0);
+
+    let doc = CompactView::new(&bytes).unwrap();
+    let mut view = doc;
+    for _ in 0..10 {
+        view = view.index(0).unwrap();
+    }
+    assert!(view.is_array());
+}